@@ -1,4 +1,5 @@
 use crate::{
+    json_rpc::{error_codes, JsonRpcError},
     methods::{Method, MethodError},
     utils::HexU32Be,
 };
@@ -7,6 +8,9 @@ use crate::{
 #[non_exhaustive]
 pub enum Error<'a> {
     BadBytesConvert(binary_sv2::Error),
+    /// Errors if a byte field has the wrong length: (`field`, expected length, actual length).
+    /// Returned by [`try_b032`](crate::utils::try_b032) and [`try_u256`](crate::utils::try_u256).
+    InvalidLength(&'static str, &'static str, usize),
     BTCHashError(bitcoin_hashes::Error),
     /// Errors on bad hex decode/encode.
     HexError(hex::FromHexError),
@@ -31,6 +35,30 @@ pub enum Error<'a> {
     /// Errors if server does not recognize the client's `id`.
     UnknownID(u64),
     InvalidVersionMask(HexU32Be),
+    /// Errors on `json_rpc` message serialization.
+    Json(serde_json::Error),
+    /// Errors if a `mining.set_difficulty` difficulty is not a positive finite number.
+    InvalidDifficulty(f64),
+    /// Errors if the `result` of a `mining.subscribe` response is not
+    /// `[subscriptions, extranonce1, extranonce2_size]`.
+    InvalidSubscribeResult(serde_json::Value),
+    /// Errors reported by the remote peer in a `json_rpc` error response that do not map to a
+    /// more specific variant: (`code`, `message`).
+    Rpc(i32, String),
+    /// Errors if the peer answered with the `json_rpc` method not found error code, it does not
+    /// support the method of the request. The message of the error is given.
+    RpcMethodNotFound(String),
+    /// Errors if no message has been read from the peer within the given time.
+    Timeout(std::time::Duration),
+    /// Errors if a batch of `json_rpc` responses contains more than one response with this id.
+    DuplicateId(u64),
+}
+
+impl<'a> Error<'a> {
+    /// Returns true for [`Error::Timeout`], the connection may still be alive.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, Error::Timeout(_))
+    }
 }
 
 impl<'a> std::fmt::Display for Error<'a> {
@@ -41,6 +69,11 @@ impl<'a> std::fmt::Display for Error<'a> {
                 "Bad U256 or B032 conversion (U256 length must be exactly 32 bytes; B032 length must be <= 32 bytes): {:?}", 
                 e
             ),
+            Error::InvalidLength(field, expected, actual) => write!(
+                f,
+                "Invalid `{}` length: expected {} bytes, got {} bytes",
+                field, expected, actual
+            ),
             Error::BTCHashError(ref e) => write!(f, "Bitcoin Hashes Error: `{:?}`", e),
             Error::HexError(ref e) => write!(f, "Bad hex encode/decode: `{:?}`", e),
             Error::IncorrectClientStatus(s) => {
@@ -73,6 +106,27 @@ impl<'a> std::fmt::Display for Error<'a> {
                 id
             ),
             Error::UnknownID(e) => write!(f, "Server did not recognize the client id: `{}`.", e),
+            Error::InvalidSubscribeResult(v) => write!(
+                f,
+                "Invalid `mining.subscribe` result, expected `[subscriptions, extranonce1, extranonce2_size]`: `{}`",
+                v
+            ),
+            Error::Rpc(code, message) => {
+                write!(f, "Peer returned `json_rpc` error {}: `{}`", code, message)
+            }
+            Error::RpcMethodNotFound(message) => {
+                write!(f, "Peer does not support the requested method: `{}`", message)
+            }
+            Error::Json(ref e) => write!(f, "Json serialization error: `{}`", e),
+            Error::InvalidDifficulty(d) => write!(
+                f,
+                "Difficulty must be a positive finite number, got `{}`",
+                d
+            ),
+            Error::Timeout(d) => write!(f, "Timed out after {:?} waiting for the peer", d),
+            Error::DuplicateId(id) => {
+                write!(f, "Batch contains more than one response with id `{}`", id)
+            }
             Error::InvalidVersionMask(e) => write!(f, "First 3 bits of version rolling mask must be 0 and last 13 bits of version rolling mask must be 0. Version rolling mask is: `{:b}`.", e.0),
         }
     }
@@ -90,6 +144,12 @@ impl<'a> From<hex::FromHexError> for Error<'a> {
     }
 }
 
+impl<'a> From<serde_json::Error> for Error<'a> {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
 impl<'a> From<std::convert::Infallible> for Error<'a> {
     fn from(e: std::convert::Infallible) -> Self {
         Error::Infallible(e)
@@ -107,3 +167,59 @@ impl<'a> From<binary_sv2::Error> for Error<'a> {
         Error::BadBytesConvert(inner)
     }
 }
+
+impl<'a> From<JsonRpcError> for Error<'a> {
+    fn from(e: JsonRpcError) -> Self {
+        match e.code {
+            error_codes::METHOD_NOT_FOUND => Error::RpcMethodNotFound(e.message),
+            code => Error::Rpc(code, e.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_error_method_not_found() {
+        let rpc_error = JsonRpcError {
+            code: error_codes::METHOD_NOT_FOUND,
+            message: "mining.suggest_difficulty".to_string(),
+            data: None,
+        };
+        match Error::from(rpc_error) {
+            Error::RpcMethodNotFound(m) => assert_eq!(m, "mining.suggest_difficulty"),
+            e => panic!("expected RpcMethodNotFound, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_rpc_error_custom_pool_code() {
+        let rpc_error = JsonRpcError {
+            code: 23,
+            message: "Low difficulty share".to_string(),
+            data: None,
+        };
+        match Error::from(rpc_error) {
+            Error::Rpc(code, message) => {
+                assert_eq!(code, 23);
+                assert_eq!(message, "Low difficulty share");
+            }
+            e => panic!("expected Rpc, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let error = Error::Timeout(std::time::Duration::from_secs(30));
+        assert!(error.is_timeout());
+        assert!(matches!(error, Error::Timeout(d) if d.as_secs() == 30));
+        assert_eq!(
+            error.to_string(),
+            "Timed out after 30s waiting for the peer"
+        );
+
+        assert!(!Error::InvalidSubmission.is_timeout());
+    }
+}
@@ -1,5 +1,14 @@
 //! https://www.jsonrpc.org/specification#response_object
+use crate::{
+    error::Error,
+    methods::{Method, MethodError},
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    convert::TryFrom,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(untagged)]
@@ -21,6 +30,30 @@ impl Message {
         }
     }
 
+    /// Serializes the message to a byte-stable JSON string, as needed to sign request bodies.
+    ///
+    /// Object keys are emitted in lexicographic order at every level, both for the message itself
+    /// and for any object inside `params`, and no whitespace is added.
+    #[allow(clippy::result_large_err)]
+    pub fn to_canonical_string(&self) -> Result<String, Error<'static>> {
+        let mut out = String::new();
+        write_canonical(&serde_json::to_value(self)?, &mut out)?;
+        Ok(out)
+    }
+
+    /// Parses the message into the Sv1 [`Method`] named by its `method` field (or into a response
+    /// for `OkResponse`/`ErrorResponse`).
+    ///
+    /// Unknown method names and malformed params are returned as [`Error::Method`], a method that
+    /// was not expected on this side of the connection as [`Error::InvalidReceiver`].
+    #[allow(clippy::result_large_err)]
+    pub fn into_method(self) -> Result<Method<'static>, Error<'static>> {
+        Method::try_from(self).map_err(|e| match e {
+            MethodError::UnexpectedMethod(method) => Error::InvalidReceiver(method),
+            e => Error::Method(e),
+        })
+    }
+
     //pub fn error(&self) -> Option<JsonRpcError> {
     //    match self {
     //        Message::Response(r) => r.error.clone(),
@@ -29,33 +62,249 @@ impl Message {
     //}
 }
 
+// The wire names of the fields are given explicitly with `serde(rename)`, so that renaming a field
+// does not change the messages sent to the peer. They are all lowercase single words, as every SV1
+// implementation expects, so there is no `rename_all` casing to choose.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct StandardRequest {
+    #[serde(rename = "id")]
     pub id: u64,
+    #[serde(rename = "method")]
     pub method: String,
+    #[serde(rename = "params")]
     pub params: serde_json::Value,
 }
 
+impl StandardRequest {
+    /// Drops the id, turning the request into a notification with the same method and params.
+    pub fn into_notification(self) -> Notification {
+        Notification {
+            method: self.method,
+            params: self.params,
+        }
+    }
+
+    /// Returns the param called `name`, if `params` is an object (named params).
+    pub fn param(&self, name: &str) -> Option<&serde_json::Value> {
+        self.params.as_object()?.get(name)
+    }
+
+    /// Returns the param at position `index`, if `params` is an array (positional params).
+    pub fn param_at(&self, index: usize) -> Option<&serde_json::Value> {
+        self.params.as_array()?.get(index)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Notification {
+    #[serde(rename = "method")]
     pub method: String,
+    #[serde(rename = "params")]
     pub params: serde_json::Value,
 }
 
+impl Notification {
+    /// Turns the notification into a request with id `id` and the same method and params.
+    pub fn into_request(self, id: Id) -> StandardRequest {
+        StandardRequest {
+            id,
+            method: self.method,
+            params: self.params,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Response {
+    #[serde(rename = "id")]
     pub id: u64,
+    #[serde(rename = "error")]
     pub error: Option<JsonRpcError>,
+    #[serde(rename = "result")]
     pub result: serde_json::Value,
 }
 
+impl Response {
+    /// Builds a successful response, it always converts to [`Message::OkResponse`].
+    pub fn ok(id: u64, result: serde_json::Value) -> Self {
+        Response {
+            id,
+            error: None,
+            result,
+        }
+    }
+
+    /// Builds an error response with a `null` result, it always converts to
+    /// [`Message::ErrorResponse`].
+    pub fn err(id: u64, error: JsonRpcError) -> Self {
+        Response {
+            id,
+            error: Some(error),
+            result: serde_json::Value::Null,
+        }
+    }
+
+    /// Returns the response with the error, if any, in the SV1 `[code, message, data]` array
+    /// form expected by miners, see [`JsonRpcError::to_sv1_value`].
+    pub fn to_sv1_value(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "error": self.error.as_ref().map(JsonRpcError::to_sv1_value),
+            "result": self.result,
+        })
+    }
+}
+
+/// Checks that every response of a batch has a different id, the client could not match them to
+/// its requests otherwise.
+///
+/// Returns [`Error::DuplicateId`] with the first repeated id.
+#[allow(clippy::result_large_err)]
+pub fn validate_batch_ids(responses: &[Response]) -> Result<(), Error<'static>> {
+    let mut seen = std::collections::HashSet::with_capacity(responses.len());
+    match responses.iter().find(|response| !seen.insert(response.id)) {
+        Some(response) => Err(Error::DuplicateId(response.id)),
+        None => Ok(()),
+    }
+}
+
+/// Serializes as the JSON-RPC error object, use [`JsonRpcError::to_sv1_value`] for the SV1
+/// `[code, message, data]` array. Both forms are accepted when deserializing.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct JsonRpcError {
+    #[serde(rename = "code")]
     pub code: i32, // json do not specify precision which one should be used?
+    #[serde(rename = "message")]
     pub message: String,
+    #[serde(rename = "data")]
     pub data: Option<serde_json::Value>,
 }
 
+/// SV1 `json_rpc` error codes conventionally used by pools to reject a request.
+pub mod error_codes {
+    pub const OTHER: i32 = 20;
+    pub const JOB_NOT_FOUND: i32 = 21;
+    pub const DUPLICATE_SHARE: i32 = 22;
+    pub const LOW_DIFFICULTY_SHARE: i32 = 23;
+    pub const UNAUTHORIZED_WORKER: i32 = 24;
+    pub const NOT_SUBSCRIBED: i32 = 25;
+
+    // Codes defined by the JSON-RPC 2.0 specification.
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+    /// Range reserved to implementation-defined server errors.
+    pub const SERVER_ERROR_RANGE: core::ops::RangeInclusive<i32> = -32099..=-32000;
+}
+
+/// SV2 error codes (share rejects and `SetupConnection.Error`) and the SV1 `code` and `message`
+/// they translate to.
+const SV2_ERROR_CODES: &[(&str, i32, &str)] = &[
+    ("stale-share", error_codes::JOB_NOT_FOUND, "Job not found"),
+    (
+        "invalid-job-id",
+        error_codes::JOB_NOT_FOUND,
+        "Job not found",
+    ),
+    (
+        "difficulty-too-low",
+        error_codes::LOW_DIFFICULTY_SHARE,
+        "Low difficulty share",
+    ),
+    (
+        "invalid-channel-id",
+        error_codes::NOT_SUBSCRIBED,
+        "Not subscribed",
+    ),
+    (
+        "unsupported-feature-flags",
+        error_codes::OTHER,
+        "Unsupported feature flags",
+    ),
+    (
+        "unsupported-protocol",
+        error_codes::OTHER,
+        "Unsupported protocol",
+    ),
+    (
+        "protocol-version-mismatch",
+        error_codes::OTHER,
+        "Protocol version mismatch",
+    ),
+];
+
+impl JsonRpcError {
+    /// Translates an SV2 error code, such as the `error_code` of a `SubmitSharesError` or of a
+    /// `SetupConnection.Error`, into the SV1 error to send to a miner.
+    ///
+    /// Codes without an SV1 equivalent map to [`error_codes::OTHER`] with the SV2 code in the
+    /// message.
+    pub fn from_sv2_error_code(sv2_code: &str) -> Self {
+        let (code, message) = SV2_ERROR_CODES
+            .iter()
+            .find(|(c, _, _)| *c == sv2_code)
+            .map(|(_, code, message)| (*code, message.to_string()))
+            .unwrap_or_else(|| (error_codes::OTHER, format!("Other/Unknown: {}", sv2_code)));
+        JsonRpcError {
+            code,
+            message,
+            data: None,
+        }
+    }
+
+    /// Returns the error in the SV1 wire form, the array `[code, message, data]`.
+    pub fn to_sv1_value(&self) -> Value {
+        serde_json::json!([self.code, self.message, self.data])
+    }
+
+    /// Returns `true` if the request may succeed when sent again, possibly on a new connection.
+    ///
+    /// Only [`error_codes::INTERNAL_ERROR`] and the implementation-defined server errors in
+    /// [`error_codes::SERVER_ERROR_RANGE`] are transient failures of the server. Every other code
+    /// is fatal: parse, method and params errors will be returned again for the same request, and
+    /// the SV1 reject codes answer a specific share or worker.
+    pub fn is_retryable(&self) -> bool {
+        self.code == error_codes::INTERNAL_ERROR
+            || error_codes::SERVER_ERROR_RANGE.contains(&self.code)
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn write_canonical(value: &Value, out: &mut String) -> Result<(), Error<'static>> {
+    match value {
+        Value::Array(values) => {
+            out.push('[');
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(v, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            // sort explicitly, the map preserves insertion order if serde_json `preserve_order` is
+            // enabled anywhere in the dependency graph
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.push('{');
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(k)?);
+                out.push(':');
+                write_canonical(v, out)?;
+            }
+            out.push('}');
+        }
+        primitive => out.push_str(&serde_json::to_string(primitive)?),
+    }
+    Ok(())
+}
+
 impl From<Response> for Message {
     fn from(res: Response) -> Self {
         if res.error.is_some() {
@@ -77,3 +326,500 @@ impl From<Notification> for Message {
         Message::Notification(n)
     }
 }
+
+/// Id of a JSON-RPC request.
+pub type Id = u64;
+
+/// Allocates the ids of the requests sent on a connection, it can be shared between tasks.
+///
+/// Ids are returned in increasing order starting from the first id. The counter wraps around after
+/// `u64::MAX`, so the id following `u64::MAX` is `0`: a connection would need 2^64 requests to get
+/// there, after that ids are no longer guaranteed to be unique. Reserved ids (e.g. the fixed ids
+/// some clients use for `mining.subscribe` and `mining.authorize`) are never returned.
+#[derive(Debug, Default)]
+pub struct IdAllocator {
+    next: AtomicU64,
+    reserved: Vec<Id>,
+}
+
+impl IdAllocator {
+    /// Creates an allocator whose first id is `first`.
+    pub fn new(first: Id) -> Self {
+        Self {
+            next: AtomicU64::new(first),
+            reserved: Vec::new(),
+        }
+    }
+
+    /// Makes the allocator skip the `reserved` ids.
+    pub fn with_reserved(mut self, reserved: impl IntoIterator<Item = Id>) -> Self {
+        self.reserved.extend(reserved);
+        self
+    }
+
+    /// Returns the next id that is not reserved.
+    pub fn next(&self) -> Id {
+        loop {
+            let id = self.next.fetch_add(1, Ordering::Relaxed);
+            if !self.reserved.contains(&id) {
+                return id;
+            }
+        }
+    }
+}
+
+/// Hooks run around the handling of a [`Message`], e.g. to add logging, metrics or auth checks.
+///
+/// Both hooks do nothing by default, so a middleware only implements the ones it needs.
+pub trait MessageMiddleware {
+    /// Called with every message before it is handled.
+    fn before(&self, _msg: &Message) {}
+
+    /// Called with the message and the response the handler produced for it, if any.
+    fn after(&self, _req: &Message, _resp: &Message) {}
+}
+
+/// An ordered list of [`MessageMiddleware`] run around a message handler by
+/// [`MiddlewareStack::dispatch`].
+///
+/// The first middleware added is the outermost one: its `before` runs first and its `after` runs
+/// last.
+#[derive(Default)]
+pub struct MiddlewareStack {
+    layers: Vec<Box<dyn MessageMiddleware + Send + Sync>>,
+}
+
+impl MiddlewareStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `middleware` inside the ones already in the stack.
+    pub fn with(mut self, middleware: impl MessageMiddleware + Send + Sync + 'static) -> Self {
+        self.layers.push(Box::new(middleware));
+        self
+    }
+
+    /// Handles `msg` with `handler`, running the `before` hooks first and, if the handler answers
+    /// with a message, the `after` hooks in reverse order. Errors are returned as they are and
+    /// skip the `after` hooks.
+    pub fn dispatch<E>(
+        &self,
+        msg: &Message,
+        handler: impl FnOnce(&Message) -> Result<Option<Message>, E>,
+    ) -> Result<Option<Message>, E> {
+        for layer in &self.layers {
+            layer.before(msg);
+        }
+        let response = handler(msg)?;
+        if let Some(response) = &response {
+            for layer in self.layers.iter().rev() {
+                layer.after(msg, response);
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn keys<T: Serialize>(message: &T) -> BTreeSet<String> {
+        serde_json::to_value(message)
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    fn expected(keys: &[&str]) -> BTreeSet<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn standard_request_keys() {
+        let request = StandardRequest {
+            id: 1,
+            method: "mining.subscribe".to_string(),
+            params: serde_json::json!([]),
+        };
+        assert_eq!(keys(&request), expected(&["id", "method", "params"]));
+    }
+
+    #[test]
+    fn notification_has_no_id_key() {
+        let notification = Notification {
+            method: "mining.set_difficulty".to_string(),
+            params: serde_json::json!([1]),
+        };
+        assert_eq!(keys(&notification), expected(&["method", "params"]));
+    }
+
+    #[test]
+    fn response_keys() {
+        let ok = Response {
+            id: 1,
+            error: None,
+            result: serde_json::json!(true),
+        };
+        assert_eq!(keys(&ok), expected(&["id", "error", "result"]));
+
+        let error = JsonRpcError {
+            code: 21,
+            message: "Job not found".to_string(),
+            data: None,
+        };
+        assert_eq!(keys(&error), expected(&["code", "message", "data"]));
+        let err = Response {
+            id: 1,
+            error: Some(error),
+            result: serde_json::Value::Null,
+        };
+        assert_eq!(keys(&err), expected(&["id", "error", "result"]));
+        assert_eq!(
+            keys(&Message::from(err)),
+            expected(&["id", "error", "result"])
+        );
+    }
+
+    #[test]
+    fn request_named_and_positional_params() {
+        let named = StandardRequest {
+            id: 1,
+            method: "mining.authorize".to_string(),
+            params: serde_json::json!({"username": "user.worker", "password": "x"}),
+        };
+        assert_eq!(
+            named.param("username"),
+            Some(&serde_json::json!("user.worker"))
+        );
+        assert_eq!(named.param("missing"), None);
+        assert_eq!(named.param_at(0), None);
+
+        let positional = StandardRequest {
+            params: serde_json::json!(["user.worker", "x"]),
+            ..named
+        };
+        assert_eq!(positional.param_at(1), Some(&serde_json::json!("x")));
+        assert_eq!(positional.param_at(2), None);
+        assert_eq!(positional.param("username"), None);
+    }
+
+    #[test]
+    fn notification_request_conversions() {
+        let notification = Notification {
+            method: "mining.notify".to_string(),
+            params: serde_json::json!(["job", true]),
+        };
+
+        let request = notification.clone().into_request(7);
+        assert_eq!(request.id, 7);
+        assert_eq!(request.method, notification.method);
+        assert_eq!(request.params, notification.params);
+
+        let back = request.into_notification();
+        assert_eq!(back.method, notification.method);
+        assert_eq!(back.params, notification.params);
+        assert_eq!(keys(&back), expected(&["method", "params"]));
+    }
+
+    #[test]
+    fn response_constructors_pick_the_message_variant() {
+        match Message::from(Response::ok(1, serde_json::json!(true))) {
+            Message::OkResponse(response) => {
+                assert_eq!(response.id, 1);
+                assert!(response.error.is_none());
+                assert_eq!(response.result, serde_json::json!(true));
+            }
+            message => panic!("expected an ok response, got {:?}", message),
+        }
+
+        let error = JsonRpcError::from_sv2_error_code("stale-share");
+        match Message::from(Response::err(2, error)) {
+            Message::ErrorResponse(response) => {
+                assert_eq!(response.id, 2);
+                assert_eq!(response.error.unwrap().code, error_codes::JOB_NOT_FOUND);
+                assert!(response.result.is_null());
+            }
+            message => panic!("expected an error response, got {:?}", message),
+        }
+    }
+
+    #[test]
+    fn retryable_json_rpc_errors() {
+        let error = |code| JsonRpcError {
+            code,
+            message: String::new(),
+            data: None,
+        };
+        assert!(error(error_codes::INTERNAL_ERROR).is_retryable());
+        assert!(error(-32603).is_retryable());
+        assert!(error(-32000).is_retryable());
+        assert!(error(-32099).is_retryable());
+
+        assert!(!error(error_codes::METHOD_NOT_FOUND).is_retryable());
+        assert!(!error(-32601).is_retryable());
+        assert!(!error(error_codes::PARSE_ERROR).is_retryable());
+        assert!(!error(error_codes::INVALID_PARAMS).is_retryable());
+        assert!(!error(-32100).is_retryable());
+        assert!(!JsonRpcError::from_sv2_error_code("stale-share").is_retryable());
+        assert!(!error(error_codes::UNAUTHORIZED_WORKER).is_retryable());
+    }
+
+    #[test]
+    fn into_method_recognized_method() {
+        let message: Message = StandardRequest {
+            id: 1,
+            method: "mining.authorize".to_string(),
+            params: serde_json::json!(["user.worker", "password"]),
+        }
+        .into();
+        match message.into_method() {
+            Ok(Method::Client2Server(crate::methods::Client2Server::Authorize(authorize))) => {
+                assert_eq!(authorize.id, 1);
+                assert_eq!(authorize.name, "user.worker");
+            }
+            result => panic!("expected mining.authorize, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn into_method_unknown_method() {
+        let message: Message = Notification {
+            method: "mining.unknown".to_string(),
+            params: serde_json::json!([]),
+        }
+        .into();
+        match message.into_method() {
+            Err(Error::Method(MethodError::MethodNotFound(method))) => {
+                assert_eq!(method, "mining.unknown")
+            }
+            result => panic!("expected MethodNotFound, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn error_response_serializes_the_error_object() {
+        let response = Response::err(3, JsonRpcError::from_sv2_error_code("difficulty-too-low"));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            serde_json::json!({
+                "id": 3,
+                "error": {"code": 23, "message": "Low difficulty share", "data": null},
+                "result": null,
+            })
+        );
+    }
+
+    #[test]
+    fn error_response_to_sv1_value() {
+        let response = Response::err(3, JsonRpcError::from_sv2_error_code("difficulty-too-low"));
+        assert_eq!(
+            response.to_sv1_value(),
+            serde_json::json!({
+                "id": 3,
+                "error": [23, "Low difficulty share", null],
+                "result": null,
+            })
+        );
+        let ok = Response::ok(4, serde_json::json!(true));
+        assert_eq!(
+            ok.to_sv1_value(),
+            serde_json::json!({"id": 4, "error": null, "result": true})
+        );
+    }
+
+    #[test]
+    fn json_rpc_error_deserializes_array_and_object() {
+        for json in [
+            r#"[23, "Low difficulty share", null]"#,
+            r#"{"code": 23, "message": "Low difficulty share", "data": null}"#,
+        ] {
+            let error: JsonRpcError = serde_json::from_str(json).unwrap();
+            assert_eq!(error.code, 23);
+            assert_eq!(error.message, "Low difficulty share");
+            assert!(error.data.is_none());
+        }
+    }
+
+    #[test]
+    fn batch_ids_are_unique() {
+        let batch = [
+            Response::ok(1, serde_json::json!(true)),
+            Response::ok(2, serde_json::json!(true)),
+            Response::err(3, JsonRpcError::from_sv2_error_code("stale-share")),
+        ];
+        assert!(validate_batch_ids(&batch).is_ok());
+        assert!(validate_batch_ids(&[]).is_ok());
+    }
+
+    #[test]
+    fn batch_with_duplicate_id() {
+        let batch = [
+            Response::ok(1, serde_json::json!(true)),
+            Response::ok(2, serde_json::json!(true)),
+            Response::ok(1, serde_json::json!(false)),
+        ];
+        match validate_batch_ids(&batch) {
+            Err(Error::DuplicateId(id)) => assert_eq!(id, 1),
+            result => panic!("expected DuplicateId, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn canonical_string_sorts_keys() {
+        let a: Message = serde_json::from_str(
+            r#"{"params": [{"b": 1, "a": {"d": null, "c": "x"}}], "method": "mining.configure", "id": 3}"#,
+        )
+        .unwrap();
+        let b: Message = serde_json::from_str(
+            r#"{"id":3,"method":"mining.configure","params":[{"a":{"c":"x","d":null},"b":1}]}"#,
+        )
+        .unwrap();
+        let canonical = a.to_canonical_string().unwrap();
+        assert_eq!(canonical, b.to_canonical_string().unwrap());
+        assert_eq!(
+            canonical,
+            r#"{"id":3,"method":"mining.configure","params":[{"a":{"c":"x","d":null},"b":1}]}"#
+        );
+    }
+
+    #[test]
+    fn sv2_error_code_to_json_rpc_error() {
+        for (sv2_code, code, message) in [
+            ("stale-share", 21, "Job not found"),
+            ("invalid-job-id", 21, "Job not found"),
+            ("difficulty-too-low", 23, "Low difficulty share"),
+            ("invalid-channel-id", 25, "Not subscribed"),
+            ("unsupported-feature-flags", 20, "Unsupported feature flags"),
+            ("unsupported-protocol", 20, "Unsupported protocol"),
+            ("protocol-version-mismatch", 20, "Protocol version mismatch"),
+            ("duplicate-share", 20, "Other/Unknown: duplicate-share"),
+        ] {
+            let error = JsonRpcError::from_sv2_error_code(sv2_code);
+            assert_eq!(error.code, code);
+            assert_eq!(error.message, message);
+            assert!(error.data.is_none());
+        }
+    }
+
+    #[test]
+    fn id_allocator_is_monotonic() {
+        let allocator = IdAllocator::new(1).with_reserved(vec![3, 4]);
+        let ids: Vec<Id> = (0..4).map(|_| allocator.next()).collect();
+        assert_eq!(ids, vec![1, 2, 5, 6]);
+    }
+
+    #[test]
+    fn id_allocator_wraps_around_at_max() {
+        let allocator = IdAllocator::new(u64::MAX - 1);
+        assert_eq!(allocator.next(), u64::MAX - 1);
+        assert_eq!(allocator.next(), u64::MAX);
+        assert_eq!(allocator.next(), 0);
+    }
+
+    #[test]
+    fn id_allocator_concurrent_ids_are_unique() {
+        use std::{collections::HashSet, sync::Arc, thread};
+
+        let allocator = Arc::new(IdAllocator::new(0).with_reserved(vec![10]));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let allocator = allocator.clone();
+                thread::spawn(move || (0..1000).map(|_| allocator.next()).collect::<Vec<Id>>())
+            })
+            .collect();
+        let ids: HashSet<Id> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        assert_eq!(ids.len(), 8000);
+        assert!(!ids.contains(&10));
+    }
+
+    #[test]
+    fn middleware_hooks_fire_in_order() {
+        use std::sync::{Arc, Mutex};
+
+        struct Recorder {
+            name: &'static str,
+            log: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl MessageMiddleware for Recorder {
+            fn before(&self, msg: &Message) {
+                assert!(!msg.is_response());
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} before", self.name));
+            }
+
+            fn after(&self, req: &Message, resp: &Message) {
+                assert!(!req.is_response());
+                assert!(resp.is_response());
+                self.log
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} after", self.name));
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let stack = MiddlewareStack::new()
+            .with(Recorder {
+                name: "outer",
+                log: log.clone(),
+            })
+            .with(Recorder {
+                name: "inner",
+                log: log.clone(),
+            });
+        let request: Message = StandardRequest {
+            id: 1,
+            method: "mining.authorize".to_string(),
+            params: serde_json::json!(["user.worker", "x"]),
+        }
+        .into();
+
+        let response = stack
+            .dispatch(&request, |msg| {
+                log.lock().unwrap().push("handler".to_string());
+                match msg {
+                    Message::StandardRequest(request) => Ok::<_, ()>(Some(
+                        Response::ok(request.id, serde_json::json!(true)).into(),
+                    )),
+                    _ => Ok(None),
+                }
+            })
+            .unwrap();
+        assert!(matches!(response, Some(Message::OkResponse(_))));
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "outer before",
+                "inner before",
+                "handler",
+                "inner after",
+                "outer after"
+            ]
+        );
+
+        // without a response only the before hooks fire
+        log.lock().unwrap().clear();
+        assert!(stack
+            .dispatch(&request, |_| Ok::<_, ()>(None))
+            .unwrap()
+            .is_none());
+        assert_eq!(*log.lock().unwrap(), ["outer before", "inner before"]);
+        assert!(matches!(
+            stack.dispatch(&request, |_| Err("closed")),
+            Err("closed")
+        ));
+        assert_eq!(log.lock().unwrap().len(), 4);
+    }
+}
@@ -1,4 +1,5 @@
 use bitcoin_hashes::hex::ToHex;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{
     Value,
     Value::{Array as JArrary, Null, Number as JNumber, String as JString},
@@ -9,7 +10,7 @@ use crate::{
     error::Error,
     json_rpc::{Message, Response, StandardRequest},
     methods::ParsingMethodError,
-    utils::{Extranonce, HexU32Be},
+    utils::{try_b032, Extranonce, HexU32Be},
 };
 
 #[cfg(test)]
@@ -41,6 +42,16 @@ impl Authorize {
     }
 }
 
+/// Builds the `mining.authorize` request for the worker `username`, `password` can be empty if the
+/// server does not require passwords.
+pub fn mining_authorize(id: u64, username: &str, password: &str) -> StandardRequest {
+    StandardRequest {
+        id,
+        method: "mining.authorize".into(),
+        params: serde_json::json!([username, password]),
+    }
+}
+
 impl From<Authorize> for Message {
     fn from(auth: Authorize) -> Self {
         Message::StandardRequest(StandardRequest {
@@ -140,23 +151,19 @@ impl<'a> Submit<'a> {
 }
 
 impl<'a> From<Submit<'a>> for Message {
-    fn from(submit: Submit) -> Self {
-        let ex: String = submit.extra_nonce2.0.inner_as_ref().to_hex();
-        let mut params: Vec<Value> = vec![
-            submit.user_name.into(),
-            submit.job_id.into(),
-            ex.into(),
-            submit.time.into(),
-            submit.nonce.into(),
-        ];
-        if let Some(a) = submit.version_bits {
-            let a: String = a.into();
-            params.push(a.into());
+    fn from(submit: Submit<'a>) -> Self {
+        let params = MiningSubmitParams {
+            worker: submit.user_name,
+            job_id: submit.job_id,
+            extranonce2: submit.extra_nonce2,
+            ntime: submit.time,
+            nonce: submit.nonce,
+            version_bits: submit.version_bits,
         };
         Message::StandardRequest(StandardRequest {
             id: submit.id,
             method: "mining.submit".into(),
-            params: params.into(),
+            params: params.to_value(),
         })
     }
 }
@@ -164,60 +171,94 @@ impl<'a> From<Submit<'a>> for Message {
 impl<'a> TryFrom<StandardRequest> for Submit<'a> {
     type Error = ParsingMethodError;
 
-    #[allow(clippy::many_single_char_names)]
     fn try_from(msg: StandardRequest) -> Result<Self, Self::Error> {
-        match msg.params.as_array() {
-            Some(params) => {
-                let (user_name, job_id, extra_nonce2, time, nonce, version_bits) = match &params[..]
-                {
-                    [JString(a), JString(b), JString(c), JNumber(d), JNumber(e), JString(f)] => (
-                        a.into(),
-                        b.into(),
-                        Extranonce::try_from(hex::decode(c)?)?,
-                        HexU32Be(d.as_u64().unwrap() as u32),
-                        HexU32Be(e.as_u64().unwrap() as u32),
-                        Some((f.as_str()).try_into()?),
-                    ),
-                    [JString(a), JString(b), JString(c), JString(d), JString(e), JString(f)] => (
-                        a.into(),
-                        b.into(),
-                        Extranonce::try_from(hex::decode(c)?)?,
-                        (d.as_str()).try_into()?,
-                        (e.as_str()).try_into()?,
-                        Some((f.as_str()).try_into()?),
-                    ),
-                    [JString(a), JString(b), JString(c), JNumber(d), JNumber(e)] => (
-                        a.into(),
-                        b.into(),
-                        Extranonce::try_from(hex::decode(c)?)?,
-                        HexU32Be(d.as_u64().unwrap() as u32),
-                        HexU32Be(e.as_u64().unwrap() as u32),
-                        None,
-                    ),
-                    [JString(a), JString(b), JString(c), JString(d), JString(e)] => (
-                        a.into(),
-                        b.into(),
-                        Extranonce::try_from(hex::decode(c)?)?,
-                        (d.as_str()).try_into()?,
-                        (e.as_str()).try_into()?,
-                        None,
-                    ),
-                    _ => return Err(ParsingMethodError::wrong_args_from_value(msg.params)),
-                };
-                let id = msg.id;
-                let res = crate::client_to_server::Submit {
-                    user_name,
-                    job_id,
-                    extra_nonce2,
-                    time,
-                    nonce,
-                    version_bits,
-                    id,
-                };
-                Ok(res)
-            }
-            None => Err(ParsingMethodError::not_array_from_value(msg.params)),
+        let params = MiningSubmitParams::from_value(&msg.params)?;
+        Ok(Submit {
+            user_name: params.worker,
+            job_id: params.job_id,
+            extra_nonce2: params.extranonce2,
+            time: params.ntime,
+            nonce: params.nonce,
+            version_bits: params.version_bits,
+            id: msg.id,
+        })
+    }
+}
+
+/// Typed params of a _mining.submit_ request:
+/// `[worker, job_id, extranonce2, ntime, nonce]` plus the optional `version_bits` used by the
+/// version-rolling extension.
+///
+/// `extranonce2` is decoded from hex, `ntime`, `nonce` and `version_bits` from big-endian hex
+/// strings (some miners send `ntime` and `nonce` as plain numbers, those are accepted too).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiningSubmitParams<'a> {
+    pub worker: String,
+    pub job_id: String,
+    pub extranonce2: Extranonce<'a>,
+    pub ntime: HexU32Be,
+    pub nonce: HexU32Be,
+    pub version_bits: Option<HexU32Be>,
+}
+
+impl<'a> MiningSubmitParams<'a> {
+    /// Parses the `params` field of a _mining.submit_ request.
+    pub fn from_value(value: &Value) -> Result<Self, ParsingMethodError> {
+        let params = value
+            .as_array()
+            .ok_or_else(|| ParsingMethodError::not_array_from_value(value.clone()))?;
+        let (worker, job_id, extranonce2, ntime, nonce, version_bits) = match &params[..] {
+            [JString(a), JString(b), JString(c), d, e] => (a, b, c, d, e, None),
+            [JString(a), JString(b), JString(c), d, e, f] => (a, b, c, d, e, Some(f)),
+            _ => return Err(ParsingMethodError::wrong_args_from_value(value.clone())),
+        };
+        Ok(MiningSubmitParams {
+            worker: worker.clone(),
+            job_id: job_id.clone(),
+            extranonce2: Extranonce(try_b032("extranonce2", &hex::decode(extranonce2)?)?),
+            ntime: hex_u32_from_value(ntime)?,
+            nonce: hex_u32_from_value(nonce)?,
+            version_bits: match version_bits {
+                Some(JString(f)) => Some(hex_u32_from_str(f)?),
+                Some(f) => return Err(ParsingMethodError::ValueNotAString(Box::new(f.clone()))),
+                None => None,
+            },
+        })
+    }
+
+    /// Serializes the params back to the array expected by _mining.submit_.
+    pub fn to_value(&self) -> Value {
+        let mut params: Vec<Value> = vec![
+            self.worker.clone().into(),
+            self.job_id.clone().into(),
+            self.extranonce2.0.inner_as_ref().to_hex().into(),
+            self.ntime.clone().into(),
+            self.nonce.clone().into(),
+        ];
+        if let Some(version_bits) = &self.version_bits {
+            params.push(version_bits.clone().into());
         }
+        params.into()
+    }
+}
+
+fn hex_u32_from_str(s: &str) -> Result<HexU32Be, ParsingMethodError> {
+    // HexU32Be::try_from pads short strings, longer ones can not fit in a u32
+    if s.len() > 8 {
+        return Err(ParsingMethodError::UnexpectedValue(Box::new(s.into())));
+    }
+    Ok(s.try_into()?)
+}
+
+fn hex_u32_from_value(value: &Value) -> Result<HexU32Be, ParsingMethodError> {
+    match value {
+        JString(s) => hex_u32_from_str(s),
+        JNumber(n) => n
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .map(HexU32Be)
+            .ok_or_else(|| ParsingMethodError::ImpossibleToParseAsU64(Box::new(n.clone()))),
+        _ => Err(ParsingMethodError::ValueNotAString(Box::new(value.clone()))),
     }
 }
 
@@ -292,6 +333,28 @@ impl<'a> Subscribe<'a> {
     }
 }
 
+/// Builds the `mining.subscribe` request. `session_id` is the extranonce1, as hex, of a previous
+/// subscription the client wants to resume; since params are positional an empty user agent is
+/// sent if only `session_id` is given.
+pub fn mining_subscribe(
+    id: u64,
+    user_agent: Option<&str>,
+    session_id: Option<&str>,
+) -> StandardRequest {
+    let params = match (user_agent, session_id) {
+        (user_agent, Some(session_id)) => {
+            serde_json::json!([user_agent.unwrap_or_default(), session_id])
+        }
+        (Some(user_agent), None) => serde_json::json!([user_agent]),
+        (None, None) => serde_json::json!([]),
+    };
+    StandardRequest {
+        id,
+        method: "mining.subscribe".into(),
+        params,
+    }
+}
+
 impl<'a> TryFrom<Subscribe<'a>> for Message {
     type Error = Error<'a>;
 
@@ -451,8 +514,6 @@ impl ConfigureExtension {
             return Err(ParsingMethodError::Todo);
         };
 
-        let version_rolling_mask = val.pointer("/1/version-rolling.mask");
-        let version_rolling_min_bit = val.pointer("/1/version-rolling.min-bit-count");
         let info_connection_url = val.pointer("/1/info.connection-url");
         let info_hw_version = val.pointer("/1/info.hw-version");
         let info_sw_version = val.pointer("/1/info.sw-version");
@@ -466,39 +527,8 @@ impl ConfigureExtension {
         {
             res.push(ConfigureExtension::SubcribeExtraNonce)
         }
-        let (mask, min_bit_count) = match (version_rolling_mask, version_rolling_min_bit) {
-            (None, None) => (None, None),
-            // WhatsMiner sent mask without min bit count
-            (Some(JString(mask)), None) => {
-                let mask: HexU32Be = mask.as_str().try_into()?;
-                (Some(mask), None)
-            }
-            // Min bit can be a string cpuminer
-            (Some(JString(mask)), Some(JString(min_bit))) => {
-                let mask: HexU32Be = mask.as_str().try_into()?;
-                let min_bit: HexU32Be = min_bit.as_str().try_into()?;
-                (Some(mask), Some(min_bit))
-            }
-            // Min bit can be a number s9, s19
-            (Some(JString(mask)), Some(JNumber(min_bit))) => {
-                let mask: HexU32Be = mask.as_str().try_into()?;
-                // min_bit is a json number checked above so as_u64 can not fail
-                let min_bit: HexU32Be = HexU32Be(min_bit.as_u64().unwrap() as u32);
-                (Some(mask), Some(min_bit))
-            }
-            // We can not have min bit count without a mask
-            (None, Some(_)) => return Err(ParsingMethodError::Todo),
-            // Mask need to be a JString
-            (Some(_), None) => return Err(ParsingMethodError::Todo),
-            // Min bit need to be a string or a number
-            (Some(_), Some(_)) => return Err(ParsingMethodError::Todo),
-        };
-        if mask.is_some() || min_bit_count.is_some() {
-            let params = VersionRollingParams {
-                mask,
-                min_bit_count,
-            };
-            res.push(ConfigureExtension::VersionRolling(params));
+        if let Some(params) = ConfigureParams::from_params_object(val.pointer("/1"))? {
+            res.push(ConfigureExtension::VersionRolling(params.into()));
         }
 
         if let Some(minimum_difficulty_value) = minimum_difficulty_value {
@@ -629,6 +659,112 @@ impl From<VersionRollingParams> for serde_json::Map<String, Value> {
     }
 }
 
+/// The `version-rolling` extension of a `mining.configure` request, i.e. the
+/// `version-rolling.mask` and `version-rolling.min-bit-count` fields of the params object.
+///
+/// It is the [`ConfigureExtension::VersionRolling`] of a [`Configure`] request, and it is
+/// (de)serialized as those fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigureParams {
+    pub mask: HexU32Be,
+    /// Some miners send it as a hex string (cpuminer) others as a number (s9, s19), WhatsMiner
+    /// does not send it at all.
+    pub min_bit_count: Option<HexU32Be>,
+}
+
+impl ConfigureParams {
+    /// Returns the `version-rolling` extension of the params of a `mining.configure` request, or
+    /// `None` if the miner did not ask for version rolling.
+    pub fn from_request_params(params: &Value) -> Result<Option<Self>, ParsingMethodError> {
+        Ok(ConfigureExtension::from_value(params)?
+            .into_iter()
+            .find_map(|extension| match extension {
+                ConfigureExtension::VersionRolling(VersionRollingParams {
+                    mask: Some(mask),
+                    min_bit_count,
+                }) => Some(ConfigureParams {
+                    mask,
+                    min_bit_count,
+                }),
+                _ => None,
+            }))
+    }
+
+    /// Builds the `mining.configure` request asking only for version rolling.
+    pub fn to_request(&self, id: u64) -> StandardRequest {
+        let configure = Configure::new(id, Some(self.mask.clone()), self.min_bit_count.clone());
+        match Message::from(configure) {
+            Message::StandardRequest(request) => request,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Parses the `version-rolling` fields of the params object of a `mining.configure` request,
+    /// `None` if there are none.
+    fn from_params_object(object: Option<&Value>) -> Result<Option<Self>, ParsingMethodError> {
+        let version_rolling_mask = object.and_then(|object| object.get("version-rolling.mask"));
+        let version_rolling_min_bit =
+            object.and_then(|object| object.get("version-rolling.min-bit-count"));
+        let (mask, min_bit_count) = match (version_rolling_mask, version_rolling_min_bit) {
+            (None, None) => return Ok(None),
+            // WhatsMiner sent mask without min bit count
+            (Some(JString(mask)), None) => {
+                let mask: HexU32Be = mask.as_str().try_into()?;
+                (mask, None)
+            }
+            // Min bit can be a string cpuminer
+            (Some(JString(mask)), Some(JString(min_bit))) => {
+                let mask: HexU32Be = mask.as_str().try_into()?;
+                let min_bit: HexU32Be = min_bit.as_str().try_into()?;
+                (mask, Some(min_bit))
+            }
+            // Min bit can be a number s9, s19
+            (Some(JString(mask)), Some(JNumber(min_bit))) => {
+                let mask: HexU32Be = mask.as_str().try_into()?;
+                let min_bit = min_bit
+                    .as_u64()
+                    .and_then(|min_bit| u32::try_from(min_bit).ok())
+                    .ok_or_else(|| ParsingMethodError::not_unsigned_from_value(min_bit.clone()))?;
+                (mask, Some(HexU32Be(min_bit)))
+            }
+            // We can not have min bit count without a mask
+            (None, Some(_)) => return Err(ParsingMethodError::Todo),
+            // Mask need to be a JString
+            (Some(_), None) => return Err(ParsingMethodError::Todo),
+            // Min bit need to be a string or a number
+            (Some(_), Some(_)) => return Err(ParsingMethodError::Todo),
+        };
+        Ok(Some(ConfigureParams {
+            mask,
+            min_bit_count,
+        }))
+    }
+}
+
+impl From<ConfigureParams> for VersionRollingParams {
+    fn from(params: ConfigureParams) -> Self {
+        VersionRollingParams {
+            mask: Some(params.mask),
+            min_bit_count: params.min_bit_count,
+        }
+    }
+}
+
+impl Serialize for ConfigureParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_json::Map::from(VersionRollingParams::from(self.clone())).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ConfigureParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let object = Value::deserialize(deserializer)?;
+        ConfigureParams::from_params_object(Some(&object))
+            .map_err(|e| D::Error::custom(format!("invalid version-rolling params: {:?}", e)))?
+            .ok_or_else(|| D::Error::missing_field("version-rolling.mask"))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct InfoParams {
     connection_url: Option<String>,
@@ -716,3 +852,104 @@ fn test_version_extension_with_no_bit_count() {
         _ => panic!(),
     };
 }
+
+#[test]
+fn test_mining_submit_params_from_to_value() {
+    let value = serde_json::json!([
+        "worker.1",
+        "2",
+        "147a3f0000000000",
+        "6436eddf",
+        "41d5deb0",
+        "00000000"
+    ]);
+    let params = MiningSubmitParams::from_value(&value).unwrap();
+    assert_eq!(params.worker, "worker.1");
+    assert_eq!(params.job_id, "2");
+    assert_eq!(
+        params.extranonce2.0.inner_as_ref(),
+        &[0x14, 0x7a, 0x3f, 0, 0, 0, 0, 0][..]
+    );
+    assert_eq!(params.ntime, HexU32Be(0x6436_eddf));
+    assert_eq!(params.nonce, HexU32Be(0x41d5_deb0));
+    assert_eq!(params.version_bits, Some(HexU32Be(0)));
+    assert_eq!(params.to_value(), value);
+}
+
+#[test]
+fn test_mining_submit_params_wrong_arity() {
+    let value = serde_json::json!(["worker.1", "2", "147a3f0000000000", "6436eddf"]);
+    match MiningSubmitParams::from_value(&value) {
+        Err(ParsingMethodError::WrongArgs(params)) => assert_eq!(*params, value),
+        other => panic!("expected WrongArgs, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_configure_params_from_request() {
+    let client_message = r#"{"id":1,
+            "method": "mining.configure",
+            "params":[
+                ["minimum-difficulty", "version-rolling"],
+                {"minimum-difficulty.value": 2048,
+                "version-rolling.mask":"1fffe000",
+                "version-rolling.min-bit-count":2}
+            ]
+        }"#;
+    let client_message: StandardRequest = serde_json::from_str(client_message).unwrap();
+    let params = ConfigureParams::from_request_params(&client_message.params)
+        .unwrap()
+        .unwrap();
+    assert_eq!(params.mask, HexU32Be(0x1fffe000));
+    assert_eq!(params.min_bit_count, Some(HexU32Be(2)));
+
+    let request = params.to_request(1);
+    assert_eq!(
+        request.params,
+        serde_json::json!([
+            ["version-rolling"],
+            {"version-rolling.mask": "1fffe000", "version-rolling.min-bit-count": "00000002"}
+        ])
+    );
+    assert_eq!(
+        ConfigureParams::from_request_params(&request.params).unwrap(),
+        Some(params)
+    );
+
+    let no_version_rolling = serde_json::json!([["subscribe-extranonce"], {}]);
+    assert_eq!(
+        ConfigureParams::from_request_params(&no_version_rolling).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_configure_min_bit_count_parsing() {
+    let configure = |min_bit_count: Value| StandardRequest {
+        id: 1,
+        method: "mining.configure".into(),
+        params: serde_json::json!([
+            ["version-rolling"],
+            {"version-rolling.mask": "1fffe000", "version-rolling.min-bit-count": min_bit_count}
+        ]),
+    };
+    for min_bit_count in [serde_json::json!(16), serde_json::json!("00000010")].iter() {
+        let request = configure(min_bit_count.clone());
+        let params = ConfigureParams::from_request_params(&request.params)
+            .unwrap()
+            .unwrap();
+        assert_eq!(params.min_bit_count, Some(HexU32Be(16)));
+        let configure = Configure::try_from(request).unwrap();
+        assert_eq!(
+            configure.version_rolling_min_bit_count(),
+            Some(HexU32Be(16))
+        );
+    }
+    // Configure and ConfigureParams share the parser, both reject a count that is not a u32
+    for min_bit_count in [serde_json::json!(2.5), serde_json::json!(u64::MAX)].iter() {
+        let request = configure(min_bit_count.clone());
+        assert!(ConfigureParams::from_request_params(&request.params).is_err());
+        assert!(serde_json::from_value::<ConfigureParams>(request.params[1].clone()).is_err());
+        assert!(Configure::try_from(request).is_err());
+    }
+}
@@ -54,6 +54,8 @@ pub enum ParsingMethodError {
     HexError(Box<FromHexError>),
     #[allow(clippy::upper_case_acronyms)]
     BTCHashError(Box<BTCHashError>),
+    /// (`field`, expected length, actual length), see [`Error::InvalidLength`].
+    InvalidLength(&'static str, &'static str, usize),
     ValueNotAnArray(Box<serde_json::Value>),
     WrongArgs(Box<serde_json::Value>),
     ValueNotAString(Box<serde_json::Value>),
@@ -74,6 +76,10 @@ impl<'a> From<Error<'a>> for ParsingMethodError {
         match inner {
             Error::HexError(e) => ParsingMethodError::HexError(Box::new(e)),
             Error::BTCHashError(e) => ParsingMethodError::BTCHashError(Box::new(e)),
+            Error::BadBytesConvert(e) => ParsingMethodError::BadU256Convert(Box::new(e)),
+            Error::InvalidLength(field, expected, actual) => {
+                ParsingMethodError::InvalidLength(field, expected, actual)
+            }
             _ => panic!("v1 Error does not implement this ParsingMethodError, but probably should"),
         }
     }
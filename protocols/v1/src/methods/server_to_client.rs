@@ -1,3 +1,4 @@
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{
     Value,
     Value::{Array as JArrary, Bool as JBool, Number as JNumber, String as JString},
@@ -5,6 +6,7 @@ use serde_json::{
 use std::convert::{TryFrom, TryInto};
 
 use crate::{
+    client_to_server::ConfigureParams,
     error::Error,
     json_rpc::{Message, Notification, Response},
     methods::ParsingMethodError,
@@ -48,7 +50,7 @@ pub struct Notify<'a> {
     pub clean_jobs: bool,
 }
 
-impl<'a> From<Notify<'a>> for Message {
+impl<'a> From<Notify<'a>> for Notification {
     fn from(notify: Notify) -> Self {
         let prev_hash: Value = notify.prev_hash.into();
         let coin_base1: Value = notify.coin_base1.into();
@@ -62,7 +64,7 @@ impl<'a> From<Notify<'a>> for Message {
         let version: Value = notify.version.into();
         let bits: Value = notify.bits.into();
         let time: Value = notify.time.into();
-        Message::Notification(Notification {
+        Notification {
             method: "mining.notify".to_string(),
             params: (&[
                 notify.job_id.into(),
@@ -76,7 +78,58 @@ impl<'a> From<Notify<'a>> for Message {
                 notify.clean_jobs.into(),
             ][..])
                 .into(),
-        })
+        }
+    }
+}
+
+impl<'a> From<Notify<'a>> for Message {
+    fn from(notify: Notify) -> Self {
+        Message::Notification(notify.into())
+    }
+}
+
+/// Builds a `mining.notify` [`Notification`] with the params in SV1 order:
+/// `[job_id, prevhash, coinb1, coinb2, merkle_branch, version, nbits, ntime, clean_jobs]`.
+#[allow(clippy::too_many_arguments)]
+pub fn mining_notify(
+    job_id: String,
+    prev_hash: PrevHash,
+    coin_base1: HexBytes,
+    coin_base2: HexBytes,
+    merkle_branch: Vec<MerkleNode>,
+    version: u32,
+    bits: u32,
+    time: u32,
+    clean_jobs: bool,
+) -> Notification {
+    Notify {
+        job_id,
+        prev_hash,
+        coin_base1,
+        coin_base2,
+        merkle_branch,
+        version: HexU32Be(version),
+        bits: HexU32Be(bits),
+        time: HexU32Be(time),
+        clean_jobs,
+    }
+    .into()
+}
+
+/// Returns the `clean_jobs` flag of `mining.notify` `params`.
+///
+/// `clean_jobs` must be the ninth and last param: a notify with a different number of params, or
+/// whose last param is not a boolean, is rejected instead of defaulting the flag, so that a miner
+/// is never left mining stale jobs.
+pub fn parse_notify_clean_jobs(params: &Value) -> Result<bool, ParsingMethodError> {
+    let params = params
+        .as_array()
+        .ok_or_else(|| ParsingMethodError::not_array_from_value(params.clone()))?;
+    match &params[..] {
+        [_, _, _, _, _, _, _, _, JBool(clean_jobs)] => Ok(*clean_jobs),
+        _ => Err(ParsingMethodError::wrong_args_from_value(
+            params.clone().into(),
+        )),
     }
 }
 
@@ -154,13 +207,30 @@ pub struct SetDifficulty {
     pub value: f64,
 }
 
-impl From<SetDifficulty> for Message {
+impl SetDifficulty {
+    /// Errors if `value` is NaN, infinite, zero or negative.
+    #[allow(clippy::result_large_err)]
+    pub fn new(value: f64) -> Result<Self, Error<'static>> {
+        if !value.is_finite() || value <= 0.0 {
+            return Err(Error::InvalidDifficulty(value));
+        }
+        Ok(SetDifficulty { value })
+    }
+}
+
+impl From<SetDifficulty> for Notification {
     fn from(sd: SetDifficulty) -> Self {
         let value: Value = sd.value.into();
-        Message::Notification(Notification {
+        Notification {
             method: "mining.set_difficulty".to_string(),
             params: (&[value][..]).into(),
-        })
+        }
+    }
+}
+
+impl From<SetDifficulty> for Message {
+    fn from(sd: SetDifficulty) -> Self {
+        Message::Notification(sd.into())
     }
 }
 
@@ -301,6 +371,31 @@ impl TryFrom<&Response> for GeneralResponse {
     }
 }
 
+/// Parses the response to a `mining.authorize` request, returning true if the worker has been
+/// authorized.
+///
+/// An error response is returned as the [`Error`] mapped from its `json_rpc` error, a result that
+/// is not a boolean as [`Error::Method`].
+#[allow(clippy::result_large_err)]
+pub fn parse_authorize_result(response: &Response) -> Result<bool, Error<'static>> {
+    if let Some(error) = &response.error {
+        return Err(error.clone().into());
+    }
+    let general = GeneralResponse::try_from(response)
+        .map_err(|e| e.as_method_error(Message::OkResponse(response.clone())))?;
+    Ok(general.result)
+}
+
+/// Like [`parse_authorize_result`] but a `false` result is returned as
+/// [`Error::UnauthorizedClient`] with `username`.
+#[allow(clippy::result_large_err)]
+pub fn check_authorize_result(response: &Response, username: &str) -> Result<(), Error<'static>> {
+    match parse_authorize_result(response)? {
+        true => Ok(()),
+        false => Err(Error::UnauthorizedClient(username.to_string())),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Authorize {
     pub id: u64,
@@ -318,6 +413,35 @@ impl Authorize {
     }
 }
 
+#[test]
+fn authorize_request_and_result() {
+    let request = crate::client_to_server::mining_authorize(3, "user.worker", "x");
+    let authorize = crate::client_to_server::Authorize::try_from(request).unwrap();
+    assert_eq!(authorize.id, 3);
+    assert_eq!(authorize.name, "user.worker");
+    assert_eq!(authorize.password, "x");
+
+    let response = authorize.clone().respond(true);
+    assert!(parse_authorize_result(&response).unwrap());
+    assert!(check_authorize_result(&response, &authorize.name).is_ok());
+}
+
+#[test]
+fn authorize_result_unauthorized() {
+    let response = Response::ok(3, serde_json::json!(false));
+    assert!(!parse_authorize_result(&response).unwrap());
+    match check_authorize_result(&response, "user.worker") {
+        Err(Error::UnauthorizedClient(name)) => assert_eq!(name, "user.worker"),
+        other => panic!("expected UnauthorizedClient, got {:?}", other),
+    }
+
+    let response = Response::ok(3, serde_json::json!("yes"));
+    assert!(matches!(
+        parse_authorize_result(&response),
+        Err(Error::Method(_))
+    ));
+}
+
 #[derive(Debug, Clone)]
 pub struct Submit {
     pub id: u64,
@@ -419,6 +543,157 @@ impl<'a> TryFrom<&Response> for Subscribe<'a> {
     }
 }
 
+/// Parses the `result` field of a `mining.subscribe` response, `[subscriptions, extranonce1,
+/// extranonce2_size]`, returning the extranonce1 bytes and the extranonce2 size.
+///
+/// A translation proxy uses these to size the SV2 extranonce: extranonce1 is the part fixed by the
+/// upstream and extranonce2_size is the part left to the miner.
+#[allow(clippy::result_large_err)]
+pub fn parse_subscribe_result(value: &Value) -> Result<(Vec<u8>, usize), Error<'static>> {
+    let result = value
+        .as_array()
+        .ok_or_else(|| Error::InvalidSubscribeResult(value.clone()))?;
+    match &result[..] {
+        [JArrary(_), JString(extra_nonce1), JNumber(extra_nonce2_size)] => {
+            let extra_nonce2_size = extra_nonce2_size
+                .as_u64()
+                .ok_or_else(|| Error::InvalidSubscribeResult(value.clone()))?
+                as usize;
+            Ok((hex::decode(extra_nonce1)?, extra_nonce2_size))
+        }
+        _ => Err(Error::InvalidSubscribeResult(value.clone())),
+    }
+}
+
+/// The `result` of a `mining.subscribe` response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscribeResult {
+    /// `(method, subscription id)` pairs, e.g. `("mining.notify", "ae6812eb")`.
+    pub subscriptions: Vec<(String, String)>,
+    pub extranonce1: Vec<u8>,
+    pub extranonce2_size: usize,
+}
+
+impl SubscribeResult {
+    /// Parses the `result` field of a `mining.subscribe` response, see [`parse_subscribe_result`].
+    ///
+    /// Besides the usual list of pairs, a single pair not wrapped in a list is accepted as
+    /// subscriptions, as some pools send it that way.
+    #[allow(clippy::result_large_err)]
+    pub fn from_value(value: &Value) -> Result<Self, Error<'static>> {
+        let (extranonce1, extranonce2_size) = parse_subscribe_result(value)?;
+        let invalid = || Error::InvalidSubscribeResult(value.clone());
+        let pair = |pair: &Value| match pair.as_array().map(|p| &p[..]) {
+            Some([JString(method), JString(id)]) => Ok((method.clone(), id.clone())),
+            _ => Err(invalid()),
+        };
+        let subscriptions = match &value[0] {
+            single @ JArrary(p) if matches!(p.first(), Some(JString(_))) => {
+                vec![pair(single)?]
+            }
+            JArrary(pairs) => pairs.iter().map(pair).collect::<Result<_, _>>()?,
+            _ => return Err(invalid()),
+        };
+        Ok(Self {
+            subscriptions,
+            extranonce1,
+            extranonce2_size,
+        })
+    }
+}
+
+#[test]
+fn subscribe_result_parsing() {
+    let result = serde_json::json!([
+        [["mining.notify", "ae6812eb4cd7735a302a8a9dd95cf71f"]],
+        "08000002",
+        4
+    ]);
+    let (extra_nonce1, extra_nonce2_size) = parse_subscribe_result(&result).unwrap();
+    assert_eq!(extra_nonce1, vec![0x08, 0x00, 0x00, 0x02]);
+    assert_eq!(extra_nonce2_size, 4);
+}
+
+#[test]
+fn subscribe_request_with_and_without_user_agent() {
+    use crate::client_to_server::{mining_subscribe, Subscribe};
+
+    let request = mining_subscribe(1, Some("cgminer/4.10.0"), None);
+    assert_eq!(request.method, "mining.subscribe");
+    assert_eq!(request.params, serde_json::json!(["cgminer/4.10.0"]));
+    let subscribe = Subscribe::try_from(request).unwrap();
+    assert_eq!(subscribe.id, 1);
+    assert_eq!(subscribe.agent_signature, "cgminer/4.10.0");
+    assert!(subscribe.extranonce1.is_none());
+
+    let request = mining_subscribe(2, None, None);
+    assert_eq!(request.params, serde_json::json!([]));
+    assert_eq!(Subscribe::try_from(request).unwrap().agent_signature, "");
+
+    let request = mining_subscribe(3, None, Some("08000002"));
+    assert_eq!(request.params, serde_json::json!(["", "08000002"]));
+    let subscribe = Subscribe::try_from(request).unwrap();
+    assert_eq!(subscribe.agent_signature, "");
+    assert_eq!(
+        subscribe.extranonce1.unwrap().0.inner_as_ref(),
+        &[0x08, 0x00, 0x00, 0x02]
+    );
+}
+
+#[test]
+fn typical_subscribe_result() {
+    let result = serde_json::json!([
+        [
+            ["mining.set_difficulty", "b4b6693b72a50c7116db18d6497cac52"],
+            ["mining.notify", "ae6812eb4cd7735a302a8a9dd95cf71f"]
+        ],
+        "08000002",
+        4
+    ]);
+    let parsed = SubscribeResult::from_value(&result).unwrap();
+    assert_eq!(
+        parsed.subscriptions,
+        vec![
+            (
+                "mining.set_difficulty".to_string(),
+                "b4b6693b72a50c7116db18d6497cac52".to_string()
+            ),
+            (
+                "mining.notify".to_string(),
+                "ae6812eb4cd7735a302a8a9dd95cf71f".to_string()
+            ),
+        ]
+    );
+    assert_eq!(parsed.extranonce1, vec![0x08, 0x00, 0x00, 0x02]);
+    assert_eq!(parsed.extranonce2_size, 4);
+
+    // a single pair not wrapped in a list
+    let result = serde_json::json!([["mining.notify", "ae6812eb"], "08000002", 4]);
+    let parsed = SubscribeResult::from_value(&result).unwrap();
+    assert_eq!(
+        parsed.subscriptions,
+        vec![("mining.notify".to_string(), "ae6812eb".to_string())]
+    );
+
+    let result = serde_json::json!([[["mining.notify"]], "08000002", 4]);
+    match SubscribeResult::from_value(&result) {
+        Err(Error::InvalidSubscribeResult(v)) => assert_eq!(v, result),
+        other => panic!("expected InvalidSubscribeResult, got {:?}", other),
+    }
+}
+
+#[test]
+fn subscribe_result_parsing_missing_extranonce2_size() {
+    let result = serde_json::json!([
+        [["mining.notify", "ae6812eb4cd7735a302a8a9dd95cf71f"]],
+        "08000002"
+    ]);
+    match parse_subscribe_result(&result) {
+        Err(Error::InvalidSubscribeResult(v)) => assert_eq!(v, result),
+        other => panic!("expected InvalidSubscribeResult, got {:?}", other),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Configure {
     pub id: u64,
@@ -481,52 +756,8 @@ impl TryFrom<&Response> for Configure {
             ParsingMethodError::ImpossibleToParseResultField(Box::new(msg.clone()))
         })?;
 
-        let version_rolling_ = params.get("version-rolling");
-        let version_rolling_mask = params.get("version-rolling.mask");
-        let version_rolling_min_bit_count = params.get("version-rolling.min-bit-count");
         let minimum_difficulty = params.get("minimum-difficulty");
-
-        // Deserialize version-rolling response.
-        // Composed by 3 fields:
-        //   version-rolling (required),
-        //   version-rolling.mask (required)
-        //   version-rolling.min-bit-count (optional)
-        let version_rolling: Option<VersionRollingParams>;
-        if version_rolling_.is_some() && version_rolling_mask.is_some() {
-            let vr: bool = version_rolling_
-                .unwrap()
-                .as_bool()
-                .ok_or_else(|| ParsingMethodError::UnexpectedObjectParams(params.clone()))?;
-
-            let version_rolling_mask: HexU32Be = version_rolling_mask
-                .unwrap()
-                .as_str()
-                .ok_or_else(|| ParsingMethodError::UnexpectedObjectParams(params.clone()))?
-                .try_into()?;
-
-            // version-rolling.min-bit-count is often not returned by stratum servers,
-            // but min-bit-count should be taken into consideration in the returned mask
-            let version_rolling_min_bit_count: HexU32Be = match version_rolling_min_bit_count {
-                Some(version_rolling_min_bit_count) => version_rolling_min_bit_count
-                    .as_str()
-                    .ok_or_else(|| ParsingMethodError::UnexpectedObjectParams(params.clone()))?
-                    .try_into()?,
-                None => HexU32Be(0),
-            };
-
-            version_rolling = Some(VersionRollingParams {
-                version_rolling: vr,
-                version_rolling_mask,
-                version_rolling_min_bit_count,
-            });
-        } else if version_rolling_.is_none()
-            && version_rolling_mask.is_none()
-            && version_rolling_min_bit_count.is_none()
-        {
-            version_rolling = None;
-        } else {
-            return Err(ParsingMethodError::UnexpectedObjectParams(params.clone()));
-        };
+        let version_rolling = VersionRollingParams::from_result(params)?;
 
         let minimum_difficulty = match minimum_difficulty {
             Some(a) => Some(
@@ -544,7 +775,7 @@ impl TryFrom<&Response> for Configure {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VersionRollingParams {
     pub version_rolling: bool,
     pub version_rolling_mask: HexU32Be,
@@ -616,6 +847,116 @@ impl VersionRollingParams {
     }
 }
 
+/// Result of a `mining.configure` request for the `version-rolling` extension, i.e. the
+/// `version-rolling` fields of the [`Configure`] response.
+///
+/// The mask can only contain the bits of `0x1FFFE000`, a result with any other bit set is rejected
+/// when deserialized with [`Error::InvalidVersionMask`].
+pub type ConfigureResult = VersionRollingParams;
+
+impl VersionRollingParams {
+    /// Parses the `version-rolling` fields of the result of a `mining.configure` response, `None`
+    /// if there are none.
+    fn from_result(
+        params: &serde_json::Map<String, Value>,
+    ) -> Result<Option<Self>, ParsingMethodError> {
+        let version_rolling_ = params.get("version-rolling");
+        let version_rolling_mask = params.get("version-rolling.mask");
+        let version_rolling_min_bit_count = params.get("version-rolling.min-bit-count");
+
+        // Deserialize version-rolling response.
+        // Composed by 3 fields:
+        //   version-rolling (required),
+        //   version-rolling.mask (required)
+        //   version-rolling.min-bit-count (optional)
+        if version_rolling_.is_some() && version_rolling_mask.is_some() {
+            let vr: bool = version_rolling_
+                .unwrap()
+                .as_bool()
+                .ok_or_else(|| ParsingMethodError::UnexpectedObjectParams(params.clone()))?;
+
+            let version_rolling_mask: HexU32Be = version_rolling_mask
+                .unwrap()
+                .as_str()
+                .ok_or_else(|| ParsingMethodError::UnexpectedObjectParams(params.clone()))?
+                .try_into()?;
+
+            // version-rolling.min-bit-count is often not returned by stratum servers,
+            // but min-bit-count should be taken into consideration in the returned mask
+            let version_rolling_min_bit_count: HexU32Be = match version_rolling_min_bit_count {
+                Some(version_rolling_min_bit_count) => version_rolling_min_bit_count
+                    .as_str()
+                    .ok_or_else(|| ParsingMethodError::UnexpectedObjectParams(params.clone()))?
+                    .try_into()?,
+                None => HexU32Be(0),
+            };
+
+            Ok(Some(VersionRollingParams {
+                version_rolling: vr,
+                version_rolling_mask,
+                version_rolling_min_bit_count,
+            }))
+        } else if version_rolling_.is_none()
+            && version_rolling_mask.is_none()
+            && version_rolling_min_bit_count.is_none()
+        {
+            Ok(None)
+        } else {
+            Err(ParsingMethodError::UnexpectedObjectParams(params.clone()))
+        }
+    }
+
+    /// Negotiates version rolling with a miner that sent `params`: the mask is the intersection of
+    /// the requested mask and `server_mask`, restricted to the bits that can be rolled.
+    #[allow(clippy::result_large_err)]
+    pub fn negotiate(
+        params: &ConfigureParams,
+        server_mask: HexU32Be,
+    ) -> Result<Self, Error<'static>> {
+        let min_bit_count = params.min_bit_count.clone().unwrap_or(HexU32Be(0));
+        VersionRollingParams::new(HexU32Be(params.mask.0 & server_mask.0), min_bit_count)
+    }
+
+    /// Builds the response to the `mining.configure` request with id `id`.
+    pub fn into_response(self, id: u64) -> Response {
+        let configure = Configure {
+            id,
+            version_rolling: Some(self),
+            minimum_difficulty: None,
+        };
+        match Message::from(configure) {
+            Message::OkResponse(response) => response,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Serialize for VersionRollingParams {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde_json::Map::from(self.clone()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionRollingParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let params = serde_json::Map::deserialize(deserializer)?;
+        let result = VersionRollingParams::from_result(&params)
+            .map_err(|e| D::Error::custom(format!("invalid version-rolling result: {:?}", e)))?
+            .ok_or_else(|| D::Error::missing_field("version-rolling"))?;
+        let allowed = VersionRollingParams::new(
+            result.version_rolling_mask.clone(),
+            result.version_rolling_min_bit_count.clone(),
+        )
+        .map_err(D::Error::custom)?;
+        if allowed.version_rolling_mask != result.version_rolling_mask {
+            return Err(D::Error::custom(Error::InvalidVersionMask(
+                result.version_rolling_mask,
+            )));
+        }
+        Ok(result)
+    }
+}
+
 impl From<VersionRollingParams> for serde_json::Map<String, Value> {
     fn from(vp: VersionRollingParams) -> Self {
         let version_rolling: Value = vp.version_rolling.into();
@@ -631,3 +972,110 @@ impl From<VersionRollingParams> for serde_json::Map<String, Value> {
         params
     }
 }
+
+#[test]
+fn set_difficulty_notification() {
+    let notification: Notification = SetDifficulty::new(1024.5).unwrap().into();
+    assert_eq!(notification.method, "mining.set_difficulty");
+    assert_eq!(notification.params, serde_json::json!([1024.5]));
+
+    for invalid in [f64::NAN, f64::INFINITY, -1.0, 0.0] {
+        assert!(matches!(
+            SetDifficulty::new(invalid),
+            Err(Error::InvalidDifficulty(_))
+        ));
+    }
+}
+
+#[test]
+fn mining_notify_params() {
+    let prev_hash = "00".repeat(31) + "01";
+    let merkle_node = "ab".repeat(32);
+    let notification = mining_notify(
+        "bf".to_string(),
+        prev_hash.as_str().try_into().unwrap(),
+        vec![0x01, 0x02].into(),
+        vec![0x03].into(),
+        vec![merkle_node.as_str().try_into().unwrap()],
+        0x2000_0000,
+        0x1703_4219,
+        0x6436_eddf,
+        true,
+    );
+    assert_eq!(notification.method, "mining.notify");
+    assert_eq!(
+        notification.params,
+        serde_json::json!([
+            "bf",
+            prev_hash,
+            "0102",
+            "03",
+            [merkle_node],
+            "20000000",
+            "17034219",
+            "6436eddf",
+            true
+        ])
+    );
+    assert!(parse_notify_clean_jobs(&notification.params).unwrap());
+    assert!(Notify::try_from(notification).is_ok());
+}
+
+#[test]
+fn parse_clean_jobs() {
+    let params = serde_json::json!([
+        "bf",
+        "00".repeat(32),
+        "0102",
+        "03",
+        [],
+        "20000000",
+        "17034219",
+        "6436eddf",
+        true
+    ]);
+    assert!(parse_notify_clean_jobs(&params).unwrap());
+
+    // clean_jobs missing
+    let mut missing = params.as_array().unwrap().clone();
+    missing.pop();
+    assert!(matches!(
+        parse_notify_clean_jobs(&Value::Array(missing)),
+        Err(ParsingMethodError::WrongArgs(_))
+    ));
+
+    // clean_jobs not in last position
+    let mut misplaced = params.as_array().unwrap().clone();
+    misplaced.swap(7, 8);
+    assert!(parse_notify_clean_jobs(&Value::Array(misplaced)).is_err());
+}
+
+#[test]
+fn configure_result_from_params() {
+    let params = ConfigureParams {
+        mask: HexU32Be(0xffff_ffff),
+        min_bit_count: Some(HexU32Be(2)),
+    };
+    let result = ConfigureResult::negotiate(&params, HexU32Be(0x1fff_e000)).unwrap();
+    assert!(result.version_rolling);
+    assert_eq!(result.version_rolling_mask, HexU32Be(0x1fff_e000));
+
+    let response = result.clone().into_response(1);
+    assert_eq!(
+        response.result,
+        serde_json::json!({
+            "version-rolling": true,
+            "version-rolling.mask": "1fffe000",
+            "version-rolling.min-bit-count": "00000002"
+        })
+    );
+    let parsed: ConfigureResult = serde_json::from_value(response.result.clone()).unwrap();
+    assert_eq!(parsed, result);
+    // and the same as the existing mining.configure response parsing
+    let configure = Configure::try_from(&response).unwrap();
+    assert_eq!(configure.version_rolling, Some(result));
+
+    // the mask can not contain bits outside of 0x1fffe000
+    let invalid = serde_json::json!({"version-rolling": true, "version-rolling.mask": "ffffffff"});
+    assert!(serde_json::from_value::<ConfigureResult>(invalid).is_err());
+}
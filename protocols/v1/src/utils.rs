@@ -4,7 +4,11 @@ use bitcoin_hashes::hex::{FromHex, ToHex};
 use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{convert::TryFrom, mem::size_of, ops::BitAnd};
+use std::{
+    convert::{TryFrom, TryInto},
+    mem::size_of,
+    ops::BitAnd,
+};
 
 /// Helper type that allows simple serialization and deserialization of byte vectors
 /// that are represented as hex strings in JSON.
@@ -24,7 +28,7 @@ impl<'a> Extranonce<'a> {
 impl<'a> TryFrom<Vec<u8>> for Extranonce<'a> {
     type Error = Error<'a>;
     fn try_from(value: Vec<u8>) -> Result<Self, Error<'a>> {
-        Ok(Extranonce(B032::try_from(value)?))
+        Ok(Extranonce(try_b032("extranonce", &value)?))
     }
 }
 
@@ -40,6 +44,26 @@ impl<'a> From<Extranonce<'a>> for Value {
     }
 }
 
+/// Converts `bytes` into a [`B032`], failing with an error that names `field` and the actual
+/// length if `bytes` is longer than 32 bytes.
+#[allow(clippy::result_large_err)]
+pub fn try_b032<'a>(field: &'static str, bytes: &[u8]) -> Result<B032<'a>, Error<'a>> {
+    if bytes.len() > 32 {
+        return Err(Error::InvalidLength(field, "at most 32", bytes.len()));
+    }
+    Ok(B032::try_from(bytes.to_vec())?)
+}
+
+/// Converts `bytes` into a [`U256`], failing with an error that names `field` and the actual
+/// length if `bytes` is not exactly 32 bytes long.
+#[allow(clippy::result_large_err)]
+pub fn try_u256<'a>(field: &'static str, bytes: &[u8]) -> Result<U256<'a>, Error<'a>> {
+    if bytes.len() != 32 {
+        return Err(Error::InvalidLength(field, "exactly 32", bytes.len()));
+    }
+    Ok(U256::try_from(bytes.to_vec())?)
+}
+
 /// fix for error on odd-length hex sequences
 /// FIXME: find a nicer solution
 fn hex_decode(s: &str) -> Result<Vec<u8>, Error<'static>> {
@@ -54,7 +78,7 @@ impl<'a> TryFrom<&str> for Extranonce<'a> {
     type Error = error::Error<'a>;
 
     fn try_from(value: &str) -> Result<Self, Error<'a>> {
-        Ok(Extranonce(B032::try_from(hex_decode(value)?)?))
+        Ok(Extranonce(try_b032("extranonce", &hex_decode(value)?)?))
     }
 }
 
@@ -123,6 +147,38 @@ impl From<u32> for HexU32Be {
     }
 }
 
+/// Encodes `value` as SV1 does for `ntime`, `nonce` and `version`: 8 hex characters, big-endian.
+pub fn hex_be_u32(value: u32) -> String {
+    HexU32Be(value).into()
+}
+
+/// Parses a big-endian hex `u32` as encoded by [`hex_be_u32`].
+///
+/// Unlike the [`HexU32Be`] conversion, that left pads short inputs, `s` must be exactly 8 hex
+/// characters long.
+#[allow(clippy::result_large_err)]
+pub fn parse_hex_be_u32(s: &str) -> Result<u32, Error<'static>> {
+    let bytes = hex::decode(s)?;
+    let bytes: [u8; 4] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| Error::InvalidLength("u32", "exactly 4", bytes.len()))?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+/// Encodes `bytes` as lowercase hex, in the order they appear in the [`B032`].
+pub fn b032_to_hex(bytes: &B032) -> String {
+    hex::encode(bytes.inner_as_ref())
+}
+
+/// Decodes a [`B032`] from the hex encoding produced by [`b032_to_hex`].
+///
+/// Errors on odd-length input and on more than 32 bytes.
+#[allow(clippy::result_large_err)]
+pub fn b032_from_hex(s: &str) -> Result<B032<'static>, Error<'static>> {
+    try_b032("B032", &hex::decode(s)?)
+}
+
 //Example of serialization for testing purpose
 impl Serialize for HexU32Be {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -250,8 +306,7 @@ impl<'a> TryFrom<Vec<u8>> for MerkleNode<'a> {
     type Error = Error<'a>;
 
     fn try_from(value: Vec<u8>) -> Result<Self, Error<'a>> {
-        //TODO handle error
-        Ok(MerkleNode(U256::try_from(value).unwrap()))
+        Ok(MerkleNode(try_u256("merkle node", &value)?))
     }
 }
 
@@ -278,8 +333,7 @@ impl<'a> TryFrom<&str> for MerkleNode<'a> {
     type Error = Error<'a>;
 
     fn try_from(value: &str) -> Result<Self, Error<'a>> {
-        //TODO handle error
-        Ok(MerkleNode(U256::try_from(hex_decode(value)?).unwrap()))
+        Ok(MerkleNode(try_u256("merkle node", &hex_decode(value)?)?))
     }
 }
 
@@ -396,4 +450,60 @@ mod tests {
 
         be_hex == back_to_hex && be_hex == value_to_string
     }
+
+    #[test]
+    fn test_try_b032_reports_length() {
+        assert_eq!(
+            try_b032("extranonce", &[0; 32]).unwrap().to_vec(),
+            vec![0; 32]
+        );
+        let err = try_b032("extranonce", &[0; 33]).unwrap_err();
+        assert!(matches!(err, Error::InvalidLength("extranonce", _, 33)));
+        let message = err.to_string();
+        assert!(message.contains("extranonce"));
+        assert!(message.contains("got 33 bytes"));
+    }
+
+    #[test]
+    fn test_try_u256_reports_length() {
+        assert!(try_u256("merkle node", &[0; 32]).is_ok());
+        let message = try_u256("merkle node", &[0; 31]).unwrap_err().to_string();
+        assert!(message.contains("merkle node"));
+        assert!(message.contains("got 31 bytes"));
+    }
+
+    #[test]
+    fn test_extranonce_too_long() {
+        let err = Extranonce::try_from(vec![0; 40]).unwrap_err();
+        assert!(err.to_string().contains("got 40 bytes"));
+    }
+
+    #[test]
+    fn test_hex_be_u32() {
+        assert_eq!(hex_be_u32(0x0102_0304), "01020304");
+        assert_eq!(parse_hex_be_u32("01020304").unwrap(), 0x0102_0304);
+        assert_eq!(
+            parse_hex_be_u32(&hex_be_u32(0xdead_beef)).unwrap(),
+            0xdead_beef
+        );
+        assert!(matches!(
+            parse_hex_be_u32("0102030"),
+            Err(Error::HexError(hex::FromHexError::OddLength))
+        ));
+        assert!(matches!(
+            parse_hex_be_u32("0102"),
+            Err(Error::InvalidLength("u32", _, 2))
+        ));
+    }
+
+    #[test]
+    fn test_b032_hex() {
+        let bytes = b032_from_hex("0102030405").unwrap();
+        assert_eq!(bytes.inner_as_ref(), &[1, 2, 3, 4, 5]);
+        assert_eq!(b032_to_hex(&bytes), "0102030405");
+        assert!(matches!(
+            b032_from_hex("010"),
+            Err(Error::HexError(hex::FromHexError::OddLength))
+        ));
+    }
 }
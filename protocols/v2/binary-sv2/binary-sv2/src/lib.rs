@@ -55,6 +55,40 @@ pub fn u256_from_int<V: Into<u64>>(value: V) -> U256<'static> {
     u256
 }
 
+/// Encodes and decodes a type with whichever codec is enabled (`binary_codec_sv2` by default,
+/// `serde_sv2` with the `with_serde` feature), so that callers do not need feature-gated code.
+///
+/// It is implemented for every type that derives [`Serialize`] and [`Deserialize`].
+pub trait Sv2Codec<'a>: Sized {
+    /// Serializes `self` into a new buffer.
+    fn encode(&self) -> core::result::Result<alloc::vec::Vec<u8>, Error>;
+    /// Deserializes a value from `bytes`, the returned value can borrow from `bytes`.
+    fn decode(bytes: &'a mut [u8]) -> core::result::Result<Self, Error>;
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a, T: Serialize + Deserialize<'a> + GetSize + Clone> Sv2Codec<'a> for T {
+    fn encode(&self) -> core::result::Result<alloc::vec::Vec<u8>, Error> {
+        // `binary_codec_sv2` encodes by value
+        to_bytes(self.clone())
+    }
+
+    fn decode(bytes: &'a mut [u8]) -> core::result::Result<Self, Error> {
+        from_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "with_serde")]
+impl<'a, T: Serialize + Deserialize<'a>> Sv2Codec<'a> for T {
+    fn encode(&self) -> core::result::Result<alloc::vec::Vec<u8>, Error> {
+        to_bytes(self)
+    }
+
+    fn decode(bytes: &'a mut [u8]) -> core::result::Result<Self, Error> {
+        from_bytes(bytes)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
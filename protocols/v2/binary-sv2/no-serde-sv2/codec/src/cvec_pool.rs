@@ -0,0 +1,117 @@
+// # CVec Pool
+//
+// Converting a message to its C representation copies every variable-size field in a freshly
+// allocated buffer, that is freed again when the C message is dropped. Proxies doing it for every
+// share (or template) hit the allocator a lot for buffers that always have about the same size.
+//
+// `CVecPool` keeps the buffers of the dropped `CVec`s (and the outer buffers of the `CVec2`s) and
+// hands them out again on the next conversion. It is opt-in: the plain `From` conversions keep
+// allocating, the C messages that support pooling expose a `from_pooled`/`recycle` pair.
+
+use crate::{CVec, CVec2, Seq064K};
+use alloc::vec::Vec;
+
+/// A bounded pool of buffers backing [`CVec`]s and [`CVec2`]s.
+///
+/// Buffers are taken with [`CVecPool::cvec_from_slice`] and [`CVecPool::cvec2_from_seq064k`], and
+/// given back with [`CVecPool::release`] and [`CVecPool::release_2`]. At most `capacity` buffers
+/// of each kind are kept, the ones released when the pool is full are freed.
+#[derive(Debug)]
+pub struct CVecPool {
+    buffers: Vec<Vec<u8>>,
+    lists: Vec<Vec<CVec>>,
+    capacity: usize,
+}
+
+impl CVecPool {
+    /// Creates an empty pool keeping at most `capacity` byte buffers and `capacity` lists.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffers: Vec::with_capacity(capacity),
+            lists: Vec::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Number of byte buffers ready to be reused.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// Copies `v` in a pooled buffer, allocating only if the pool is empty or the buffer is too
+    /// small.
+    pub fn cvec_from_slice(&mut self, v: &[u8]) -> CVec {
+        let mut buffer = self.buffers.pop().unwrap_or_default();
+        buffer.clear();
+        buffer.extend_from_slice(v);
+        into_cvec(buffer)
+    }
+
+    /// Like `From<Seq064K<T>> for CVec2` but both the list and its elements use pooled buffers.
+    pub fn cvec2_from_seq064k<T: AsRef<[u8]>>(&mut self, v: &Seq064K<'_, T>) -> CVec2 {
+        let mut list = self.lists.pop().unwrap_or_default();
+        list.clear();
+        for element in &v.0 {
+            let cvec = self.cvec_from_slice(element.as_ref());
+            list.push(cvec);
+        }
+        // Get the length, first, then the pointer (doing it the other way around **currently**
+        // doesn't cause UB, but it may be unsound due to unclear (to me, at least) guarantees of
+        // the std lib)
+        let len = list.len();
+        let capacity = list.capacity();
+        let data = list.as_mut_ptr();
+        core::mem::forget(list);
+        CVec2 {
+            data,
+            len,
+            capacity,
+        }
+    }
+
+    /// Takes back the buffer of `buf`, leaving it empty so that freeing it afterwards is a no-op.
+    ///
+    /// `buf` must own its buffer, i.e. it must not have been built with
+    /// [`CVec::as_shared_buffer`].
+    pub fn release(&mut self, buf: &mut CVec) {
+        let buffer = unsafe { Vec::from_raw_parts(buf.data, buf.len, buf.capacity) };
+        *buf = into_cvec(Vec::new());
+        if self.buffers.len() < self.capacity {
+            self.buffers.push(buffer);
+        }
+    }
+
+    /// Takes back the buffers of `buf` and of all its elements, leaving it empty.
+    pub fn release_2(&mut self, buf: &mut CVec2) {
+        let mut list = unsafe { Vec::from_raw_parts(buf.data, buf.len, buf.capacity) };
+        for cvec in list.iter_mut() {
+            self.release(cvec);
+        }
+        list.clear();
+        let mut empty = Vec::<CVec>::new();
+        *buf = CVec2 {
+            data: empty.as_mut_ptr(),
+            len: 0,
+            capacity: 0,
+        };
+        if self.lists.len() < self.capacity {
+            self.lists.push(list);
+        }
+    }
+}
+
+fn into_cvec(mut buffer: Vec<u8>) -> CVec {
+    let len = buffer.len();
+    let capacity = buffer.capacity();
+    let data = buffer.as_mut_ptr();
+    core::mem::forget(buffer);
+    CVec {
+        data,
+        len,
+        capacity,
+    }
+}
@@ -20,6 +20,7 @@
 // - Represents a sequence of up to 65535 elements.
 // - Similar to `Seq0255`, it provides:
 //   - `to_vec()` and `inner_as_ref()` methods to convert or reference each element.
+//   - `iter()` to borrow the elements without collecting them.
 //   - `new()` enforces the maximum size limit, preventing excess memory usage.
 // - Like `Seq0255`, `Seq064K` is `Decodable` and implements `GetSize`, making it versatile for
 //   serialization scenarios.
@@ -193,6 +194,11 @@ impl<'a, T: 'a> Seq064K<'a, T> {
     pub fn into_inner(self) -> Vec<T> {
         self.0
     }
+
+    /// Iterates over the elements of the sequence without allocating.
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.0.iter()
+    }
 }
 
 impl<'a, T: GetSize> GetSize for Seq064K<'a, T> {
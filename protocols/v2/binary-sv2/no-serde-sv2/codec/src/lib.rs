@@ -73,6 +73,7 @@
 //! - `CVec`: Represents a byte vector for safe passing between C and Rust.
 //! - `CError`: A C-compatible error type.
 //! - `CVec2`: Manages collections of `CVec` objects across FFI boundaries.
+//! - `CVecPool`: Reuses the buffers of `CVec`s and `CVec2`s for hot conversion paths.
 //!
 //! Facilitates integration of SV2 functionality into cross-language projects.
 
@@ -82,7 +83,9 @@
 use std::io::{Error as E, ErrorKind};
 
 mod codec;
+mod cvec_pool;
 mod datatypes;
+pub use cvec_pool::CVecPool;
 pub use datatypes::{
     PubKey, Seq0255, Seq064K, ShortTxId, Signature, Str0255, Sv2DataType, Sv2Option, U32AsRef,
     B016M, B0255, B032, B064K, U24, U256,
@@ -592,16 +595,31 @@ impl From<CVec2> for Vec<CVec> {
 }
 
 /// Frees the underlying memory of a `CVec`.
+///
+/// In debug builds `buf` is poisoned once freed and freeing it again panics instead of freeing the
+/// memory twice. Only `buf` itself is poisoned, not the copies made before freeing it.
 pub fn free_vec(buf: &mut CVec) {
+    debug_assert!(!buf.data.is_null(), "CVec freed twice");
     let _: Vec<u8> = unsafe { Vec::from_raw_parts(buf.data, buf.len, buf.capacity) };
+    #[cfg(debug_assertions)]
+    {
+        buf.data = core::ptr::null_mut();
+    }
 }
 
 /// Frees the underlying memory of a `CVec2` and all its elements.
+///
+/// Like [`free_vec`], freeing the same `CVec2` twice panics in debug builds.
 pub fn free_vec_2(buf: &mut CVec2) {
+    debug_assert!(!buf.data.is_null(), "CVec2 freed twice");
     let vs: Vec<CVec> = unsafe { Vec::from_raw_parts(buf.data, buf.len, buf.capacity) };
     for mut s in vs {
         free_vec(&mut s)
     }
+    #[cfg(debug_assertions)]
+    {
+        buf.data = core::ptr::null_mut();
+    }
 }
 
 impl<'a, const A: bool, const B: usize, const C: usize, const D: usize>
@@ -728,3 +746,51 @@ pub extern "C" fn _c_export_cvec(_a: CVec) {}
 /// Exported FFI functions for interoperability with C code for CVec2
 #[no_mangle]
 pub extern "C" fn _c_export_cvec2(_a: CVec2) {}
+
+// No FFI calls and no leaks, so that these can also run under miri.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::TryFrom;
+
+    #[test]
+    fn free_vec_once() {
+        let mut buf = CVec::from(&[1, 2, 3][..]);
+        assert_eq!(buf.as_mut_slice(), &[1, 2, 3]);
+        free_vec(&mut buf);
+
+        let elements = vec![
+            B064K::try_from(vec![1, 2]).unwrap(),
+            B064K::try_from(vec![3]).unwrap(),
+        ];
+        let mut list = CVec2::from(Seq064K::new(elements).unwrap());
+        assert_eq!(list.as_mut_slice()[0].as_mut_slice(), &[1, 2]);
+        assert_eq!(list.as_mut_slice()[1].as_mut_slice(), &[3]);
+        free_vec_2(&mut list);
+
+        // a buffer taken back by a pool is left empty and freed as usual
+        let mut pool = CVecPool::new(1);
+        let mut buf = pool.cvec_from_slice(&[4, 5]);
+        pool.release(&mut buf);
+        free_vec(&mut buf);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "CVec freed twice")]
+    fn free_vec_twice_panics() {
+        let mut buf = CVec::from(&[1, 2, 3][..]);
+        free_vec(&mut buf);
+        free_vec(&mut buf);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "CVec2 freed twice")]
+    fn free_vec_2_twice_panics() {
+        let elements = vec![B064K::try_from(vec![1]).unwrap()];
+        let mut list = CVec2::from(Seq064K::new(elements).unwrap());
+        free_vec_2(&mut list);
+        free_vec_2(&mut list);
+    }
+}
@@ -133,9 +133,9 @@ impl<'de> Deserializer<'de> {
 
     #[inline]
     fn parse_u64(&mut self) -> Result<u64> {
-        let u32_ = self.get_slice(8)?;
+        let u64_ = self.get_slice(8)?;
         Ok(u64::from_le_bytes([
-            u32_[0], u32_[1], u32_[2], u32_[3], u32_[0], u32_[1], u32_[2], u32_[3],
+            u64_[0], u64_[1], u64_[2], u64_[3], u64_[4], u64_[5], u64_[6], u64_[7],
         ]))
     }
 
@@ -542,6 +542,29 @@ fn test_struct() {
     assert_eq!(deserialized, expected);
 }
 
+#[test]
+fn test_u64() {
+    use serde::Serialize;
+
+    #[derive(Deserialize, Serialize, PartialEq, Debug)]
+    struct Test {
+        a: u64,
+        b: u64,
+    }
+
+    // the high and low 4 bytes differ, so that reading either half twice is caught
+    let expected = Test {
+        a: 0x0102_0304_0506_0708,
+        b: u64::MAX - 1,
+    };
+
+    let mut bytes = crate::ser::to_bytes(&expected).unwrap();
+    assert_eq!(&bytes[..8], &[8, 7, 6, 5, 4, 3, 2, 1]);
+    let deserialized: Test = from_bytes(&mut bytes[..]).unwrap();
+
+    assert_eq!(deserialized, expected);
+}
+
 #[test]
 fn test_b0255() {
     use serde::Serialize;
@@ -7,6 +7,7 @@ use crate::{
     Error, ShortTxId,
 };
 use alloc::vec::Vec;
+use core::convert::TryInto;
 use serde::{ser, ser::SerializeTuple, Deserialize, Deserializer, Serialize};
 
 #[derive(Debug, Clone)]
@@ -445,15 +446,26 @@ impl<'s> Seq064K<'s, B064K<'s>> {
 }
 impl<'s> Seq064K<'s, B016M<'s>> {
     pub fn into_static(self) -> Seq064K<'static, B016M<'static>> {
-        if let Some(inner) = self.data {
-            let inner = inner.clone();
-            let data: Vec<B016M<'static>> = inner.into_iter().map(|i| i.into_static()).collect();
-            Seq064K {
-                seq: None,
-                data: Some(data),
+        let data: Vec<B016M<'static>> = match (self.seq, self.data) {
+            (_, Some(inner)) => inner.into_iter().map(|i| i.into_static()).collect(),
+            // decoded from bytes, the elements are still length prefixed in the input buffer
+            (Some(seq), None) => {
+                let mut elements = Vec::new();
+                let mut rest = seq.data;
+                while rest.len() >= 3 {
+                    let len = u32::from_le_bytes([rest[0], rest[1], rest[2], 0]) as usize;
+                    let (element, tail) = rest[3..].split_at(len);
+                    // a 3 bytes length always fits in a B016M
+                    elements.push(element.to_vec().try_into().unwrap());
+                    rest = tail;
+                }
+                elements
             }
-        } else {
-            panic!()
+            (None, None) => panic!(),
+        };
+        Seq064K {
+            seq: None,
+            data: Some(data),
         }
     }
     pub fn to_vec(&self) -> Vec<Vec<u8>> {
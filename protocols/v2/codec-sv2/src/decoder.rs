@@ -29,6 +29,7 @@ use binary_sv2::Deserialize;
 use binary_sv2::GetSize;
 use binary_sv2::Serialize;
 pub use buffer_sv2::AeadBuffer;
+use const_sv2::SV2_FRAME_MAX_PAYLOAD_LEN;
 #[allow(unused_imports)]
 pub use const_sv2::{SV2_FRAME_CHUNK_SIZE, SV2_FRAME_HEADER_SIZE};
 use core::marker::PhantomData;
@@ -43,9 +44,7 @@ use framing_sv2::{
 #[cfg(feature = "noise_sv2")]
 use noise_sv2::NoiseCodec;
 
-#[cfg(feature = "noise_sv2")]
-use crate::error::Error;
-use crate::error::Result;
+use crate::error::{Error, Result};
 
 use crate::Error::MissingBytes;
 #[cfg(feature = "noise_sv2")]
@@ -124,6 +123,9 @@ pub struct WithNoise<B: IsBuffer, T: Serialize + binary_sv2::GetSize> {
     //
     // Stores the decrypted data until it is ready to be processed and converted into a Sv2 frame.
     sv2_buffer: B,
+
+    // Largest payload accepted, frames announcing a bigger one are rejected before buffering it.
+    max_payload_len: usize,
 }
 
 #[cfg(feature = "noise_sv2")]
@@ -138,6 +140,11 @@ impl<'a, T: Serialize + GetSize + Deserialize<'a>, B: IsBuffer + AeadBuffer> Wit
     /// `writable`, read another chunk from the incoming message stream, and then call `next_frame`
     /// again. This process should be repeated until `next_frame` returns `Ok`, indicating that the
     /// full message has been received, and the decoding and decryption of the frame can proceed.
+    ///
+    /// A header announcing a payload over `max_payload_len` is rejected with
+    /// `Error::PayloadTooLarge`. The connection must then be dropped: the payload is never
+    /// decrypted, so the receiving cipher nonce no longer matches the sender's and every later
+    /// frame would fail to decrypt. Skipping the payload in the stream does not recover from it.
     #[inline]
     pub fn next_frame(&mut self, state: &mut State) -> Result<Frame<T, B::Slice>> {
         match state {
@@ -250,6 +257,12 @@ impl<'a, T: Serialize + GetSize + Deserialize<'a>, B: IsBuffer + AeadBuffer> Wit
                 noise_codec.decrypt(&mut self.sv2_buffer)?;
                 let header =
                     Header::from_bytes(self.sv2_buffer.get_data_by_ref(SV2_FRAME_HEADER_SIZE))?;
+                if header.len() > self.max_payload_len {
+                    // drop the decrypted header, the caller has to close the connection as the
+                    // payload will not be decrypted and the nonces are now out of sync
+                    self.sv2_buffer.get_data_owned();
+                    return Err(Error::PayloadTooLarge(header.len(), self.max_payload_len));
+                }
                 self.missing_noise_b = header.encrypted_len();
                 Err(Error::MissingBytes(header.encrypted_len()))
             }
@@ -292,11 +305,19 @@ impl<T: Serialize + binary_sv2::GetSize> WithNoise<Buffer, T> {
     /// Initializes the decoder with default buffer sizes and sets the number of missing bytes to
     /// 0.
     pub fn new() -> Self {
+        Self::with_max_payload_len(SV2_FRAME_MAX_PAYLOAD_LEN)
+    }
+
+    /// Creates a new [`WithNoise`] decoder rejecting frames with a payload longer than
+    /// `max_payload_len` with [`Error::PayloadTooLarge`], before reading the payload. After that
+    /// error the connection must be dropped, see [`WithNoise::next_frame`].
+    pub fn with_max_payload_len(max_payload_len: usize) -> Self {
         Self {
             frame: PhantomData,
             missing_noise_b: 0,
             noise_buffer: Buffer::new(2_usize.pow(16) * 5),
             sv2_buffer: Buffer::new(2_usize.pow(16) * 5),
+            max_payload_len,
         }
     }
 }
@@ -333,6 +354,9 @@ pub struct WithoutNoise<B: IsBuffer, T: Serialize + binary_sv2::GetSize> {
     // necessary bytes until a full frame is available. Once the full encoded frame has been
     // received, the buffer's contents are processed and decoded into an Sv2 frame.
     buffer: B,
+
+    // Largest payload accepted, frames announcing a bigger one are rejected before buffering it.
+    max_payload_len: usize,
 }
 
 impl<T: Serialize + binary_sv2::GetSize, B: IsBuffer> WithoutNoise<B, T> {
@@ -348,10 +372,23 @@ impl<T: Serialize + binary_sv2::GetSize, B: IsBuffer> WithoutNoise<B, T> {
     /// `writable`, read another chunk from the incoming message stream, and then call `next_frame`
     /// again. This process should be repeated until `next_frame` returns `Ok`, indicating that the
     /// full message has been received, and the frame can be fully decoded.
+    ///
+    /// Once the header is received, a payload longer than the decoder limit is rejected with
+    /// `Error::PayloadTooLarge`, without growing the buffer for it. The rejected header is
+    /// discarded and the decoder waits for a new header, but the announced payload is not
+    /// consumed: the caller must skip it in the stream or, more likely, drop the connection.
     #[inline]
     pub fn next_frame(&mut self) -> Result<Sv2Frame<T, B::Slice>> {
         let len = self.buffer.len();
         let src = self.buffer.get_data_by_ref(len);
+        if let Ok(header) = Header::from_bytes(src) {
+            if header.len() > self.max_payload_len {
+                self.missing_b = Header::SIZE;
+                self.buffer.get_data_owned();
+                return Err(Error::PayloadTooLarge(header.len(), self.max_payload_len));
+            }
+        }
+        let src = self.buffer.get_data_by_ref(len);
         let hint = Sv2Frame::<T, B::Slice>::size_hint(src) as usize;
 
         match hint {
@@ -385,10 +422,17 @@ impl<T: Serialize + binary_sv2::GetSize> WithoutNoise<Buffer, T> {
     /// Initializes the decoder with a default buffer size and sets the number of missing bytes to
     /// the size of the header.
     pub fn new() -> Self {
+        Self::with_max_payload_len(SV2_FRAME_MAX_PAYLOAD_LEN)
+    }
+
+    /// Creates a new [`WithoutNoise`] rejecting frames with a payload longer than
+    /// `max_payload_len` with [`Error::PayloadTooLarge`], before reading the payload.
+    pub fn with_max_payload_len(max_payload_len: usize) -> Self {
         Self {
             frame: PhantomData,
             missing_b: Header::SIZE,
             buffer: Buffer::new(2_usize.pow(16) * 5),
+            max_payload_len,
         }
     }
 }
@@ -404,9 +448,19 @@ mod tests {
     use super::*;
     use binary_sv2::{binary_codec_sv2, Serialize};
 
-    #[derive(Serialize)]
+    #[derive(Serialize, Debug)]
     pub struct TestMessage {}
 
+    // the noise decoder also needs the message to be decodable, and the derived code clashes with
+    // the crate `Error` and `Result` imported above
+    #[cfg(feature = "noise_sv2")]
+    mod decodable {
+        use binary_sv2::{binary_codec_sv2, Deserialize, Serialize};
+
+        #[derive(Serialize, Deserialize, Debug)]
+        pub struct DecodableMessage {}
+    }
+
     #[test]
     fn unencrypted_writable_with_missing_b_initialized_as_header_size() {
         let mut decoder = StandardDecoder::<TestMessage>::new();
@@ -414,4 +468,109 @@ mod tests {
         let expect = [0u8; Header::SIZE];
         assert_eq!(actual, expect);
     }
+
+    #[test]
+    fn unencrypted_rejects_payload_over_max_len() {
+        let mut decoder = StandardDecoder::<TestMessage>::with_max_payload_len(100);
+        // extension type 0, message type 0x1b, payload length 0x0f4240 (1_000_000) in little endian
+        decoder
+            .writable()
+            .copy_from_slice(&[0x00, 0x00, 0x1b, 0x40, 0x42, 0x0f]);
+
+        assert_eq!(
+            decoder.next_frame().unwrap_err(),
+            Error::PayloadTooLarge(1_000_000, 100)
+        );
+        // the rejected header is dropped and the decoder asks for a new one, not for the payload
+        assert_eq!(decoder.writable().len(), Header::SIZE);
+    }
+
+    #[test]
+    fn unencrypted_decodes_next_frame_after_rejecting_payload() {
+        let mut decoder = StandardDecoder::<TestMessage>::with_max_payload_len(100);
+        decoder
+            .writable()
+            .copy_from_slice(&[0x00, 0x00, 0x1b, 0x40, 0x42, 0x0f]);
+        assert_eq!(
+            decoder.next_frame().unwrap_err(),
+            Error::PayloadTooLarge(1_000_000, 100)
+        );
+
+        // a frame with an empty payload, received after the caller skipped the rejected one
+        decoder
+            .writable()
+            .copy_from_slice(&[0x00, 0x00, 0x1b, 0x00, 0x00, 0x00]);
+        let frame = decoder.next_frame().unwrap();
+        let header = frame.get_header().unwrap();
+        assert_eq!(header.msg_type(), 0x1b);
+        assert_eq!(header.len(), 0);
+    }
+
+    #[cfg(feature = "noise_sv2")]
+    #[test]
+    fn noise_decodes_next_frame_after_rejecting_payload() {
+        use key_utils::{Secp256k1PublicKey, Secp256k1SecretKey};
+        use noise_sv2::{Initiator, Responder};
+
+        let secret: Secp256k1SecretKey = "mkDLTBBRxdBv998612qipDYoTK3YUrqLe8uWw7gu3iXbSrn2n"
+            .parse()
+            .unwrap();
+        let public = Secp256k1PublicKey::from(secret);
+        let mut initiator = Initiator::without_pk().unwrap();
+        let mut responder = Responder::from_authority_kp(
+            &public.into_bytes(),
+            &secret.into_bytes(),
+            core::time::Duration::from_secs(3600),
+        )
+        .unwrap();
+        let (message, responder_codec) = responder.step_1(initiator.step_0().unwrap()).unwrap();
+        let mut initiator_codec = initiator.step_2(message).unwrap();
+        let mut encrypt = |bytes: &[u8]| {
+            let mut bytes = bytes.to_vec();
+            initiator_codec.encrypt(&mut bytes).unwrap();
+            bytes
+        };
+
+        let mut state = State::with_transport_mode(responder_codec);
+        let mut decoder =
+            StandardNoiseDecoder::<decodable::DecodableMessage>::with_max_payload_len(100);
+        assert_eq!(
+            decoder.next_frame(&mut state).unwrap_err(),
+            Error::MissingBytes(NOISE_HEADER_ENCRYPTED_SIZE)
+        );
+        decoder
+            .writable()
+            .copy_from_slice(&encrypt(&[0x00, 0x00, 0x1b, 0x40, 0x42, 0x0f]));
+        assert_eq!(
+            decoder.next_frame(&mut state).unwrap_err(),
+            Error::PayloadTooLarge(1_000_000, 100)
+        );
+
+        // a frame with a 1 byte payload, received after the caller skipped the rejected one
+        let header = encrypt(&[0x00, 0x00, 0x1b, 0x01, 0x00, 0x00]);
+        let payload = encrypt(&[0x01]);
+        decoder.writable().copy_from_slice(&header);
+        assert_eq!(
+            decoder.next_frame(&mut state).unwrap_err(),
+            Error::MissingBytes(payload.len())
+        );
+        decoder.writable().copy_from_slice(&payload);
+        match decoder.next_frame(&mut state).unwrap() {
+            Frame::Sv2(frame) => {
+                let header = frame.get_header().unwrap();
+                assert_eq!(header.msg_type(), 0x1b);
+                assert_eq!(header.len(), 1);
+            }
+            Frame::HandShake(_) => panic!("expected an Sv2 frame"),
+        }
+    }
+
+    #[test]
+    fn unencrypted_accepts_payload_up_to_max_len() {
+        let mut decoder = StandardDecoder::<TestMessage>::with_max_payload_len(100);
+        decoder
+            .writable()
+            .copy_from_slice(&[0x00, 0x00, 0x1b, 0x64, 0x00, 0x00]);
+        assert_eq!(decoder.next_frame().unwrap_err(), Error::MissingBytes(100));
+    }
 }
@@ -47,6 +47,9 @@ pub enum Error {
     /// Incomplete frame with the number of missing bytes remaining to completion.
     MissingBytes(usize),
 
+    /// Frame header announcing a payload of `.0` bytes, over the `.1` bytes limit of the decoder.
+    PayloadTooLarge(usize, usize),
+
     /// Sv2 Noise protocol error.
     #[cfg(feature = "noise_sv2")]
     NoiseSv2Error(NoiseError),
@@ -79,6 +82,11 @@ impl fmt::Display for Error {
                 "This noise handshake step can not be executed by a responder"
             ),
             MissingBytes(u) => write!(f, "Missing `{}` Noise bytes", u),
+            PayloadTooLarge(len, max) => write!(
+                f,
+                "Frame payload of `{}` bytes exceeds the maximum of `{}` bytes",
+                len, max
+            ),
             #[cfg(feature = "noise_sv2")]
             NoiseSv2Error(e) => write!(f, "Noise SV2 Error: `{:?}`", e),
             #[cfg(feature = "noise_sv2")]
@@ -147,6 +155,9 @@ pub enum CError {
     /// Missing bytes in the Noise protocol.
     MissingBytes(usize),
 
+    /// Frame payload over the decoder limit.
+    PayloadTooLarge(usize, usize),
+
     /// Sv2 Noise protocol error.
     NoiseSv2Error,
 
@@ -180,6 +191,7 @@ impl From<Error> for CError {
             #[cfg(feature = "noise_sv2")]
             Error::InvalidStepForResponder => CError::InvalidStepForResponder,
             Error::MissingBytes(u) => CError::MissingBytes(u),
+            Error::PayloadTooLarge(len, max) => CError::PayloadTooLarge(len, max),
             #[cfg(feature = "noise_sv2")]
             Error::NoiseSv2Error(_) => CError::NoiseSv2Error,
             #[cfg(feature = "noise_sv2")]
@@ -199,6 +211,7 @@ impl Drop for CError {
             CError::InvalidStepForInitiator => (),
             CError::InvalidStepForResponder => (),
             CError::MissingBytes(_) => (),
+            CError::PayloadTooLarge(_, _) => (),
             CError::NoiseSv2Error => (),
             CError::NotInHandShakeState => (),
             CError::UnexpectedNoiseState => (),
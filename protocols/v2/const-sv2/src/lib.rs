@@ -74,6 +74,10 @@ pub const NOISE_FRAME_HEADER_SIZE: usize = 2;
 // const there is not even used
 pub const NOISE_FRAME_HEADER_LEN_OFFSET: usize = 0;
 
+/// Maximum payload length of an SV2 frame, the largest value of the 24 bits `msg_length` field of
+/// the header.
+pub const SV2_FRAME_MAX_PAYLOAD_LEN: usize = (1 << 24) - 1;
+
 // It's not used anywhere.
 // Refactoring: deprecate it.
 pub const NOISE_FRAME_MAX_SIZE: usize = u16::MAX as usize;
@@ -129,6 +133,9 @@ pub const NOISE_HASHED_PROTOCOL_NAME_CHACHA: [u8; 32] = [
 // Refactoring: deprecate it.
 pub const NOISE_SUPPORTED_CIPHERS_MESSAGE: [u8; 5] = [1, 0x47, 0x53, 0x45, 0x41];
 
+/// Stratum V2 protocol version, `SetupConnection` version ranges must include it.
+pub const SV2_PROTOCOL_VERSION: u16 = 2;
+
 // Discriminants for distinct Stratum V2 (sub)protocols. More info at https://github.com/stratum-
 // mining/sv2-spec/blob/main/03-Protocol-Overview.md#3-protocol-overview
 pub const SV2_MINING_PROTOCOL_DISCRIMINANT: u8 = 0;
@@ -14,6 +14,8 @@ pub enum Error {
     ExpectedHandshakeFrame,
     ExpectedSv2Frame,
     UnexpectedHeaderLength(isize),
+    /// Frame shorter than its header length field, with the number of missing bytes.
+    MissingBytes(usize),
 }
 
 impl fmt::Display for Error {
@@ -37,6 +39,9 @@ impl fmt::Display for Error {
                     const_sv2::SV2_FRAME_HEADER_SIZE
                 )
             }
+            MissingBytes(missing) => {
+                write!(f, "Frame is missing `{}` bytes of payload", missing)
+            }
         }
     }
 }
@@ -289,6 +289,37 @@ pub fn handshake_message_to_frame<T: AsRef<[u8]>>(message: T) -> HandShakeFrame
     }
 }
 
+/// Prepends the 6-byte Sv2 header to an already encoded, plain (not encrypted) `payload`.
+///
+/// Returns [`None`] if the payload does not fit in a frame, as [`Sv2Frame::from_message`].
+pub fn frame_payload(
+    payload: &[u8],
+    message_type: u8,
+    extension_type: u16,
+    channel_msg: bool,
+) -> Option<Vec<u8>> {
+    let extension_type = update_extension_type(extension_type, channel_msg);
+    let len = u32::try_from(payload.len()).ok()?;
+    let header = Header::from_len(len, message_type, extension_type)?;
+    let mut frame = Vec::with_capacity(Header::SIZE + payload.len());
+    frame.extend_from_slice(&header.ext_type().to_le_bytes());
+    frame.push(header.msg_type());
+    frame.extend_from_slice(&len.to_le_bytes()[..3]);
+    frame.extend_from_slice(payload);
+    Some(frame)
+}
+
+/// Splits a plain (not encrypted) Sv2 frame at the start of `bytes` into its message type and
+/// payload, any bytes after the frame are ignored.
+pub fn unframe_payload(bytes: &[u8]) -> Result<(u8, &[u8]), Error> {
+    let header = Header::from_bytes(bytes)?;
+    let end = Header::SIZE + header.len();
+    if bytes.len() < end {
+        return Err(Error::MissingBytes(end - bytes.len()));
+    }
+    Ok((header.msg_type(), &bytes[Header::SIZE..end]))
+}
+
 // Basically a Boolean bit filter for `extension_type`.
 //
 // Takes an `extension_type` represented as a `u16` and a Boolean flag (`channel_msg`). If
@@ -316,3 +347,47 @@ fn test_size_hint() {
     let h = Sv2Frame::<T, Vec<u8>>::size_hint(&[0, 128, 30, 46, 0, 0][..]);
     assert!(h == 46);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binary_sv2::{from_bytes, to_bytes};
+    use common_messages_sv2::SetupConnectionSuccess;
+    use const_sv2::MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS;
+
+    #[test]
+    fn test_frame_unframe_setup_connection_success() {
+        let message = SetupConnectionSuccess {
+            used_version: 2,
+            flags: 0b110,
+        };
+        let payload = to_bytes(message).unwrap();
+        let frame =
+            frame_payload(&payload, MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS, 0, false).unwrap();
+        assert_eq!(frame.len(), Header::SIZE + payload.len());
+        assert_eq!(
+            &frame[..Header::SIZE],
+            &[0, 0, MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS, 6, 0, 0]
+        );
+
+        let (msg_type, framed_payload) = unframe_payload(&frame).unwrap();
+        assert_eq!(msg_type, MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS);
+        let mut framed_payload = framed_payload.to_vec();
+        let decoded: SetupConnectionSuccess = from_bytes(&mut framed_payload).unwrap();
+        assert_eq!(decoded.used_version, 2);
+        assert_eq!(decoded.flags, 0b110);
+    }
+
+    #[test]
+    fn test_unframe_truncated_payload() {
+        let frame = frame_payload(&[1, 2, 3, 4], 0x01, 0, false).unwrap();
+        assert_eq!(
+            unframe_payload(&frame[..frame.len() - 1]),
+            Err(Error::MissingBytes(1))
+        );
+        assert_eq!(
+            unframe_payload(&frame[..3]),
+            Err(Error::UnexpectedHeaderLength(3))
+        );
+    }
+}
@@ -74,10 +74,10 @@ impl Header {
         })
     }
 
-    // Get the payload length
+    /// Returns the payload length announced by the header.
     #[allow(clippy::len_without_is_empty)]
     #[inline]
-    pub(crate) fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         let inner: u32 = self.msg_length.into();
         inner as usize
     }
@@ -3,6 +3,7 @@ use crate::{
     common_properties::StandardChannel,
     job_creator::{self, JobsCreators},
     parsers::Mining,
+    str0255_ext::str0255,
     utils::{GroupId, Id, Mutex},
     Error,
 };
@@ -897,12 +898,7 @@ impl ChannelFactory {
             let error = SubmitSharesError {
                 channel_id: m.get_channel_id(),
                 sequence_number: m.get_sequence_number(),
-                // Infallible unwrap we already know the len of the error code (is a
-                // static string)
-                error_code: SubmitSharesError::difficulty_too_low_error_code()
-                    .to_string()
-                    .try_into()
-                    .unwrap(),
+                error_code: str0255(SubmitSharesError::difficulty_too_low_error_code())?,
             };
             Ok(OnNewShare::SendErrorDownstream(error))
         }
@@ -1135,10 +1131,7 @@ impl PoolChannelFactory {
                 let err = SubmitSharesError {
                     channel_id: m.channel_id,
                     sequence_number: m.sequence_number,
-                    error_code: SubmitSharesError::invalid_channel_error_code()
-                        .to_string()
-                        .try_into()
-                        .unwrap(),
+                    error_code: str0255(SubmitSharesError::invalid_channel_error_code())?,
                 };
                 Ok(OnNewShare::SendErrorDownstream(err))
             }
@@ -1510,12 +1503,7 @@ impl ProxyExtendedChannelFactory {
             let error = SubmitSharesError {
                 channel_id: m.channel_id,
                 sequence_number: m.sequence_number,
-                // Infallible unwrap we already know the len of the error code (is a
-                // static string)
-                error_code: SubmitSharesError::invalid_job_id_error_code()
-                    .to_string()
-                    .try_into()
-                    .unwrap(),
+                error_code: str0255(SubmitSharesError::invalid_job_id_error_code())?,
             };
             return Ok(OnNewShare::SendErrorDownstream(error));
         }
@@ -1660,10 +1648,7 @@ impl ProxyExtendedChannelFactory {
                 let err = SubmitSharesError {
                     channel_id: m.channel_id,
                     sequence_number: m.sequence_number,
-                    error_code: SubmitSharesError::invalid_channel_error_code()
-                        .to_string()
-                        .try_into()
-                        .unwrap(),
+                    error_code: str0255(SubmitSharesError::invalid_channel_error_code())?,
                 };
                 Ok(OnNewShare::SendErrorDownstream(err))
             }
@@ -1921,7 +1906,7 @@ mod test {
         // Build open standard channel
         let open_standard_channel = OpenStandardMiningChannel {
             request_id: 100.into(),
-            user_identity: "Gigi".to_string().try_into().unwrap(),
+            user_identity: str0255("Gigi").unwrap(),
             nominal_hash_rate: 100_000_000_000_000.0,
             max_target: [255; 32].try_into().unwrap(),
         };
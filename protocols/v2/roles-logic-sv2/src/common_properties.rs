@@ -2,7 +2,10 @@
 use crate::selectors::{
     DownstreamMiningSelector, DownstreamSelector, NullDownstreamMiningSelector,
 };
-use common_messages_sv2::{has_requires_std_job, Protocol, SetupConnection};
+use common_messages_sv2::{
+    has_requires_std_job, Protocol, SetupConnection, SetupConnectionSuccess,
+    REQUIRES_STANDARD_JOBS, REQUIRES_VERSION_ROLLING, REQUIRES_WORK_SELECTION,
+};
 use mining_sv2::{Extranonce, Target};
 use nohash_hasher::BuildNoHashHasher;
 use std::{collections::HashMap, fmt::Debug as D};
@@ -24,6 +27,48 @@ pub struct PairSettings {
     pub flags: u32,
 }
 
+/// What has been negotiated on a connection, proxies keep one for each downstream connection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConnectionState {
+    pub protocol: Protocol,
+    pub used_version: u16,
+    /// Flags sent by the downstream in the `SetupConnection` accepted by the upstream
+    pub flags: u32,
+}
+
+impl ConnectionState {
+    /// Builds the state of a connection from the downstream's `SetupConnection` and the
+    /// upstream's `SetupConnectionSuccess`.
+    pub fn from_negotiation(setup: &SetupConnection, success: &SetupConnectionSuccess) -> Self {
+        Self {
+            protocol: setup.protocol,
+            used_version: success.used_version,
+            flags: setup.flags,
+        }
+    }
+
+    /// Mining flags only have a meaning on mining connections, other protocols reuse the same bits
+    /// for different features.
+    fn has_mining_flag(&self, flag: u32) -> bool {
+        self.protocol == Protocol::MiningProtocol && self.flags & flag != 0
+    }
+
+    /// True if the downstream is a mining device that only supports standard jobs.
+    pub fn requires_standard_jobs(&self) -> bool {
+        self.has_mining_flag(REQUIRES_STANDARD_JOBS)
+    }
+
+    /// True if the downstream wants to select its own work (custom mining jobs).
+    pub fn supports_work_selection(&self) -> bool {
+        self.has_mining_flag(REQUIRES_WORK_SELECTION)
+    }
+
+    /// True if the downstream is going to roll the version field of the header.
+    pub fn supports_version_rolling(&self) -> bool {
+        self.has_mining_flag(REQUIRES_VERSION_ROLLING)
+    }
+}
+
 /// A trait that defines the basic properties of an upstream node.
 pub trait IsUpstream<Down: IsDownstream, Sel: DownstreamSelector<Down> + ?Sized> {
     /// Used to bitcoin protocol version for the channel.
@@ -189,6 +234,7 @@ impl RequestIdMapper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::str0255_ext::str0255;
 
     #[test]
     fn builds_request_id_mapper() {
@@ -227,4 +273,53 @@ mod tests {
         request_id_mapper.remove(0);
         assert!(request_id_mapper.request_ids_map.is_empty());
     }
+
+    fn setup_connection(protocol: Protocol, flags: u32) -> SetupConnection<'static> {
+        SetupConnection {
+            protocol,
+            min_version: 2,
+            max_version: 2,
+            flags,
+            endpoint_host: str0255("").unwrap(),
+            endpoint_port: 0,
+            vendor: str0255("").unwrap(),
+            hardware_version: str0255("").unwrap(),
+            firmware: str0255("").unwrap(),
+            device_id: str0255("").unwrap(),
+        }
+    }
+
+    #[test]
+    fn builds_connection_state_from_mining_negotiation() {
+        let setup = setup_connection(
+            Protocol::MiningProtocol,
+            REQUIRES_STANDARD_JOBS | REQUIRES_VERSION_ROLLING,
+        );
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: 0,
+        };
+        let state = ConnectionState::from_negotiation(&setup, &success);
+
+        assert_eq!(state.protocol, Protocol::MiningProtocol);
+        assert_eq!(state.used_version, 2);
+        assert!(state.requires_standard_jobs());
+        assert!(state.supports_version_rolling());
+        assert!(!state.supports_work_selection());
+    }
+
+    #[test]
+    fn mining_capabilities_only_apply_to_mining_connections() {
+        // bit 0 is `REQUIRES_ASYNC_JOB_MINING` for the job declaration protocol
+        let setup = setup_connection(Protocol::JobDeclarationProtocol, 0b111);
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: 0,
+        };
+        let state = ConnectionState::from_negotiation(&setup, &success);
+
+        assert!(!state.requires_standard_jobs());
+        assert!(!state.supports_version_rolling());
+        assert!(!state.supports_work_selection());
+    }
 }
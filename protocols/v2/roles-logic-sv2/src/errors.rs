@@ -61,6 +61,25 @@ pub enum Error {
     HashrateError(InputError),
     LogicErrorMessage(std::boxed::Box<AllMessages<'static>>),
     JDSMissingTransactions,
+    /// (`ntime`, min allowed `ntime`, max allowed `ntime`)
+    InvalidNtime(u32, u32, u32),
+    IoError(std::io::Error),
+    /// (expected frame length, bytes read before the stream ended)
+    TruncatedFrame(usize, usize),
+    /// A string of the given len in bytes does not fit in a `Str0255` (max 255 bytes)
+    StringTooLong(usize),
+    /// A share references a downstream job id with no known upstream job id
+    UnknownJobId(u32),
+    /// A message references a template id that was never sent in a `NewTemplate`
+    UnknownTemplateId(u64),
+    /// A `NewTemplate` reuses the id of a template already issued
+    DuplicateTemplateId(u64),
+    /// Every template id up to `u64::MAX` has been issued
+    TemplateIdsExhausted,
+    /// A `Str0255` field does not contain valid UTF-8
+    InvalidUtf8(std::str::Utf8Error),
+    /// (expected extranonce len, extranonce prefix len + share extranonce len)
+    ExtranonceLenMismatch(usize, usize),
 }
 
 impl From<BinarySv2Error> for Error {
@@ -153,6 +172,16 @@ impl Display for Error {
             HashrateError(e) => write!(f, "Impossible to get Hashrate: {:?}", e),
             LogicErrorMessage(e) => write!(f, "Message is well formatted but can not be handled: {:?}", e),
             JDSMissingTransactions => write!(f, "JD server cannot propagate the block: missing transactions"),
+            InvalidNtime(ntime, min, max) => write!(f, "Invalid ntime {}: must be between {} and {}", ntime, min, max),
+            IoError(e) => write!(f, "I/O error: {}", e),
+            TruncatedFrame(expected, read) => write!(f, "Stream ended in the middle of a frame: expected {} bytes, read {}", expected, read),
+            StringTooLong(len) => write!(f, "String is {} bytes long but a Str0255 can hold at most 255 bytes", len),
+            UnknownJobId(id) => write!(f, "No upstream job id is mapped to the downstream job id {}", id),
+            UnknownTemplateId(id) => write!(f, "Template id {} was never issued in a NewTemplate", id),
+            DuplicateTemplateId(id) => write!(f, "Template id {} has already been issued in a NewTemplate", id),
+            TemplateIdsExhausted => write!(f, "No template id left to issue, every id up to u64::MAX has been issued"),
+            InvalidUtf8(e) => write!(f, "String field is not valid UTF-8: {}", e),
+            ExtranonceLenMismatch(expected, len) => write!(f, "Extranonce prefix and share extranonce are {} bytes long, the channel expects {}", len, expected),
         }
     }
 }
@@ -10,8 +10,8 @@ use crate::{
     Error,
 };
 use mining_sv2::{
-    NewExtendedMiningJob, NewMiningJob, SetNewPrevHash, SubmitSharesError, SubmitSharesStandard,
-    Target,
+    NewExtendedMiningJob, NewMiningJob, SetNewPrevHash, SubmitSharesError, SubmitSharesExtended,
+    SubmitSharesStandard, Target,
 };
 use nohash_hasher::BuildNoHashHasher;
 use std::{collections::HashMap, convert::TryInto, sync::Arc};
@@ -245,6 +245,35 @@ impl GroupChannelJobDispatcher {
     }
 }
 
+/// Implemented by the shares a proxy forwards upstream, which must reference the upstream job id
+/// instead of the one the proxy sent downstream.
+pub trait JobIdRemapper {
+    /// Rewrites the job id of the share with the upstream job id mapped to it in `map`
+    /// (downstream job id -> upstream job id).
+    ///
+    /// Returns [`Error::UnknownJobId`] and leaves the share untouched if the job id is not in
+    /// `map`.
+    fn remap_job_id(&mut self, map: &HashMap<u32, u32>) -> Result<(), Error>;
+}
+
+impl JobIdRemapper for SubmitSharesStandard {
+    fn remap_job_id(&mut self, map: &HashMap<u32, u32>) -> Result<(), Error> {
+        self.job_id = *map
+            .get(&self.job_id)
+            .ok_or(Error::UnknownJobId(self.job_id))?;
+        Ok(())
+    }
+}
+
+impl<'a> JobIdRemapper for SubmitSharesExtended<'a> {
+    fn remap_job_id(&mut self, map: &HashMap<u32, u32>) -> Result<(), Error> {
+        self.job_id = *map
+            .get(&self.job_id)
+            .ok_or(Error::UnknownJobId(self.job_id))?;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +283,7 @@ mod tests {
             tests::{new_pub_key, template_from_gen},
             JobsCreators,
         },
+        str0255_ext::str0255,
     };
     use binary_sv2::{u256_from_int, U256};
     use mining_sv2::Extranonce;
@@ -538,10 +568,7 @@ mod tests {
                     );
                     assert_eq!(err.channel_id, standard_channel_id);
                     assert_eq!(err.sequence_number, shares.sequence_number);
-                    assert_eq!(
-                        err.error_code,
-                        "".to_string().into_bytes().try_into().unwrap()
-                    );
+                    assert_eq!(err.error_code, str0255("").unwrap());
                 }
             };
         }
@@ -594,6 +621,29 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_remap_job_id() {
+        let map: HashMap<u32, u32> = [(1, 100), (2, 200)].iter().copied().collect();
+
+        let mut share = SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 2,
+            nonce: 0,
+            ntime: 0,
+            version: 0x2000_0000,
+        };
+        share.remap_job_id(&map).unwrap();
+        assert_eq!(share.job_id, 200);
+
+        share.job_id = 3;
+        match share.remap_job_id(&map) {
+            Err(Error::UnknownJobId(3)) => (),
+            other => panic!("expected UnknownJobId(3), got {:?}", other),
+        }
+        assert_eq!(share.job_id, 3);
+    }
+
     pub mod utils {
         use super::*;
         use std::fmt::Write;
@@ -7,6 +7,7 @@
 //! - Routers in [`routing_logic`] are used by the traits in `handlers` to decide which
 //!   downstream/upstream to relay/send by using [`selectors`]
 //! - For serializing/deserializing messages, see [`parsers`]
+//! - [`prelude`] re-exports the messages, flags and error codes most roles need
 //! - see [`utils`] for helpers such as safe locking, target and merkle root calculations
 //!
 //!```txt
@@ -40,12 +41,51 @@ pub mod errors;
 pub mod handlers;
 pub mod job_creator;
 pub mod job_dispatcher;
+pub mod ntime_validator;
 pub mod parsers;
+pub mod prelude;
 pub mod routing_logic;
 pub mod selectors;
+pub mod share_accounting;
+pub mod share_decoder;
+pub mod share_validator;
+pub mod solution_dispatcher;
+pub mod str0255_ext;
+pub mod template_registry;
+#[cfg(all(test, not(feature = "with_serde")))]
+mod test_vectors;
 pub mod utils;
 pub use common_messages_sv2;
 pub use errors::Error;
 pub use job_declaration_sv2;
 pub use mining_sv2;
 pub use template_distribution_sv2;
+
+#[cfg(test)]
+mod tests {
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    // Pools share these across tasks (usually in an `Arc<Mutex<_>>`), a field that is not
+    // thread-safe would only surface as a `!Send` error in the role using them.
+    #[test]
+    fn shared_state_is_send_sync() {
+        assert_send_sync::<crate::ntime_validator::PrevHashClock>();
+        assert_send_sync::<crate::template_registry::PendingTxDataRequests>();
+        assert_send_sync::<crate::share_accounting::ShareRateLimiter>();
+        assert_send_sync::<crate::share_accounting::ShareAckBatcher>();
+        assert_send_sync::<crate::share_accounting::SeenShares>();
+        assert_send_sync::<crate::template_registry::TxDataCache>();
+        assert_send_sync::<crate::share_accounting::ShareStats>();
+        assert_send_sync::<crate::template_registry::TemplateRegistry>();
+        assert_send_sync::<crate::template_registry::TemplateIdSequencer>();
+        assert_send_sync::<crate::solution_dispatcher::SolutionDispatcher<'static>>();
+        assert_send_sync::<crate::utils::Mutex<crate::share_accounting::SeenShares>>();
+        assert_send_sync::<crate::common_properties::RequestIdMapper>();
+        assert_send_sync::<crate::common_properties::ConnectionState>();
+        assert_send_sync::<crate::share_validator::ChainedValidator>();
+        assert_send_sync::<crate::channel_logic::channel_factory::PoolChannelFactory>();
+        assert_send_sync::<crate::channel_logic::channel_factory::ProxyExtendedChannelFactory>();
+        assert_send_sync::<crate::job_creator::JobsCreators>();
+        assert_send_sync::<crate::job_dispatcher::GroupChannelJobDispatcher>();
+    }
+}
@@ -0,0 +1,267 @@
+//! Validation of the block header `nTime` carried by shares and solutions against the latest
+//! `SetNewPrevHash` of their channel.
+
+use crate::errors::Error;
+use mining_sv2::{SubmitSharesExtended, SubmitSharesStandard};
+use std::{convert::TryFrom, time::Instant};
+use template_distribution_sv2::SubmitSolution;
+
+/// Checks that `ntime` is greater than or equal to `prevhash_timestamp` and lower than or equal to
+/// `prevhash_timestamp` plus the number of seconds elapsed between `received_at` (when the latest
+/// `SetNewPrevHash` was received) and `now`.
+///
+/// This is the rule documented for [`SubmitSharesStandard::ntime`],
+/// [`SubmitSharesExtended::ntime`] and [`SubmitSolution::header_timestamp`].
+pub fn validate_ntime(
+    ntime: u32,
+    prevhash_timestamp: u32,
+    received_at: Instant,
+    now: Instant,
+) -> Result<(), Error> {
+    PrevHashClock::new(prevhash_timestamp, received_at).check(ntime, now)
+}
+
+/// Implemented by every message that carries a block header `nTime` bounded by the latest
+/// `SetNewPrevHash`, so that shares and solutions are validated with the same rule.
+pub trait NtimeValidator {
+    /// The `nTime` field of the block header built from this message.
+    fn ntime(&self) -> u32;
+
+    /// See [`validate_ntime`].
+    fn validate_ntime(
+        &self,
+        prevhash_timestamp: u32,
+        received_at: Instant,
+        now: Instant,
+    ) -> Result<(), Error> {
+        validate_ntime(self.ntime(), prevhash_timestamp, received_at, now)
+    }
+}
+
+impl NtimeValidator for SubmitSharesStandard {
+    fn ntime(&self) -> u32 {
+        self.ntime
+    }
+}
+
+impl<'a> NtimeValidator for SubmitSharesExtended<'a> {
+    fn ntime(&self) -> u32 {
+        self.ntime
+    }
+}
+
+impl<'a> NtimeValidator for SubmitSolution<'a> {
+    fn ntime(&self) -> u32 {
+        self.header_timestamp
+    }
+}
+
+/// Timestamp of the latest `SetNewPrevHash` of a channel and the instant it was received.
+///
+/// Shares and solutions can carry an `nTime` up to the prevhash timestamp plus the seconds elapsed
+/// since its receipt, validators keep one clock per channel and check messages against it.
+///
+/// The upper bound can be relaxed by a few seconds with [`PrevHashClock::with_future_tolerance`],
+/// to accept messages from a downstream whose clock is slightly ahead of the Template Provider's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrevHashClock {
+    prevhash_timestamp: u32,
+    received_at: Instant,
+    future_tolerance_secs: u32,
+}
+
+impl PrevHashClock {
+    pub fn new(prevhash_timestamp: u32, received_at: Instant) -> Self {
+        Self {
+            prevhash_timestamp,
+            received_at,
+            future_tolerance_secs: 0,
+        }
+    }
+
+    /// Accepts an `nTime` up to `future_tolerance_secs` after the strict upper bound, 0 (the
+    /// default) keeps the strict rule of [`validate_ntime`]. The tolerance is kept across updates.
+    pub fn with_future_tolerance(mut self, future_tolerance_secs: u32) -> Self {
+        self.future_tolerance_secs = future_tolerance_secs;
+        self
+    }
+
+    /// Restarts the clock on a new `SetNewPrevHash`.
+    pub fn update(&mut self, prevhash_timestamp: u32, received_at: Instant) {
+        self.prevhash_timestamp = prevhash_timestamp;
+        self.received_at = received_at;
+    }
+
+    pub fn prevhash_timestamp(&self) -> u32 {
+        self.prevhash_timestamp
+    }
+
+    pub fn received_at(&self) -> Instant {
+        self.received_at
+    }
+
+    /// Largest `nTime` a message received at `now` can carry, tolerance included.
+    pub fn max_allowed_ntime(&self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.received_at).as_secs();
+        let elapsed = u32::try_from(elapsed).unwrap_or(u32::MAX);
+        self.prevhash_timestamp
+            .saturating_add(elapsed)
+            .saturating_add(self.future_tolerance_secs)
+    }
+
+    /// Validates the `nTime` of a message received at `now`, see [`validate_ntime`].
+    pub fn validate<T: NtimeValidator + ?Sized>(
+        &self,
+        message: &T,
+        now: Instant,
+    ) -> Result<(), Error> {
+        self.check(message.ntime(), now)
+    }
+
+    fn check(&self, ntime: u32, now: Instant) -> Result<(), Error> {
+        let max_ntime = self.max_allowed_ntime(now);
+        if ntime < self.prevhash_timestamp || ntime > max_ntime {
+            return Err(Error::InvalidNtime(
+                ntime,
+                self.prevhash_timestamp,
+                max_ntime,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn share_with_ntime(ntime: u32) -> SubmitSharesStandard {
+        SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 0,
+            nonce: 0,
+            ntime,
+            version: 0x2000_0000,
+        }
+    }
+
+    #[test]
+    fn test_validate_ntime_before_prevhash() {
+        let received_at = Instant::now();
+        let now = received_at + std::time::Duration::from_secs(10);
+        let share = share_with_ntime(999);
+        assert!(matches!(
+            share.validate_ntime(1000, received_at, now),
+            Err(Error::InvalidNtime(999, 1000, 1010))
+        ));
+    }
+
+    #[test]
+    fn test_validate_ntime_upper_edge() {
+        let received_at = Instant::now();
+        let now = received_at + std::time::Duration::from_secs(10);
+        assert!(share_with_ntime(1000)
+            .validate_ntime(1000, received_at, now)
+            .is_ok());
+        assert!(share_with_ntime(1010)
+            .validate_ntime(1000, received_at, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_ntime_beyond_upper_edge() {
+        let received_at = Instant::now();
+        let now = received_at + std::time::Duration::from_secs(10);
+        let solution = SubmitSolution {
+            template_id: 0,
+            version: 0x2000_0000,
+            header_timestamp: 1011,
+            header_nonce: 0,
+            coinbase_tx: vec![0; 10].try_into().unwrap(),
+        };
+        assert!(matches!(
+            solution.validate_ntime(1000, received_at, now),
+            Err(Error::InvalidNtime(1011, 1000, 1010))
+        ));
+    }
+
+    #[test]
+    fn test_prev_hash_clock() {
+        let received_at = Instant::now();
+        let ten_seconds_later = received_at + std::time::Duration::from_secs(10);
+        let mut clock = PrevHashClock::new(1_700_000_000, received_at);
+        let share = share_with_ntime;
+
+        assert_eq!(clock.max_allowed_ntime(received_at), 1_700_000_000);
+        assert!(clock.validate(&share(1_700_000_000), received_at).is_ok());
+        assert!(clock.validate(&share(1_700_000_005), received_at).is_err());
+
+        assert_eq!(clock.max_allowed_ntime(ten_seconds_later), 1_700_000_010);
+        assert!(clock
+            .validate(&share(1_700_000_005), ten_seconds_later)
+            .is_ok());
+        assert!(clock
+            .validate(&share(1_700_000_010), ten_seconds_later)
+            .is_ok());
+        assert!(clock
+            .validate(&share(1_700_000_011), ten_seconds_later)
+            .is_err());
+
+        clock.update(1_700_000_600, ten_seconds_later);
+        assert_eq!(clock.max_allowed_ntime(ten_seconds_later), 1_700_000_600);
+        assert!(clock
+            .validate(&share(1_700_000_005), ten_seconds_later)
+            .is_err());
+    }
+
+    #[test]
+    fn test_prev_hash_clock_future_tolerance() {
+        let received_at = Instant::now();
+        let ten_seconds_later = received_at + std::time::Duration::from_secs(10);
+        let solution = |header_timestamp| SubmitSolution {
+            template_id: 0,
+            version: 0x2000_0000,
+            header_timestamp,
+            header_nonce: 0,
+            coinbase_tx: vec![0; 10].try_into().unwrap(),
+        };
+
+        // strict by default
+        let strict = PrevHashClock::new(1_700_000_000, received_at);
+        assert!(strict
+            .validate(&solution(1_700_000_011), ten_seconds_later)
+            .is_err());
+
+        let mut clock = strict.with_future_tolerance(2);
+        assert_eq!(clock.max_allowed_ntime(ten_seconds_later), 1_700_000_012);
+        assert!(clock
+            .validate(&solution(1_700_000_011), ten_seconds_later)
+            .is_ok());
+        assert!(clock
+            .validate(&solution(1_700_000_012), ten_seconds_later)
+            .is_ok());
+        assert!(matches!(
+            clock.validate(&solution(1_700_000_013), ten_seconds_later),
+            Err(Error::InvalidNtime(
+                1_700_000_013,
+                1_700_000_000,
+                1_700_000_012
+            ))
+        ));
+        // the lower bound is unchanged
+        assert!(clock
+            .validate(&solution(1_699_999_999), ten_seconds_later)
+            .is_err());
+
+        // kept across a new prevhash
+        clock.update(1_700_000_600, ten_seconds_later);
+        assert!(clock
+            .validate(&solution(1_700_000_602), ten_seconds_later)
+            .is_ok());
+        assert!(clock
+            .validate(&solution(1_700_000_603), ten_seconds_later)
+            .is_err());
+    }
+}
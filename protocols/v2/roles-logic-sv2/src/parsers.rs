@@ -1124,6 +1124,54 @@ impl<'a> TryFrom<(u8, &'a mut [u8])> for PoolMessages<'a> {
     }
 }
 
+/// Message type of a message of any subprotocol, as found in the frame header.
+#[derive(Debug, Clone, Copy)]
+pub enum MessageType {
+    Common(CommonMessageTypes),
+    Mining(MiningTypes),
+    JobDeclaration(JobDeclarationTypes),
+    TemplateDistribution(TemplateDistributionTypes),
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = Error;
+
+    fn try_from(v: u8) -> Result<MessageType, Error> {
+        // message types are unique across subprotocols, at most one of these succeeds
+        CommonMessageTypes::try_from(v)
+            .map(MessageType::Common)
+            .or_else(|_| MiningTypes::try_from(v).map(MessageType::Mining))
+            .or_else(|_| JobDeclarationTypes::try_from(v).map(MessageType::JobDeclaration))
+            .or_else(|_| {
+                TemplateDistributionTypes::try_from(v).map(MessageType::TemplateDistribution)
+            })
+    }
+}
+
+impl From<MessageType> for u8 {
+    fn from(v: MessageType) -> Self {
+        match v {
+            MessageType::Common(t) => t as u8,
+            MessageType::Mining(t) => t as u8,
+            MessageType::JobDeclaration(t) => t as u8,
+            MessageType::TemplateDistribution(t) => t as u8,
+        }
+    }
+}
+
+/// Decodes the payload of a frame carrying a message of type `msg_type`.
+///
+/// The message borrows its variable size fields from `payload`, which the decoder needs mutable.
+pub fn decode_message(msg_type: MessageType, payload: &mut [u8]) -> Result<AnyMessage<'_>, Error> {
+    let v = (u8::from(msg_type), payload);
+    match msg_type {
+        MessageType::Common(_) => Ok(AnyMessage::Common(v.try_into()?)),
+        MessageType::Mining(_) => Ok(AnyMessage::Mining(v.try_into()?)),
+        MessageType::JobDeclaration(_) => Ok(AnyMessage::JobDeclaration(v.try_into()?)),
+        MessageType::TemplateDistribution(_) => Ok(AnyMessage::TemplateDistribution(v.try_into()?)),
+    }
+}
+
 impl<'a> From<SetupConnection<'a>> for CommonMessages<'a> {
     fn from(v: SetupConnection<'a>) -> Self {
         CommonMessages::SetupConnection(v)
@@ -1224,3 +1272,68 @@ impl<'a> TryFrom<PoolMessages<'a>> for MiningDeviceMessages<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binary_sv2::B064K;
+
+    fn frame_bytes(message: AnyMessage<'static>) -> Vec<u8> {
+        let frame: Sv2Frame<AnyMessage<'static>, Vec<u8>> = message.try_into().unwrap();
+        let mut bytes = vec![0; frame.encoded_length()];
+        frame.serialize(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn decode_frame(bytes: Vec<u8>, check: impl FnOnce(AnyMessage)) {
+        let mut frame: Sv2Frame<AnyMessage<'static>, Vec<u8>> =
+            Sv2Frame::from_bytes(bytes).unwrap();
+        let msg_type = frame.get_header().unwrap().msg_type();
+        let msg_type = MessageType::try_from(msg_type).unwrap();
+        check(decode_message(msg_type, frame.payload()).unwrap());
+    }
+
+    #[test]
+    fn test_decode_framed_setup_connection() {
+        let setup = SetupConnection::new_mining("0.0.0.0", 3333, "SRI").unwrap();
+        let bytes = frame_bytes(setup.clone().into());
+        assert_eq!(
+            u8::from(MessageType::try_from(MESSAGE_TYPE_SETUP_CONNECTION).unwrap()),
+            MESSAGE_TYPE_SETUP_CONNECTION
+        );
+        decode_frame(bytes, |message| match message {
+            AnyMessage::Common(CommonMessages::SetupConnection(decoded)) => {
+                assert_eq!(decoded, setup)
+            }
+            message => panic!("expected SetupConnection, got {:?}", message),
+        });
+    }
+
+    #[test]
+    fn test_decode_framed_submit_solution() {
+        let solution = SubmitSolution {
+            template_id: 7,
+            version: 0x2000_0000,
+            header_timestamp: 1_700_000_000,
+            header_nonce: 42,
+            coinbase_tx: B064K::try_from(vec![1, 2, 3, 4]).unwrap(),
+        };
+        let bytes = frame_bytes(AnyMessage::TemplateDistribution(
+            TemplateDistribution::SubmitSolution(solution.clone()),
+        ));
+        decode_frame(bytes, |message| match message {
+            AnyMessage::TemplateDistribution(TemplateDistribution::SubmitSolution(decoded)) => {
+                assert_eq!(decoded, solution)
+            }
+            message => panic!("expected SubmitSolution, got {:?}", message),
+        });
+    }
+
+    #[test]
+    fn test_unknown_message_type() {
+        assert!(matches!(
+            MessageType::try_from(0xff),
+            Err(Error::UnexpectedMessage(0xff))
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+//! Re-exports the types most roles need, so that `use roles_logic_sv2::prelude::*` is enough to
+//! build, parse and answer the messages of every subprotocol.
+//!
+//! The prelude only re-exports: everything stays available at its usual path. Names that clash
+//! between subprotocols follow [`crate::parsers`]: the mining `SetNewPrevHash` is exported as
+//! `MiningSetNewPrevHash`, the template distribution one keeps its name.
+
+pub use crate::{
+    parsers::{
+        decode_message, AnyMessage, CommonMessages, JobDeclaration, MessageType, Mining,
+        PoolMessages, TemplateDistribution,
+    },
+    share_validator::SubmitShareRejectReason,
+};
+pub use common_messages_sv2::{
+    Capabilities, ChannelEndpointChanged, Protocol, SetupConnection, SetupConnectionError,
+    SetupConnectionErrorCode, SetupConnectionSuccess, TelemetryError, VersionOutcome,
+    REQUIRES_ASYNC_JOB_MINING, REQUIRES_EXTENDED_CHANNELS, REQUIRES_FIXED_VERSION,
+    REQUIRES_STANDARD_JOBS, REQUIRES_VERSION_ROLLING, REQUIRES_WORK_SELECTION,
+};
+pub use job_declaration_sv2::{
+    AllocateMiningJobToken, AllocateMiningJobTokenSuccess, DeclareMiningJob, DeclareMiningJobError,
+    DeclareMiningJobSuccess, IdentifyTransactions, IdentifyTransactionsSuccess,
+    ProvideMissingTransactions, ProvideMissingTransactionsSuccess, SubmitSolutionJd,
+};
+pub use mining_sv2::{
+    CloseChannel, NewExtendedMiningJob, NewMiningJob, OpenExtendedMiningChannel,
+    OpenExtendedMiningChannelSuccess, OpenMiningChannelError, OpenStandardMiningChannel,
+    OpenStandardMiningChannelSuccess, Reconnect, SetCustomMiningJob, SetCustomMiningJobError,
+    SetCustomMiningJobSuccess, SetExtranoncePrefix, SetGroupChannel,
+    SetNewPrevHash as MiningSetNewPrevHash, SetTarget, SubmitSharesError, SubmitSharesExtended,
+    SubmitSharesStandard, SubmitSharesSuccess, UpdateChannel, UpdateChannelError,
+};
+pub use template_distribution_sv2::{
+    CoinbaseOutputDataSize, NewTemplate, RequestTransactionData, RequestTransactionDataError,
+    RequestTransactionDataSuccess, SetNewPrevHash, SubmitSolution,
+};
+
+#[cfg(test)]
+mod tests {
+    // only the prelude is imported, this module fails to compile if it is missing something
+    use super::*;
+
+    #[test]
+    fn prelude_is_a_working_set() {
+        let setup = SetupConnection::new_mining("0.0.0.0", 34255, "SRI").unwrap();
+        assert_eq!(setup.protocol, Protocol::MiningProtocol);
+        assert_eq!(setup.flags, REQUIRES_VERSION_ROLLING);
+
+        let share = SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 1,
+            nonce: 0,
+            ntime: 0,
+            version: 0,
+        };
+        let error = SubmitShareRejectReason::StaleShare.to_submit_shares_error(&share);
+        let message: AnyMessage = PoolMessages::Mining(Mining::SubmitSharesError(error));
+        assert!(matches!(
+            message,
+            PoolMessages::Mining(Mining::SubmitSharesError(_))
+        ));
+    }
+}
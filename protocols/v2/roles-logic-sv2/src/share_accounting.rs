@@ -0,0 +1,462 @@
+//! Per channel bookkeeping of the shares received by an upstream: rate limiting, duplicate
+//! detection, acknowledgements and totals.
+
+use mining_sv2::{SubmitSharesExtended, SubmitSharesStandard, SubmitSharesSuccess};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+/// Per channel token bucket limiting the rate of `SubmitShares*` messages.
+///
+/// Every channel starts with `burst` tokens, each accepted share consumes one and tokens are
+/// refilled at `shares_per_second` up to `burst`. Ingest code calls [`ShareRateLimiter::allow`]
+/// before validating a share and drops it when it returns false.
+#[derive(Debug)]
+pub struct ShareRateLimiter {
+    shares_per_second: f64,
+    burst: f64,
+    buckets: HashMap<u32, TokenBucket>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl ShareRateLimiter {
+    pub fn new(shares_per_second: f64, burst: u32) -> Self {
+        Self {
+            shares_per_second,
+            burst: burst as f64,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Returns true if a share from `channel_id` received at `now` is within the rate limit.
+    pub fn allow(&mut self, channel_id: u32, now: Instant) -> bool {
+        let burst = self.burst;
+        let bucket = self.buckets.entry(channel_id).or_insert(TokenBucket {
+            tokens: burst,
+            last_refill: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.shares_per_second).min(burst);
+        bucket.last_refill = bucket.last_refill.max(now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Drops the state of a closed channel.
+    pub fn remove_channel(&mut self, channel_id: u32) {
+        self.buckets.remove(&channel_id);
+    }
+}
+
+/// Batches the acknowledgements of accepted shares into at most one `SubmitSharesSuccess` per
+/// channel per flush window.
+///
+/// Accepted shares are folded into the pending `SubmitSharesSuccess` of their channel, once `window`
+/// has elapsed since the last flush [`ShareAckBatcher::flush`] returns them and starts a new window.
+#[derive(Debug)]
+pub struct ShareAckBatcher {
+    window: Duration,
+    last_flush: Instant,
+    pending: HashMap<u32, SubmitSharesSuccess>,
+}
+
+impl ShareAckBatcher {
+    /// Creates a batcher whose first window starts at `now`.
+    pub fn new(window: Duration, now: Instant) -> Self {
+        Self {
+            window,
+            last_flush: now,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Records a share accepted on `channel_id` whose work is `share_work`.
+    pub fn on_share_accepted(&mut self, channel_id: u32, sequence_number: u32, share_work: u64) {
+        let success = self
+            .pending
+            .entry(channel_id)
+            .or_insert(SubmitSharesSuccess {
+                channel_id,
+                last_sequence_number: sequence_number,
+                new_submits_accepted_count: 0,
+                new_shares_sum: 0,
+            });
+        success.last_sequence_number = success.last_sequence_number.max(sequence_number);
+        success.new_submits_accepted_count = success.new_submits_accepted_count.saturating_add(1);
+        success.new_shares_sum = success.new_shares_sum.saturating_add(share_work);
+    }
+
+    /// Returns true if the current window is over at `now`.
+    pub fn is_due(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_flush) >= self.window
+    }
+
+    /// If the current window is over at `now` returns the `SubmitSharesSuccess` accumulated since
+    /// the last flush, ordered by channel id, and starts a new window. Otherwise returns nothing.
+    pub fn flush(&mut self, now: Instant) -> Vec<SubmitSharesSuccess> {
+        if !self.is_due(now) {
+            return vec![];
+        }
+        self.last_flush = now;
+        let mut successes: Vec<SubmitSharesSuccess> =
+            self.pending.drain().map(|(_, success)| success).collect();
+        successes.sort_by_key(|success| success.channel_id);
+        successes
+    }
+
+    /// Number of channels with shares waiting to be acknowledged.
+    pub fn pending_channels(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Running totals of the shares acknowledged by an upstream, built by recording every
+/// `SubmitSharesSuccess` received.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ShareStats {
+    /// channel id -> (accepted submits, shares sum)
+    channels: HashMap<u32, (u32, u64)>,
+}
+
+impl ShareStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Folds `success` into the totals of its channel.
+    pub fn record(&mut self, success: &SubmitSharesSuccess) {
+        let (accepted, shares_sum) = self.channels.entry(success.channel_id).or_insert((0, 0));
+        *accepted = accepted.saturating_add(success.new_submits_accepted_count);
+        *shares_sum = shares_sum.saturating_add(success.new_shares_sum);
+    }
+
+    /// Number of submits accepted on all the channels.
+    pub fn total_accepted(&self) -> u64 {
+        self.channels
+            .values()
+            .map(|(accepted, _)| *accepted as u64)
+            .sum()
+    }
+
+    /// Sum of the shares accepted on all the channels.
+    pub fn total_shares_sum(&self) -> u64 {
+        self.channels.values().fold(0, |total, (_, shares_sum)| {
+            total.saturating_add(*shares_sum)
+        })
+    }
+
+    /// Accepted submits and shares sum of each channel.
+    pub fn per_channel(&self) -> HashMap<u32, (u32, u64)> {
+        self.channels.clone()
+    }
+}
+
+/// Identifies a submitted share, two submissions with the same key are the same share.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ShareKey {
+    channel_id: u32,
+    job_id: u32,
+    nonce: u32,
+    ntime: u32,
+    version: u32,
+    kind: ShareKind,
+}
+
+/// Standard and extended shares are never the same share, even with an empty extranonce.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ShareKind {
+    Standard,
+    Extended { extranonce: Vec<u8> },
+}
+
+/// Bounded set of the shares already seen, used to reject duplicate submissions.
+///
+/// It holds at most `capacity` shares, when full the least recently used share is evicted, so
+/// memory stays constant no matter how many shares are received. Inserting a share that is
+/// already in the set counts as a use.
+#[derive(Debug)]
+pub struct SeenShares {
+    capacity: usize,
+    /// Every share in the set and the tick of its last use
+    last_use: HashMap<ShareKey, u64>,
+    /// The same shares ordered by last use, the first one is evicted
+    by_last_use: BTreeMap<u64, ShareKey>,
+    tick: u64,
+}
+
+impl SeenShares {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            last_use: HashMap::with_capacity(capacity),
+            by_last_use: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Records `share`, returns false if it has already been seen.
+    pub fn insert_if_new(&mut self, share: &SubmitSharesStandard) -> bool {
+        self.insert_key(ShareKey {
+            channel_id: share.channel_id,
+            job_id: share.job_id,
+            nonce: share.nonce,
+            ntime: share.ntime,
+            version: share.version,
+            kind: ShareKind::Standard,
+        })
+    }
+
+    /// Records `share`, returns false if it has already been seen. Shares that only differ in the
+    /// extranonce are different shares.
+    pub fn insert_extended_if_new(&mut self, share: &SubmitSharesExtended) -> bool {
+        self.insert_key(ShareKey {
+            channel_id: share.channel_id,
+            job_id: share.job_id,
+            nonce: share.nonce,
+            ntime: share.ntime,
+            version: share.version,
+            kind: ShareKind::Extended {
+                extranonce: share.extranonce.to_vec(),
+            },
+        })
+    }
+
+    fn insert_key(&mut self, key: ShareKey) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        self.tick += 1;
+        if let Some(last_use) = self.last_use.insert(key.clone(), self.tick) {
+            // already seen, move it to the most recently used end
+            self.by_last_use.remove(&last_use);
+            self.by_last_use.insert(self.tick, key);
+            return false;
+        }
+        if self.last_use.len() > self.capacity {
+            if let Some((_, evicted)) = self.by_last_use.pop_first() {
+                self.last_use.remove(&evicted);
+            }
+        }
+        self.by_last_use.insert(self.tick, key);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.last_use.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_use.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn share_with_ntime(ntime: u32) -> SubmitSharesStandard {
+        SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 0,
+            nonce: 0,
+            ntime,
+            version: 0x2000_0000,
+        }
+    }
+
+    #[test]
+    fn test_share_rate_limiter_burst() {
+        let mut limiter = ShareRateLimiter::new(1.0, 5);
+        let now = Instant::now();
+        for _ in 0..5 {
+            assert!(limiter.allow(1, now));
+        }
+        assert!(!limiter.allow(1, now));
+        // other channels have their own bucket
+        assert!(limiter.allow(2, now));
+    }
+
+    #[test]
+    fn test_share_rate_limiter_sustained_rate() {
+        let mut limiter = ShareRateLimiter::new(2.0, 2);
+        let start = Instant::now();
+        let mut allowed = 0;
+        // 10 shares per second for 5 seconds against a limit of 2 per second
+        for i in 0..50 {
+            if limiter.allow(1, start + std::time::Duration::from_millis(i * 100)) {
+                allowed += 1;
+            }
+        }
+        // the burst plus 2 shares per second of refill, throttling the rest
+        assert!((10..=12).contains(&allowed));
+        // after a pause the bucket is refilled
+        assert!(limiter.allow(1, start + std::time::Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_share_ack_batcher() {
+        let start = Instant::now();
+        let second = std::time::Duration::from_secs(1);
+        let mut batcher = ShareAckBatcher::new(second, start);
+
+        // first window
+        batcher.on_share_accepted(1, 10, 100);
+        batcher.on_share_accepted(2, 20, 50);
+        batcher.on_share_accepted(1, 11, 100);
+        assert_eq!(batcher.pending_channels(), 2);
+        // the window is still open
+        assert!(batcher.flush(start + second / 2).is_empty());
+
+        let flushed = batcher.flush(start + second);
+        assert_eq!(flushed.len(), 2);
+        assert_eq!(flushed[0].channel_id, 1);
+        assert_eq!(flushed[0].last_sequence_number, 11);
+        assert_eq!(flushed[0].new_submits_accepted_count, 2);
+        assert_eq!(flushed[0].new_shares_sum, 200);
+        assert_eq!(flushed[1].channel_id, 2);
+        assert_eq!(flushed[1].last_sequence_number, 20);
+        assert_eq!(flushed[1].new_submits_accepted_count, 1);
+        assert_eq!(flushed[1].new_shares_sum, 50);
+        assert_eq!(batcher.pending_channels(), 0);
+
+        // second window only counts the shares accepted after the first flush
+        batcher.on_share_accepted(2, 21, 50);
+        assert!(batcher.flush(start + second + second / 2).is_empty());
+        let flushed = batcher.flush(start + second * 2);
+        assert_eq!(flushed.len(), 1);
+        assert_eq!(flushed[0].channel_id, 2);
+        assert_eq!(flushed[0].last_sequence_number, 21);
+        assert_eq!(flushed[0].new_submits_accepted_count, 1);
+        assert_eq!(flushed[0].new_shares_sum, 50);
+
+        assert!(batcher.flush(start + second * 3).is_empty());
+    }
+
+    #[test]
+    fn test_share_ack_batcher_saturates() {
+        let start = Instant::now();
+        let mut batcher = ShareAckBatcher::new(Duration::from_secs(1), start);
+        batcher.on_share_accepted(1, 0, u64::MAX - 1);
+        batcher.on_share_accepted(1, 1, 2);
+        batcher
+            .pending
+            .get_mut(&1)
+            .unwrap()
+            .new_submits_accepted_count = u32::MAX;
+        batcher.on_share_accepted(1, 2, 1);
+
+        let flushed = batcher.flush(start + Duration::from_secs(1));
+        assert_eq!(flushed[0].new_submits_accepted_count, u32::MAX);
+        assert_eq!(flushed[0].new_shares_sum, u64::MAX);
+    }
+
+    #[test]
+    fn test_seen_shares() {
+        let mut seen = SeenShares::new(2);
+        let first = share_with_ntime(1);
+
+        assert!(seen.insert_if_new(&first));
+        // exact duplicate
+        assert!(!seen.insert_if_new(&first));
+        // a different nonce is a different share
+        let mut other_nonce = first.clone();
+        other_nonce.nonce += 1;
+        assert!(seen.insert_if_new(&other_nonce));
+        assert_eq!(seen.len(), 2);
+
+        // capacity exceeded, `first` is evicted and accepted again
+        assert!(seen.insert_if_new(&share_with_ntime(2)));
+        assert_eq!(seen.len(), 2);
+        assert!(seen.insert_if_new(&first));
+    }
+
+    #[test]
+    fn test_seen_shares_evicts_least_recently_used() {
+        let mut seen = SeenShares::new(2);
+        let first = share_with_ntime(1);
+        let second = share_with_ntime(2);
+        assert!(seen.insert_if_new(&first));
+        assert!(seen.insert_if_new(&second));
+
+        // seeing `first` again makes `second` the least recently used share
+        assert!(!seen.insert_if_new(&first));
+        assert!(seen.insert_if_new(&share_with_ntime(3)));
+        assert_eq!(seen.len(), 2);
+        assert!(!seen.insert_if_new(&first));
+        assert!(seen.insert_if_new(&second));
+    }
+
+    #[test]
+    fn test_seen_extended_shares() {
+        let mut seen = SeenShares::new(10);
+        let share = SubmitSharesExtended {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 2,
+            nonce: 3,
+            ntime: 4,
+            version: 5,
+            extranonce: vec![0xaa; 4].try_into().unwrap(),
+        };
+        assert!(seen.insert_extended_if_new(&share));
+        assert!(!seen.insert_extended_if_new(&share));
+
+        let mut other_extranonce = share.clone();
+        other_extranonce.extranonce = vec![0xbb; 4].try_into().unwrap();
+        assert!(seen.insert_extended_if_new(&other_extranonce));
+
+        // an extended share with an empty extranonce is not the standard share with the same fields
+        let mut empty_extranonce = share.clone();
+        empty_extranonce.extranonce = vec![].try_into().unwrap();
+        let standard = SubmitSharesStandard {
+            channel_id: share.channel_id,
+            sequence_number: share.sequence_number,
+            job_id: share.job_id,
+            nonce: share.nonce,
+            ntime: share.ntime,
+            version: share.version,
+        };
+        assert!(seen.insert_if_new(&standard));
+        assert!(seen.insert_extended_if_new(&empty_extranonce));
+        assert!(!seen.insert_if_new(&standard));
+    }
+
+    #[test]
+    fn test_share_stats() {
+        fn success(channel_id: u32, accepted: u32, shares_sum: u64) -> SubmitSharesSuccess {
+            SubmitSharesSuccess {
+                channel_id,
+                last_sequence_number: 0,
+                new_submits_accepted_count: accepted,
+                new_shares_sum: shares_sum,
+            }
+        }
+
+        let mut stats = ShareStats::new();
+        assert_eq!(stats.total_accepted(), 0);
+        assert!(stats.per_channel().is_empty());
+
+        stats.record(&success(1, 3, 300));
+        stats.record(&success(2, 1, 50));
+        stats.record(&success(1, 2, 200));
+
+        assert_eq!(stats.total_accepted(), 6);
+        assert_eq!(stats.total_shares_sum(), 550);
+        let per_channel = stats.per_channel();
+        assert_eq!(per_channel.len(), 2);
+        assert_eq!(per_channel[&1], (5, 500));
+        assert_eq!(per_channel[&2], (1, 50));
+    }
+}
@@ -0,0 +1,245 @@
+//! Decodes share submissions from a stream of plain (not encrypted) Sv2 frames.
+//!
+//! Ingest code can wrap any [`Read`] in a [`SubmitSharesDecoder`] and iterate over the decoded
+//! shares without managing buffers or partial reads itself.
+
+use crate::errors::Error;
+use binary_sv2::from_bytes;
+use const_sv2::{
+    MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED, MESSAGE_TYPE_SUBMIT_SHARES_STANDARD, SV2_FRAME_HEADER_SIZE,
+};
+use mining_sv2::{SubmitSharesExtended, SubmitSharesStandard, SUBMIT_SHARES_STANDARD_WIRE_LEN};
+use std::{
+    io::{self, Read},
+    marker::PhantomData,
+};
+
+/// A share message that can be decoded from the payload of a Sv2 frame.
+pub trait FramedShare: Sized {
+    /// Message type expected in the frame header.
+    const MESSAGE_TYPE: u8;
+
+    /// Largest encoded size of the message, frames announcing a longer payload are rejected
+    /// without buffering it.
+    const MAX_PAYLOAD_LEN: usize;
+
+    /// Decodes the message from the frame payload.
+    fn from_payload(payload: &mut [u8]) -> Result<Self, Error>;
+}
+
+impl FramedShare for SubmitSharesStandard {
+    const MESSAGE_TYPE: u8 = MESSAGE_TYPE_SUBMIT_SHARES_STANDARD;
+    const MAX_PAYLOAD_LEN: usize = SUBMIT_SHARES_STANDARD_WIRE_LEN;
+
+    fn from_payload(payload: &mut [u8]) -> Result<Self, Error> {
+        Ok(from_bytes(payload)?)
+    }
+}
+
+impl FramedShare for SubmitSharesExtended<'static> {
+    const MESSAGE_TYPE: u8 = MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED;
+    // the standard fields followed by a `B032` extranonce: 1 byte of length and up to 32 bytes
+    const MAX_PAYLOAD_LEN: usize = SUBMIT_SHARES_STANDARD_WIRE_LEN + 1 + 32;
+
+    fn from_payload(payload: &mut [u8]) -> Result<Self, Error> {
+        let share: SubmitSharesExtended = from_bytes(payload)?;
+        Ok(share.into_static())
+    }
+}
+
+/// Iterator over the shares read from a stream of Sv2 frames.
+///
+/// Every frame must carry a `T` message. A frame with a different message type yields
+/// [`Error::UnexpectedMessage`], a frame announcing a payload longer than
+/// [`FramedShare::MAX_PAYLOAD_LEN`] yields [`Error::BadPayloadSize`]; in both cases the payload is
+/// skipped without buffering it and decoding continues with the next frame. A stream that ends in
+/// the middle of a frame yields [`Error::TruncatedFrame`] and then ends.
+pub struct SubmitSharesDecoder<R: Read, T = SubmitSharesStandard> {
+    reader: R,
+    buffer: Vec<u8>,
+    share: PhantomData<T>,
+}
+
+impl<R: Read, T: FramedShare> SubmitSharesDecoder<R, T> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            share: PhantomData,
+        }
+    }
+
+    /// Consumes the decoder returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read, T: FramedShare> Iterator for SubmitSharesDecoder<R, T> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut header = [0; SV2_FRAME_HEADER_SIZE];
+        match read_full(&mut self.reader, &mut header) {
+            Ok(0) => return None,
+            Ok(read) if read < SV2_FRAME_HEADER_SIZE => {
+                return Some(Err(Error::TruncatedFrame(SV2_FRAME_HEADER_SIZE, read)))
+            }
+            Ok(_) => (),
+            Err(e) => return Some(Err(Error::IoError(e))),
+        }
+        let msg_type = header[2];
+        let len = u32::from_le_bytes([header[3], header[4], header[5], 0]) as usize;
+
+        // the length comes from the peer, check the frame before allocating for it
+        let rejected = if msg_type != T::MESSAGE_TYPE {
+            Some(Error::UnexpectedMessage(msg_type))
+        } else if len > T::MAX_PAYLOAD_LEN {
+            Some(Error::BadPayloadSize)
+        } else {
+            None
+        };
+        let read = match rejected {
+            Some(_) => io::copy(&mut (&mut self.reader).take(len as u64), &mut io::sink())
+                .map(|read| read as usize),
+            None => {
+                self.buffer.resize(len, 0);
+                read_full(&mut self.reader, &mut self.buffer)
+            }
+        };
+        match read {
+            Ok(read) if read < len => {
+                return Some(Err(Error::TruncatedFrame(
+                    SV2_FRAME_HEADER_SIZE + len,
+                    SV2_FRAME_HEADER_SIZE + read,
+                )))
+            }
+            Ok(_) => (),
+            Err(e) => return Some(Err(Error::IoError(e))),
+        }
+        match rejected {
+            Some(e) => Some(Err(e)),
+            None => Some(T::from_payload(&mut self.buffer)),
+        }
+    }
+}
+
+/// Reads until `buf` is full or the reader is exhausted, returning the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binary_sv2::{GetSize, Serialize};
+    use const_sv2::CHANNEL_BIT_SUBMIT_SHARES_STANDARD;
+    use framing_sv2::framing::Sv2Frame;
+
+    /// Reader that hands out at most `chunk` bytes per read, to exercise partial reads.
+    struct ChunkedReader {
+        data: Vec<u8>,
+        pos: usize,
+        chunk: usize,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.chunk).min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    fn share(sequence_number: u32) -> SubmitSharesStandard {
+        SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number,
+            job_id: 2,
+            nonce: 3,
+            ntime: 4,
+            version: 5,
+        }
+    }
+
+    fn frame_bytes<T: Serialize + GetSize>(message: T, msg_type: u8) -> Vec<u8> {
+        let frame: Sv2Frame<T, Vec<u8>> =
+            Sv2Frame::from_message(message, msg_type, 0, CHANNEL_BIT_SUBMIT_SHARES_STANDARD)
+                .unwrap();
+        let mut bytes = vec![0; frame.encoded_length()];
+        frame.serialize(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode_back_to_back_frames() {
+        let mut data = frame_bytes(share(1), MESSAGE_TYPE_SUBMIT_SHARES_STANDARD);
+        data.extend(frame_bytes(share(2), MESSAGE_TYPE_SUBMIT_SHARES_STANDARD));
+        let reader = ChunkedReader {
+            data,
+            pos: 0,
+            chunk: 5,
+        };
+        let decoder: SubmitSharesDecoder<_> = SubmitSharesDecoder::new(reader);
+        let sequence_numbers: Vec<u32> = decoder
+            .map(|share| share.unwrap().sequence_number)
+            .collect();
+        assert_eq!(sequence_numbers, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_reject_frame_before_buffering_payload() {
+        // an unexpected message type and an oversized share, both announcing a 16 MiB payload
+        let mut data = vec![];
+        for msg_type in [
+            MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED,
+            MESSAGE_TYPE_SUBMIT_SHARES_STANDARD,
+        ] {
+            data.extend([0x00, 0x80, msg_type, 0xff, 0xff, 0xff]);
+            data.extend(vec![0; 0xff_ffff]);
+        }
+        data.extend(frame_bytes(share(1), MESSAGE_TYPE_SUBMIT_SHARES_STANDARD));
+        let mut decoder: SubmitSharesDecoder<_> = SubmitSharesDecoder::new(&data[..]);
+
+        match decoder.next() {
+            Some(Err(Error::UnexpectedMessage(MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED))) => (),
+            other => panic!("expected UnexpectedMessage, got {:?}", other),
+        }
+        match decoder.next() {
+            Some(Err(Error::BadPayloadSize)) => (),
+            other => panic!("expected BadPayloadSize, got {:?}", other),
+        }
+        assert_eq!(decoder.buffer.capacity(), 0);
+        assert_eq!(decoder.next().unwrap().unwrap().sequence_number, 1);
+        assert!(decoder.buffer.capacity() <= SubmitSharesStandard::MAX_PAYLOAD_LEN);
+        assert!(decoder.next().is_none());
+    }
+
+    #[test]
+    fn test_decode_truncated_frame() {
+        let mut data = frame_bytes(share(1), MESSAGE_TYPE_SUBMIT_SHARES_STANDARD);
+        let second = frame_bytes(share(2), MESSAGE_TYPE_SUBMIT_SHARES_STANDARD);
+        data.extend(&second[..second.len() - 3]);
+        let mut decoder: SubmitSharesDecoder<_> = SubmitSharesDecoder::new(&data[..]);
+
+        assert_eq!(decoder.next().unwrap().unwrap().sequence_number, 1);
+        match decoder.next() {
+            Some(Err(Error::TruncatedFrame(expected, read))) => {
+                assert_eq!(expected, second.len());
+                assert_eq!(read, second.len() - 3);
+            }
+            other => panic!("expected TruncatedFrame, got {:?}", other),
+        }
+        assert!(decoder.next().is_none());
+    }
+}
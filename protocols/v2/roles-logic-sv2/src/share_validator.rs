@@ -0,0 +1,181 @@
+//! Composable checks run by an upstream on every share submitted by its downstreams.
+//!
+//! Each check (channel exists, job exists, share not stale, share meets target, ...) implements
+//! [`ShareValidator`], and a [`ChainedValidator`] runs them in order stopping at the first failure.
+
+use crate::errors::Error;
+pub use mining_sv2::SubmitShareRejectReason;
+use mining_sv2::{SubmitSharesExtended, SubmitSharesStandard};
+
+/// A single check run on every submitted share.
+pub trait ShareValidator {
+    fn validate(&self, submit: &SubmitSharesStandard) -> Result<(), SubmitShareRejectReason>;
+}
+
+impl<F> ShareValidator for F
+where
+    F: Fn(&SubmitSharesStandard) -> Result<(), SubmitShareRejectReason>,
+{
+    fn validate(&self, submit: &SubmitSharesStandard) -> Result<(), SubmitShareRejectReason> {
+        self(submit)
+    }
+}
+
+/// Runs a list of validators in insertion order, returning the first failure.
+#[derive(Default)]
+pub struct ChainedValidator {
+    validators: Vec<Box<dyn ShareValidator + Send + Sync>>,
+}
+
+impl ChainedValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `validator` to the chain.
+    pub fn with<V: ShareValidator + Send + Sync + 'static>(mut self, validator: V) -> Self {
+        self.push(validator);
+        self
+    }
+
+    /// Appends `validator` to the chain.
+    pub fn push<V: ShareValidator + Send + Sync + 'static>(&mut self, validator: V) {
+        self.validators.push(Box::new(validator));
+    }
+
+    pub fn len(&self) -> usize {
+        self.validators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty()
+    }
+}
+
+impl ShareValidator for ChainedValidator {
+    fn validate(&self, submit: &SubmitSharesStandard) -> Result<(), SubmitShareRejectReason> {
+        self.validators
+            .iter()
+            .try_for_each(|validator| validator.validate(submit))
+    }
+}
+
+/// Checks the extranonce of an extended share against the geometry of its channel.
+pub trait ExtranonceValidator {
+    /// Checks that the `extranonce_prefix` assigned to the channel followed by the extranonce of
+    /// the share is `total_extranonce_len` bytes long, as the coinbase of the job expects.
+    ///
+    /// Returns [`Error::ExtranonceLenMismatch`] otherwise, so that a share built for another
+    /// channel geometry is rejected before reconstructing the coinbase and hashing the header.
+    fn validate_against_channel(
+        &self,
+        extranonce_prefix: &[u8],
+        total_extranonce_len: usize,
+    ) -> Result<(), Error>;
+}
+
+impl<'a> ExtranonceValidator for SubmitSharesExtended<'a> {
+    fn validate_against_channel(
+        &self,
+        extranonce_prefix: &[u8],
+        total_extranonce_len: usize,
+    ) -> Result<(), Error> {
+        let len = extranonce_prefix.len() + self.extranonce.inner_as_ref().len();
+        if len != total_extranonce_len {
+            return Err(Error::ExtranonceLenMismatch(total_extranonce_len, len));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    const CURRENT_JOB_ID: u32 = 5;
+
+    /// Rejects shares for any job but the current one.
+    fn stale_check(submit: &SubmitSharesStandard) -> Result<(), SubmitShareRejectReason> {
+        if submit.job_id == CURRENT_JOB_ID {
+            Ok(())
+        } else {
+            Err(SubmitShareRejectReason::StaleShare)
+        }
+    }
+
+    /// Stands in for the hash/target comparison: shares with a nonce above `max_nonce` fail.
+    struct TargetCheck {
+        max_nonce: u32,
+    }
+
+    impl ShareValidator for TargetCheck {
+        fn validate(&self, submit: &SubmitSharesStandard) -> Result<(), SubmitShareRejectReason> {
+            if submit.nonce <= self.max_nonce {
+                Ok(())
+            } else {
+                Err(SubmitShareRejectReason::DifficultyTooLow)
+            }
+        }
+    }
+
+    fn share(job_id: u32, nonce: u32) -> SubmitSharesStandard {
+        SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 2,
+            job_id,
+            nonce,
+            ntime: 0,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn test_chained_validator_returns_first_failure() {
+        let validator = ChainedValidator::new()
+            .with(stale_check)
+            .with(TargetCheck { max_nonce: 100 });
+        assert_eq!(validator.len(), 2);
+
+        assert_eq!(validator.validate(&share(CURRENT_JOB_ID, 10)), Ok(()));
+        assert_eq!(
+            validator.validate(&share(CURRENT_JOB_ID, 1000)),
+            Err(SubmitShareRejectReason::DifficultyTooLow)
+        );
+        // fails both checks, the stale check runs first
+        assert_eq!(
+            validator.validate(&share(CURRENT_JOB_ID - 1, 1000)),
+            Err(SubmitShareRejectReason::StaleShare)
+        );
+    }
+
+    #[test]
+    fn test_reject_reason_to_submit_shares_error() {
+        let error = SubmitShareRejectReason::StaleShare.to_submit_shares_error(&share(1, 1));
+        assert_eq!(error.channel_id, 1);
+        assert_eq!(error.sequence_number, 2);
+        assert_eq!(error.error_code.inner_as_ref(), b"stale-share");
+    }
+
+    #[test]
+    fn test_validate_extranonce_against_channel() {
+        let share = SubmitSharesExtended {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id: 0,
+            nonce: 0,
+            ntime: 0,
+            version: 0x2000_0000,
+            extranonce: vec![0xee; 12].try_into().unwrap(),
+        };
+        let extranonce_prefix = [0xaa; 4];
+        assert!(share
+            .validate_against_channel(&extranonce_prefix, 16)
+            .is_ok());
+        match share.validate_against_channel(&extranonce_prefix, 32) {
+            Err(Error::ExtranonceLenMismatch(32, 16)) => (),
+            other => panic!("expected ExtranonceLenMismatch(32, 16), got {:?}", other),
+        }
+        assert!(share.validate_against_channel(&[], 16).is_err());
+    }
+}
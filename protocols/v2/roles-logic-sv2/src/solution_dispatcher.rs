@@ -0,0 +1,125 @@
+//! Builds the messages sent when a share solves a block.
+
+use crate::errors::Error;
+use binary_sv2::B032;
+use mining_sv2::SubmitSharesExtended;
+use std::convert::TryInto;
+use template_distribution_sv2::SubmitSolution;
+
+/// Builds the [`SubmitSolution`] for an extended share that solves a block of template
+/// `template_id`, `coinbase_tx` is the full coinbase reconstructed with the share extranonce (see
+/// [`SubmitSharesExtended::reconstruct_coinbase`]).
+///
+/// Fails if the coinbase does not fit in a `B064K`.
+pub fn solution_from_extended_share(
+    submit: &SubmitSharesExtended,
+    coinbase_tx: Vec<u8>,
+    template_id: u64,
+) -> Result<SubmitSolution<'static>, Error> {
+    Ok(SubmitSolution {
+        template_id,
+        version: submit.version,
+        header_timestamp: submit.ntime,
+        header_nonce: submit.nonce,
+        coinbase_tx: coinbase_tx.try_into()?,
+    })
+}
+
+/// Pairs a block solution sent to the Template Provider with the share sent to the pool.
+///
+/// A downstream that finds a block must send the Template Distribution [`SubmitSolution`], so
+/// that the Template Provider propagates the block as soon as possible, **and** submit the same
+/// work on its Mining Protocol channel, so that the pool credits it and can propagate the block
+/// too. Building both messages from the same dispatcher keeps the header fields consistent.
+#[derive(Debug, Clone)]
+pub struct SolutionDispatcher<'a> {
+    solution: SubmitSolution<'a>,
+}
+
+impl<'a> SolutionDispatcher<'a> {
+    pub fn new(solution: SubmitSolution<'a>) -> Self {
+        Self { solution }
+    }
+
+    /// The message to send to the Template Provider.
+    pub fn template_distribution_message(&self) -> &SubmitSolution<'a> {
+        &self.solution
+    }
+
+    /// The share to send on the Mining Protocol extended channel `channel_id`, with the header
+    /// fields of the solution. `extranonce` is the one used to build the solution coinbase.
+    pub fn mining_submission<'b>(
+        &self,
+        channel_id: u32,
+        sequence_number: u32,
+        job_id: u32,
+        extranonce: B032<'b>,
+    ) -> SubmitSharesExtended<'b> {
+        SubmitSharesExtended {
+            channel_id,
+            sequence_number,
+            job_id,
+            nonce: self.solution.header_nonce,
+            ntime: self.solution.header_timestamp,
+            version: self.solution.version,
+            extranonce,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solution_from_extended_share() {
+        use std::convert::TryFrom;
+
+        let share = SubmitSharesExtended {
+            channel_id: 1,
+            sequence_number: 2,
+            job_id: 3,
+            nonce: 0xdead_beef,
+            ntime: 1_700_000_000,
+            version: 0x2000_0004,
+            extranonce: B032::try_from(vec![0xaa; 8]).unwrap(),
+        };
+        let coinbase = vec![0x01, 0x02, 0x03];
+
+        let solution = solution_from_extended_share(&share, coinbase.clone(), 42).unwrap();
+        assert_eq!(solution.template_id, 42);
+        assert_eq!(solution.version, 0x2000_0004);
+        assert_eq!(solution.header_timestamp, 1_700_000_000);
+        assert_eq!(solution.header_nonce, 0xdead_beef);
+        assert_eq!(solution.coinbase_tx.inner_as_ref(), &coinbase[..]);
+
+        assert!(solution_from_extended_share(&share, vec![0; 1 << 16], 42).is_err());
+    }
+
+    #[test]
+    fn test_solution_dispatcher_yields_both_messages() {
+        use binary_sv2::{B032, B064K};
+        use std::convert::TryFrom;
+
+        let solution = SubmitSolution {
+            template_id: 42,
+            version: 0x2000_0004,
+            header_timestamp: 1_700_000_000,
+            header_nonce: 0xdead_beef,
+            coinbase_tx: B064K::try_from(vec![0x01, 0x02]).unwrap(),
+        };
+        let dispatcher = SolutionDispatcher::new(solution.clone());
+
+        assert_eq!(dispatcher.template_distribution_message(), &solution);
+
+        let extranonce = B032::try_from(vec![0xaa; 8]).unwrap();
+        let share = dispatcher.mining_submission(1, 5, 7, extranonce.clone());
+        assert_eq!(share.channel_id, 1);
+        assert_eq!(share.sequence_number, 5);
+        assert_eq!(share.job_id, 7);
+        assert_eq!(share.version, solution.version);
+        assert_eq!(share.ntime, solution.header_timestamp);
+        assert_eq!(share.nonce, solution.header_nonce);
+        assert_eq!(share.extranonce, extranonce);
+    }
+}
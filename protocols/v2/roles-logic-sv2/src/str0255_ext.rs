@@ -0,0 +1,67 @@
+//! Conversions between strings and the [`Str0255`] fields carried by the messages.
+
+use crate::errors::Error;
+use binary_sv2::Str0255;
+use std::convert::TryInto;
+
+/// Converts `s` into a [`Str0255`], returning [`Error::StringTooLong`] instead of panicking when
+/// `s` is longer than 255 bytes.
+pub fn str0255(s: &str) -> Result<Str0255<'static>, Error> {
+    s.to_string()
+        .into_bytes()
+        .try_into()
+        .map_err(|_| Error::StringTooLong(s.len()))
+}
+
+/// Owned string conversions of the [`Str0255`] fields carried by the messages (error codes, user
+/// identities, vendor names, ...).
+pub trait Str0255Ext {
+    /// Returns the field as a `String`, invalid UTF-8 sequences are replaced with `U+FFFD`.
+    fn to_string_lossy_owned(&self) -> String;
+
+    /// Returns the field as a `String`, or [`Error::InvalidUtf8`] if it is not valid UTF-8.
+    fn try_to_string(&self) -> Result<String, Error>;
+}
+
+impl<'a> Str0255Ext for Str0255<'a> {
+    fn to_string_lossy_owned(&self) -> String {
+        String::from_utf8_lossy(self.inner_as_ref()).into_owned()
+    }
+
+    fn try_to_string(&self) -> Result<String, Error> {
+        std::str::from_utf8(self.inner_as_ref())
+            .map(str::to_string)
+            .map_err(Error::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_str0255_ext() {
+        let valid = str0255("stale-share").unwrap();
+        assert_eq!(valid.to_string_lossy_owned(), "stale-share");
+        assert_eq!(valid.try_to_string().unwrap(), "stale-share");
+
+        let invalid: Str0255 = vec![b'o', b'k', 0xff].try_into().unwrap();
+        assert_eq!(invalid.to_string_lossy_owned(), "ok\u{fffd}");
+        assert!(matches!(
+            invalid.try_to_string(),
+            Err(Error::InvalidUtf8(_))
+        ));
+    }
+
+    #[test]
+    fn test_str0255() {
+        let max = "a".repeat(255);
+        assert_eq!(str0255(&max).unwrap().inner_as_ref(), max.as_bytes());
+
+        let too_long = "a".repeat(256);
+        match str0255(&too_long) {
+            Err(Error::StringTooLong(256)) => (),
+            other => panic!("expected StringTooLong(256), got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! State a Template Provider and its clients keep about the templates of a stream: the templates
+//! issued, their ids, and the transaction data requested for them.
+
+use crate::errors::Error;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::Instant,
+};
+use template_distribution_sv2::{
+    NewTemplate, RequestTransactionData, RequestTransactionDataError,
+    RequestTransactionDataSuccess, SetNewPrevHash, SubmitSolution,
+};
+
+/// What a Template Provider remembers of each `NewTemplate` it issued, to validate the
+/// `SubmitSolution` referencing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateMeta {
+    pub template_id: u64,
+    pub future_template: bool,
+    /// Block header version of the template
+    pub version: u32,
+    pub coinbase_tx_version: u32,
+}
+
+impl<'a> From<&NewTemplate<'a>> for TemplateMeta {
+    fn from(template: &NewTemplate<'a>) -> Self {
+        Self {
+            template_id: template.template_id,
+            future_template: template.future_template,
+            version: template.version,
+            coinbase_tx_version: template.coinbase_tx_version,
+        }
+    }
+}
+
+/// Templates issued by a Template Provider, indexed by template id.
+///
+/// [`TemplateRegistry::on_new_prev_hash`] forgets the templates built on the previous block, so
+/// the registry only holds the templates of the current one.
+#[derive(Debug, Default, Clone)]
+pub struct TemplateRegistry {
+    templates: HashMap<u64, TemplateMeta>,
+}
+
+impl TemplateRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `template` as issued, replacing any template with the same id.
+    pub fn register(&mut self, template: &NewTemplate) {
+        self.templates
+            .insert(template.template_id, TemplateMeta::from(template));
+    }
+
+    pub fn get(&self, template_id: u64) -> Option<&TemplateMeta> {
+        self.templates.get(&template_id)
+    }
+
+    /// Forgets `template_id`, solutions for it will be rejected from now on.
+    pub fn remove(&mut self, template_id: u64) -> Option<TemplateMeta> {
+        self.templates.remove(&template_id)
+    }
+
+    /// Forgets every template except the one referenced by `prev_hash`, the others were built on
+    /// the previous block and solutions for them will be rejected from now on.
+    pub fn on_new_prev_hash(&mut self, prev_hash: &SetNewPrevHash) {
+        self.templates
+            .retain(|template_id, _| *template_id == prev_hash.template_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+}
+
+/// Implemented by the messages that reference a template issued with `NewTemplate`.
+pub trait TemplateIdValidator {
+    /// Returns the metadata of the referenced template, or [`Error::UnknownTemplateId`] if
+    /// `registry` does not know it, in which case no block must be assembled.
+    fn validate_against<'r>(
+        &self,
+        registry: &'r TemplateRegistry,
+    ) -> Result<&'r TemplateMeta, Error>;
+}
+
+impl<'a> TemplateIdValidator for SubmitSolution<'a> {
+    fn validate_against<'r>(
+        &self,
+        registry: &'r TemplateRegistry,
+    ) -> Result<&'r TemplateMeta, Error> {
+        registry
+            .get(self.template_id)
+            .ok_or(Error::UnknownTemplateId(self.template_id))
+    }
+}
+
+/// Template ids issued in the `NewTemplate` messages of a stream.
+///
+/// Every id must be issued once, otherwise the `RequestTransactionData` and `SubmitSolution`
+/// referencing it can not be correlated to a single template. The ids issued before a
+/// `SetNewPrevHash` are forgotten by [`TemplateIdSequencer::on_new_prev_hash`], so that memory
+/// does not grow with the length of the stream, but they are never issued again.
+#[derive(Debug)]
+pub struct TemplateIdSequencer {
+    /// Ids issued since the latest `SetNewPrevHash`, plus the template it references
+    issued: HashSet<u64>,
+    /// Greater than every id issued so far, `None` once `u64::MAX` has been issued
+    next: Option<u64>,
+    /// Ids up to this one were issued (or skipped) before the latest `SetNewPrevHash`
+    retired_up_to: Option<u64>,
+}
+
+impl Default for TemplateIdSequencer {
+    fn default() -> Self {
+        Self {
+            issued: HashSet::new(),
+            next: Some(0),
+            retired_up_to: None,
+        }
+    }
+}
+
+impl TemplateIdSequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `template_id` as issued, returns [`Error::DuplicateTemplateId`] if it already was,
+    /// including before the latest `SetNewPrevHash`.
+    pub fn register(&mut self, template_id: u64) -> Result<(), Error> {
+        let retired =
+            matches!(self.retired_up_to, Some(retired_up_to) if template_id <= retired_up_to);
+        if retired || !self.issued.insert(template_id) {
+            return Err(Error::DuplicateTemplateId(template_id));
+        }
+        self.next = match (self.next, template_id.checked_add(1)) {
+            (Some(next), Some(after)) => Some(next.max(after)),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// Issues a new template id, greater than every id issued so far.
+    ///
+    /// Returns [`Error::TemplateIdsExhausted`] once `u64::MAX` has been issued.
+    pub fn next_id(&mut self) -> Result<u64, Error> {
+        let template_id = self.next.ok_or(Error::TemplateIdsExhausted)?;
+        self.issued.insert(template_id);
+        self.next = template_id.checked_add(1);
+        Ok(template_id)
+    }
+
+    pub fn is_issued(&self, template_id: u64) -> bool {
+        self.issued.contains(&template_id)
+    }
+
+    /// Forgets the ids issued before `prev_hash`, except the one of the template it references.
+    ///
+    /// The templates built on the previous block can not be mined anymore, `is_issued` returns
+    /// false for their ids from now on and `register` keeps rejecting them.
+    pub fn on_new_prev_hash(&mut self, prev_hash: &SetNewPrevHash) {
+        self.issued.retain(|id| *id == prev_hash.template_id);
+        self.retired_up_to = match self.next {
+            Some(0) => self.retired_up_to,
+            Some(next) => Some(next - 1),
+            None => Some(u64::MAX),
+        };
+    }
+}
+
+/// Tracks the `RequestTransactionData` sent upstream that are still waiting for a response.
+///
+/// Responses are matched to requests by `template_id`, a response for an id that is not
+/// outstanding is unexpected and should be dropped by the caller.
+#[derive(Debug, Default)]
+pub struct PendingTxDataRequests {
+    pending: HashMap<u64, Instant>,
+}
+
+impl PendingTxDataRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `request` as outstanding.
+    pub fn register(&mut self, request: &RequestTransactionData) {
+        self.pending.insert(request.template_id, Instant::now());
+    }
+
+    /// Like [`PendingTxDataRequests::register`], but returns [`Error::UnknownTemplateId`] and
+    /// does not record `request` if its template id has not been issued by `templates`.
+    pub fn register_issued(
+        &mut self,
+        request: &RequestTransactionData,
+        templates: &TemplateIdSequencer,
+    ) -> Result<(), Error> {
+        if !templates.is_issued(request.template_id) {
+            return Err(Error::UnknownTemplateId(request.template_id));
+        }
+        self.register(request);
+        Ok(())
+    }
+
+    /// Resolves the request `response` answers, returns false if it was not outstanding.
+    pub fn resolve_success(&mut self, response: &RequestTransactionDataSuccess) -> bool {
+        self.pending.remove(&response.template_id).is_some()
+    }
+
+    /// Resolves the request `response` answers, returns false if it was not outstanding.
+    pub fn resolve_error(&mut self, response: &RequestTransactionDataError) -> bool {
+        self.pending.remove(&response.template_id).is_some()
+    }
+
+    /// Returns when the request for `template_id` was registered, if it is still outstanding.
+    pub fn registered_at(&self, template_id: u64) -> Option<Instant> {
+        self.pending.get(&template_id).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Cache of the transaction data of the latest templates, so that a Job Declarator answering the
+/// same `RequestTransactionData` more than once assembles the transaction list only once.
+///
+/// It holds at most `capacity` templates (at least one), when full the least recently used one is
+/// evicted.
+#[derive(Debug)]
+pub struct TxDataCache {
+    capacity: usize,
+    entries: HashMap<u64, RequestTransactionDataSuccess<'static>>,
+    /// Template ids, least recently used first
+    recency: VecDeque<u64>,
+}
+
+impl TxDataCache {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            entries: HashMap::with_capacity(capacity),
+            recency: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the cached transaction data of `template_id`, marking it as the most recently used.
+    pub fn get(&mut self, template_id: u64) -> Option<&RequestTransactionDataSuccess<'static>> {
+        if self.entries.contains_key(&template_id) {
+            self.touch(template_id);
+        }
+        self.entries.get(&template_id)
+    }
+
+    /// Returns the cached transaction data of `template_id`, or caches and returns the one built
+    /// by `fetch` (use [`RequestTransactionDataSuccess::into_static`] to build an owned message).
+    pub fn get_or_insert_with<F>(
+        &mut self,
+        template_id: u64,
+        fetch: F,
+    ) -> &RequestTransactionDataSuccess<'static>
+    where
+        F: FnOnce() -> RequestTransactionDataSuccess<'static>,
+    {
+        if self.entries.contains_key(&template_id) {
+            self.touch(template_id);
+        } else {
+            if self.recency.len() == self.capacity {
+                if let Some(least_recent) = self.recency.pop_front() {
+                    self.entries.remove(&least_recent);
+                }
+            }
+            self.entries.insert(template_id, fetch());
+            self.recency.push_back(template_id);
+        }
+        &self.entries[&template_id]
+    }
+
+    fn touch(&mut self, template_id: u64) {
+        if let Some(position) = self.recency.iter().position(|id| *id == template_id) {
+            self.recency.remove(position);
+        }
+        self.recency.push_back(template_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.recency.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recency.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::str0255_ext::str0255;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_solution_validate_against_registry() {
+        use binary_sv2::{Seq0255, B0255, B064K};
+        use std::convert::TryFrom;
+
+        let template = NewTemplate {
+            template_id: 42,
+            future_template: false,
+            version: 0x2000_0000,
+            coinbase_tx_version: 2,
+            coinbase_prefix: B0255::try_from(vec![]).unwrap(),
+            coinbase_tx_input_sequence: u32::MAX,
+            coinbase_tx_value_remaining: 0,
+            coinbase_tx_outputs_count: 0,
+            coinbase_tx_outputs: B064K::try_from(vec![]).unwrap(),
+            coinbase_tx_locktime: 0,
+            merkle_path: Seq0255::new(vec![]).unwrap(),
+        };
+        let mut registry = TemplateRegistry::new();
+        registry.register(&template);
+        assert_eq!(registry.len(), 1);
+
+        let mut solution = SubmitSolution {
+            template_id: 42,
+            version: 0x2000_0000,
+            header_timestamp: 0,
+            header_nonce: 0,
+            coinbase_tx: B064K::try_from(vec![]).unwrap(),
+        };
+        let meta = solution.validate_against(&registry).unwrap();
+        assert_eq!(meta.template_id, 42);
+        assert_eq!(meta.coinbase_tx_version, 2);
+
+        solution.template_id = 43;
+        let error = solution.validate_against(&registry).unwrap_err();
+        assert!(matches!(error, Error::UnknownTemplateId(43)));
+        assert_eq!(
+            error.to_string(),
+            "Template id 43 was never issued in a NewTemplate"
+        );
+    }
+
+    #[test]
+    fn test_template_registry_prunes_on_new_prev_hash() {
+        use binary_sv2::{Seq0255, B0255, B064K};
+        use std::convert::TryFrom;
+
+        let mut registry = TemplateRegistry::new();
+        for template_id in 0..3 {
+            registry.register(&NewTemplate {
+                template_id,
+                future_template: template_id == 2,
+                version: 0x2000_0000,
+                coinbase_tx_version: 2,
+                coinbase_prefix: B0255::try_from(vec![]).unwrap(),
+                coinbase_tx_input_sequence: u32::MAX,
+                coinbase_tx_value_remaining: 0,
+                coinbase_tx_outputs_count: 0,
+                coinbase_tx_outputs: B064K::try_from(vec![]).unwrap(),
+                coinbase_tx_locktime: 0,
+                merkle_path: Seq0255::new(vec![]).unwrap(),
+            });
+        }
+        let prev_hash = SetNewPrevHash {
+            template_id: 2,
+            prev_hash: vec![0; 32].try_into().unwrap(),
+            header_timestamp: 0,
+            n_bits: 0,
+            target: vec![0; 32].try_into().unwrap(),
+        };
+        registry.on_new_prev_hash(&prev_hash);
+
+        assert_eq!(registry.len(), 1);
+        assert!(registry.get(0).is_none());
+        assert!(registry.get(1).is_none());
+        // the template the new prevhash references can still be solved
+        assert!(registry.get(2).unwrap().future_template);
+    }
+
+    #[test]
+    fn test_template_id_sequencer() {
+        let mut sequencer = TemplateIdSequencer::new();
+        assert_eq!(sequencer.next_id().unwrap(), 0);
+        assert_eq!(sequencer.next_id().unwrap(), 1);
+        sequencer.register(10).unwrap();
+        assert_eq!(sequencer.next_id().unwrap(), 11);
+
+        assert!(matches!(
+            sequencer.register(10),
+            Err(Error::DuplicateTemplateId(10))
+        ));
+        assert!(matches!(
+            sequencer.register(1),
+            Err(Error::DuplicateTemplateId(1))
+        ));
+        // ids do not need to be issued in order
+        sequencer.register(5).unwrap();
+        assert_eq!(sequencer.next_id().unwrap(), 12);
+
+        let mut pending = PendingTxDataRequests::new();
+        pending
+            .register_issued(&RequestTransactionData { template_id: 5 }, &sequencer)
+            .unwrap();
+        assert!(matches!(
+            pending.register_issued(&RequestTransactionData { template_id: 7 }, &sequencer),
+            Err(Error::UnknownTemplateId(7))
+        ));
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_template_id_sequencer_exhausted() {
+        let mut sequencer = TemplateIdSequencer::new();
+        sequencer.register(u64::MAX - 1).unwrap();
+        assert_eq!(sequencer.next_id().unwrap(), u64::MAX);
+        assert!(matches!(
+            sequencer.next_id(),
+            Err(Error::TemplateIdsExhausted)
+        ));
+        // ids left below the highest one can still be registered
+        sequencer.register(7).unwrap();
+        assert!(matches!(
+            sequencer.next_id(),
+            Err(Error::TemplateIdsExhausted)
+        ));
+    }
+
+    #[test]
+    fn test_template_id_sequencer_prunes_on_new_prev_hash() {
+        let mut sequencer = TemplateIdSequencer::new();
+        for _ in 0..3 {
+            sequencer.next_id().unwrap();
+        }
+        let prev_hash = SetNewPrevHash {
+            template_id: 2,
+            prev_hash: vec![0; 32].try_into().unwrap(),
+            header_timestamp: 0,
+            n_bits: 0,
+            target: vec![0; 32].try_into().unwrap(),
+        };
+        sequencer.on_new_prev_hash(&prev_hash);
+
+        assert!(!sequencer.is_issued(0));
+        assert!(!sequencer.is_issued(1));
+        // the template the new prevhash references is still valid
+        assert!(sequencer.is_issued(2));
+        assert_eq!(sequencer.issued.len(), 1);
+        // retired ids are never issued again
+        assert!(matches!(
+            sequencer.register(1),
+            Err(Error::DuplicateTemplateId(1))
+        ));
+        assert_eq!(sequencer.next_id().unwrap(), 3);
+        sequencer.register(10).unwrap();
+    }
+
+    #[test]
+    fn test_pending_tx_data_requests() {
+        let mut pending = PendingTxDataRequests::new();
+        pending.register(&RequestTransactionData { template_id: 1 });
+        pending.register(&RequestTransactionData { template_id: 2 });
+        assert_eq!(pending.len(), 2);
+
+        let success = RequestTransactionDataSuccess {
+            template_id: 1,
+            excess_data: vec![].try_into().unwrap(),
+            transaction_list: vec![].into(),
+        };
+        assert!(pending.resolve_success(&success));
+        // a response is only matched once
+        assert!(!pending.resolve_success(&success));
+
+        let error = RequestTransactionDataError {
+            template_id: 2,
+            error_code: str0255("template-id-not-found").unwrap(),
+        };
+        assert!(pending.resolve_error(&error));
+
+        let unexpected = RequestTransactionDataError {
+            template_id: 3,
+            ..error
+        };
+        assert!(!pending.resolve_error(&unexpected));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_tx_data_cache() {
+        fn tx_data(template_id: u64) -> RequestTransactionDataSuccess<'static> {
+            RequestTransactionDataSuccess {
+                template_id,
+                excess_data: vec![].try_into().unwrap(),
+                transaction_list: vec![vec![template_id as u8; 4].try_into().unwrap()].into(),
+            }
+        }
+
+        let mut cache = TxDataCache::new(2);
+        let mut fetches = 0;
+
+        // miss: the closure builds the data
+        let data = cache.get_or_insert_with(1, || {
+            fetches += 1;
+            tx_data(1)
+        });
+        assert_eq!(data.template_id, 1);
+        assert_eq!(fetches, 1);
+
+        // hit: the cached data is returned
+        let data = cache.get_or_insert_with(1, || {
+            fetches += 1;
+            tx_data(1)
+        });
+        assert_eq!(data.template_id, 1);
+        assert_eq!(fetches, 1);
+
+        cache.get_or_insert_with(2, || tx_data(2));
+        // 1 is now more recently used than 2
+        assert!(cache.get(1).is_some());
+        cache.get_or_insert_with(3, || tx_data(3));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(3).is_some());
+    }
+}
@@ -0,0 +1,215 @@
+//! Encoded bytes of the common, template distribution and share messages, to check other SV2
+//! implementations against.
+//!
+//! Every message is built from the fixed field values documented next to it, its payload (without
+//! the frame header) is encoded and written as hex in `test_vectors.txt`, one message per line:
+//! `<message name> <message type> <payload>`. `test_vectors_match_golden_file` fails if the
+//! encoding changes; run it with `UPDATE_TEST_VECTORS=1` to rewrite the golden file after an
+//! intended change.
+
+use binary_sv2::{to_bytes, Seq064K, B016M, B032, B064K};
+use common_messages_sv2::{
+    Protocol, SetupConnection, SetupConnectionError, SetupConnectionSuccess,
+    REQUIRES_VERSION_ROLLING,
+};
+use const_sv2::*;
+use mining_sv2::{
+    SubmitSharesError, SubmitSharesExtended, SubmitSharesStandard, SubmitSharesSuccess,
+};
+use std::convert::{TryFrom, TryInto};
+use template_distribution_sv2::{
+    RequestTransactionData, RequestTransactionDataError, RequestTransactionDataSuccess,
+    SubmitSolution,
+};
+
+const GOLDEN: &str = include_str!("test_vectors.txt");
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `(message name, message type, encoded payload)` of every test vector.
+fn vectors() -> Vec<(&'static str, u8, Vec<u8>)> {
+    vec![
+        (
+            "SetupConnection",
+            MESSAGE_TYPE_SETUP_CONNECTION,
+            // mining protocol, versions 2 to 2, version rolling, all the strings non-empty so that
+            // their length prefixes are visible
+            to_bytes(SetupConnection {
+                protocol: Protocol::MiningProtocol,
+                min_version: 2,
+                max_version: 2,
+                flags: REQUIRES_VERSION_ROLLING,
+                endpoint_host: "0.0.0.0".to_string().into_bytes().try_into().unwrap(),
+                endpoint_port: 8545,
+                vendor: "Bitmain".to_string().into_bytes().try_into().unwrap(),
+                hardware_version: "S9i 13.5".to_string().into_bytes().try_into().unwrap(),
+                firmware: "braiins-os-2018-09-22-1-hash"
+                    .to_string()
+                    .into_bytes()
+                    .try_into()
+                    .unwrap(),
+                device_id: "some-device-uuid"
+                    .to_string()
+                    .into_bytes()
+                    .try_into()
+                    .unwrap(),
+            })
+            .unwrap(),
+        ),
+        (
+            "SetupConnectionSuccess",
+            MESSAGE_TYPE_SETUP_CONNECTION_SUCCESS,
+            // version 2, fixed version and extended channels required
+            to_bytes(SetupConnectionSuccess {
+                used_version: 2,
+                flags: 0b11,
+            })
+            .unwrap(),
+        ),
+        (
+            "SetupConnectionError",
+            MESSAGE_TYPE_SETUP_CONNECTION_ERROR,
+            // work selection is not supported
+            to_bytes(SetupConnectionError {
+                flags: 0b10,
+                error_code: "unsupported-feature-flags"
+                    .to_string()
+                    .into_bytes()
+                    .try_into()
+                    .unwrap(),
+            })
+            .unwrap(),
+        ),
+        (
+            "RequestTransactionData",
+            MESSAGE_TYPE_REQUEST_TRANSACTION_DATA,
+            // a template id above u32::MAX, to check that all the 8 bytes are little-endian
+            to_bytes(RequestTransactionData {
+                template_id: 0x0102_0304_0506_0708,
+            })
+            .unwrap(),
+        ),
+        (
+            "RequestTransactionDataSuccess",
+            MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_SUCCESS,
+            // 2 bytes of excess data and 2 transactions of 3 and 1 bytes
+            to_bytes(RequestTransactionDataSuccess {
+                template_id: 1,
+                excess_data: B064K::try_from(vec![0xaa, 0xbb]).unwrap(),
+                transaction_list: Seq064K::new(vec![
+                    B016M::try_from(vec![0x01, 0x02, 0x03]).unwrap(),
+                    B016M::try_from(vec![0x04]).unwrap(),
+                ])
+                .unwrap(),
+            })
+            .unwrap(),
+        ),
+        (
+            "RequestTransactionDataError",
+            MESSAGE_TYPE_REQUEST_TRANSACTION_DATA_ERROR,
+            to_bytes(RequestTransactionDataError {
+                template_id: 1,
+                error_code: "template-id-not-found"
+                    .to_string()
+                    .into_bytes()
+                    .try_into()
+                    .unwrap(),
+            })
+            .unwrap(),
+        ),
+        (
+            "SubmitSolution",
+            MESSAGE_TYPE_SUBMIT_SOLUTION,
+            // header fields of mainnet block 100000, with a placeholder 4 bytes coinbase
+            to_bytes(SubmitSolution {
+                template_id: 1,
+                version: 1,
+                header_timestamp: 1_293_623_863,
+                header_nonce: 274_148_111,
+                coinbase_tx: B064K::try_from(vec![0x01, 0x00, 0x00, 0x00]).unwrap(),
+            })
+            .unwrap(),
+        ),
+        (
+            "SubmitSharesStandard",
+            MESSAGE_TYPE_SUBMIT_SHARES_STANDARD,
+            to_bytes(SubmitSharesStandard {
+                channel_id: 1,
+                sequence_number: 2,
+                job_id: 3,
+                nonce: 0xdead_beef,
+                ntime: 1_293_623_863,
+                version: 0x2000_0000,
+            })
+            .unwrap(),
+        ),
+        (
+            "SubmitSharesExtended",
+            MESSAGE_TYPE_SUBMIT_SHARES_EXTENDED,
+            // same share as above, with an 8 bytes extranonce
+            to_bytes(SubmitSharesExtended {
+                channel_id: 1,
+                sequence_number: 2,
+                job_id: 3,
+                nonce: 0xdead_beef,
+                ntime: 1_293_623_863,
+                version: 0x2000_0000,
+                extranonce: B032::try_from(vec![0, 1, 2, 3, 4, 5, 6, 7]).unwrap(),
+            })
+            .unwrap(),
+        ),
+        (
+            "SubmitSharesSuccess",
+            MESSAGE_TYPE_SUBMIT_SHARES_SUCCESS,
+            to_bytes(SubmitSharesSuccess {
+                channel_id: 1,
+                last_sequence_number: 2,
+                new_submits_accepted_count: 3,
+                new_shares_sum: 0x0102_0304_0506_0708,
+            })
+            .unwrap(),
+        ),
+        (
+            "SubmitSharesError",
+            MESSAGE_TYPE_SUBMIT_SHARES_ERROR,
+            to_bytes(SubmitSharesError {
+                channel_id: 1,
+                sequence_number: 2,
+                error_code: "stale-share".to_string().into_bytes().try_into().unwrap(),
+            })
+            .unwrap(),
+        ),
+    ]
+}
+
+fn generate() -> String {
+    let mut out = String::from(
+        "# Generated by roles_logic_sv2 test_vectors.rs, see there for the field values.\n\
+         # <message name> <message type> <payload>\n",
+    );
+    for (name, message_type, payload) in vectors() {
+        out.push_str(&format!(
+            "{} 0x{:02x} {}\n",
+            name,
+            message_type,
+            hex(&payload)
+        ));
+    }
+    out
+}
+
+#[test]
+fn test_vectors_match_golden_file() {
+    let generated = generate();
+    if std::env::var_os("UPDATE_TEST_VECTORS").is_some() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/test_vectors.txt");
+        std::fs::write(path, &generated).unwrap();
+        return;
+    }
+    assert_eq!(
+        generated, GOLDEN,
+        "the encoding changed, run with UPDATE_TEST_VECTORS=1 to update the golden file"
+    );
+}
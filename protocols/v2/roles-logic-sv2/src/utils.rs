@@ -170,6 +170,45 @@ pub fn merkle_root_from_path_<T: AsRef<[u8]>>(coinbase_id: [u8; 32], path: &[T])
         _ => reduce_path(coinbase_id, path),
     }
 }
+/// Computes the merkle root of a block from the hash of its coinbase transaction and the merkle
+/// branch of the coinbase, as needed to rebuild the header of a share of an extended job.
+///
+/// Every step double SHA256 hashes the current node concatenated with the next element of
+/// `branch`. Hashes are in internal byte order (the reverse of the usual hex representation).
+pub fn compute_merkle_root(coinbase_hash: [u8; 32], branch: &[[u8; 32]]) -> [u8; 32] {
+    merkle_root_from_path_(coinbase_hash, branch)
+}
+
+/// Start of the script of the witness commitment output (BIP141): `OP_RETURN`, a push of 36
+/// bytes and the `0xaa21a9ed` commitment header, followed by the 32 bytes commitment.
+const WITNESS_COMMITMENT_HEADER: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Looks for the witness commitment in a serialized coinbase transaction, as needed to check the
+/// coinbase of a [`SubmitSolution`] for a SegWit block.
+///
+/// Returns the index of the commitment output and the commitment. If more than one output matches,
+/// the one with the highest index is the commitment (BIP141). Returns `None` if the coinbase can
+/// not be deserialized or has no commitment.
+pub fn find_witness_commitment(coinbase_tx: &[u8]) -> Option<(usize, [u8; 32])> {
+    let coinbase = Transaction::deserialize(coinbase_tx).ok()?;
+    coinbase
+        .output
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, output)| {
+            let script = output.script_pubkey.as_bytes();
+            if script.len() < WITNESS_COMMITMENT_HEADER.len() + 32
+                || script[..WITNESS_COMMITMENT_HEADER.len()] != WITNESS_COMMITMENT_HEADER
+            {
+                return None;
+            }
+            let start = WITNESS_COMMITMENT_HEADER.len();
+            let commitment: [u8; 32] = script[start..start + 32].try_into().ok()?;
+            Some((index, commitment))
+        })
+}
+
 // TODO remove when we have https://github.com/rust-bitcoin/rust-bitcoin/issues/1319
 fn reduce_path<T: AsRef<[u8]>>(coinbase_id: [u8; 32], path: &[T]) -> [u8; 32] {
     let mut root = coinbase_id;
@@ -1068,6 +1107,76 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_compute_merkle_root() {
+        // block 100000, its 4 txids and merkle root as displayed by block explorers
+        use bitcoin::hashes::{hex::FromHex, sha256d, Hash};
+        // `sha256d::Hash` parses the displayed hex into the internal byte order
+        fn internal(display: &str) -> [u8; 32] {
+            sha256d::Hash::from_hex(display).unwrap().into_inner()
+        }
+        let coinbase = internal("8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87");
+        let tx1 = internal("fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4");
+        let tx2 = internal("6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4");
+        let tx3 = internal("e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d");
+        let merkle_root =
+            internal("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+
+        let tx2_tx3 = sha256d::Hash::hash(&[tx2, tx3].concat()).into_inner();
+        assert_eq!(
+            super::compute_merkle_root(coinbase, &[tx1, tx2_tx3]),
+            merkle_root
+        );
+        // a single transaction block has the coinbase hash as merkle root
+        assert_eq!(super::compute_merkle_root(coinbase, &[]), coinbase);
+    }
+
+    #[test]
+    fn test_find_witness_commitment() {
+        use bitcoin::{consensus::encode::serialize, PackedLockTime, TxIn, TxOut};
+
+        fn coinbase(scripts: Vec<Vec<u8>>) -> Vec<u8> {
+            let output = scripts
+                .into_iter()
+                .map(|script| TxOut {
+                    value: 0,
+                    script_pubkey: bitcoin::Script::from(script),
+                })
+                .collect();
+            serialize(&bitcoin::Transaction {
+                version: 2,
+                lock_time: PackedLockTime(0),
+                input: vec![TxIn::default()],
+                output,
+            })
+        }
+        // p2wpkh payout
+        let payout = [&[0x00, 0x14][..], &[0x11; 20][..]].concat();
+        let commitment_script =
+            |byte: u8| [&super::WITNESS_COMMITMENT_HEADER[..], &[byte; 32]].concat();
+
+        let with_commitment = coinbase(vec![payout.clone(), commitment_script(0xcc)]);
+        assert_eq!(
+            super::find_witness_commitment(&with_commitment),
+            Some((1, [0xcc; 32]))
+        );
+
+        // the last matching output is the commitment
+        let two_commitments = coinbase(vec![
+            commitment_script(0xaa),
+            payout.clone(),
+            commitment_script(0xbb),
+        ]);
+        assert_eq!(
+            super::find_witness_commitment(&two_commitments),
+            Some((2, [0xbb; 32]))
+        );
+
+        let without_commitment = coinbase(vec![payout]);
+        assert_eq!(super::find_witness_commitment(&without_commitment), None);
+        assert_eq!(super::find_witness_commitment(&[0x01, 0x02]), None);
+    }
+
     #[test]
     fn test_super_safe_lock() {
         let m = super::Mutex::new(1u32);
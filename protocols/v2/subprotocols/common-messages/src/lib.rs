@@ -17,6 +17,8 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 mod channel_endpoint_changed;
 mod setup_connection;
 
@@ -29,11 +31,14 @@ use quickcheck::{Arbitrary, Gen};
 
 pub use channel_endpoint_changed::ChannelEndpointChanged;
 pub use setup_connection::{
-    has_requires_std_job, has_version_rolling, has_work_selection, Protocol, SetupConnection,
-    SetupConnectionError, SetupConnectionSuccess,
+    has_requires_std_job, has_version_rolling, has_work_selection, Capabilities, Protocol,
+    SetupConnection, SetupConnectionError, SetupConnectionErrorBuilder, SetupConnectionErrorCode,
+    SetupConnectionSuccess, TelemetryError, VersionOutcome, REQUIRES_ASYNC_JOB_MINING,
+    REQUIRES_EXTENDED_CHANNELS, REQUIRES_FIXED_VERSION, REQUIRES_STANDARD_JOBS,
+    REQUIRES_VERSION_ROLLING, REQUIRES_WORK_SELECTION,
 };
 #[cfg(not(feature = "with_serde"))]
-pub use setup_connection::{CSetupConnection, CSetupConnectionError};
+pub use setup_connection::{CFieldError, CSetupConnection, CSetupConnectionError, DecodeStatus};
 
 #[cfg(not(feature = "with_serde"))]
 #[no_mangle]
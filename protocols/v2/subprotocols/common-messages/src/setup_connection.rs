@@ -1,5 +1,4 @@
-#[cfg(not(feature = "with_serde"))]
-use alloc::vec::Vec;
+use alloc::{format, string::String, vec::Vec};
 #[cfg(not(feature = "with_serde"))]
 use binary_sv2::{
     binary_codec_sv2, binary_codec_sv2::CVec, decodable::DecodableField, decodable::FieldMarker,
@@ -8,11 +7,12 @@ use binary_sv2::{
 use binary_sv2::{Deserialize, GetSize, Serialize, Str0255};
 use const_sv2::{
     SV2_JOB_DECLARATION_PROTOCOL_DISCRIMINANT, SV2_MINING_PROTOCOL_DISCRIMINANT,
-    SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT,
+    SV2_PROTOCOL_VERSION, SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT,
+};
+use core::{
+    convert::{TryFrom, TryInto},
+    str::Utf8Error,
 };
-use core::convert::TryFrom;
-#[cfg(not(feature = "with_serde"))]
-use core::convert::TryInto;
 #[cfg(feature = "with_serde")]
 use serde_repr::*;
 
@@ -64,77 +64,221 @@ pub struct SetupConnection<'decoder> {
     pub device_id: Str0255<'decoder>,
 }
 
+/// Mining Protocol [`SetupConnection::flags`] bit: the downstream requires standard jobs.
+pub const REQUIRES_STANDARD_JOBS: u32 = 0b_0000_0000_0000_0000_0000_0000_0000_0001;
+/// Mining Protocol [`SetupConnection::flags`] bit: the downstream requires custom work selection.
+pub const REQUIRES_WORK_SELECTION: u32 = 0b_0000_0000_0000_0000_0000_0000_0000_0010;
+/// Mining Protocol [`SetupConnection::flags`] bit: the downstream requires version rolling.
+pub const REQUIRES_VERSION_ROLLING: u32 = 0b_0000_0000_0000_0000_0000_0000_0000_0100;
+/// Job Declaration Protocol [`SetupConnection::flags`] bit: the downstream requires asynchronous
+/// job mining.
+pub const REQUIRES_ASYNC_JOB_MINING: u32 = 0b_0000_0000_0000_0000_0000_0000_0000_0001;
+/// Mining Protocol [`SetupConnectionSuccess::flags`] bit: the upstream does not accept changes to
+/// the version field of the header.
+pub const REQUIRES_FIXED_VERSION: u32 = 0b_0000_0000_0000_0000_0000_0000_0000_0001;
+/// Mining Protocol [`SetupConnectionSuccess::flags`] bit: the upstream does not accept opening
+/// standard channels.
+pub const REQUIRES_EXTENDED_CHANNELS: u32 = 0b_0000_0000_0000_0000_0000_0000_0000_0010;
+
+/// Result of [`SetupConnection::negotiate_version`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOutcome {
+    /// The version ranges do not overlap.
+    Incompatible,
+    /// The negotiated version is the highest one supported by the caller.
+    Exact(u16),
+    /// The negotiated version is lower than the highest one supported by the caller.
+    Downgraded(u16),
+}
+
+impl SetupConnection<'static> {
+    /// Builds a Mining Protocol `SetupConnection` for version 2 with the
+    /// [`Protocol::default_downstream_flags`].
+    ///
+    /// Errors if `host` or `vendor` is longer than 255 bytes.
+    pub fn new_mining(host: &str, port: u16, vendor: &str) -> Result<Self, binary_sv2::Error> {
+        Self::new_for_protocol(Protocol::MiningProtocol, host, port, vendor)
+    }
+
+    /// Builds a Job Declaration Protocol `SetupConnection` for version 2 with the
+    /// [`Protocol::default_downstream_flags`].
+    ///
+    /// Errors if `host` or `vendor` is longer than 255 bytes.
+    pub fn new_job_declaration(
+        host: &str,
+        port: u16,
+        vendor: &str,
+    ) -> Result<Self, binary_sv2::Error> {
+        Self::new_for_protocol(Protocol::JobDeclarationProtocol, host, port, vendor)
+    }
+
+    /// Builds a Template Distribution Protocol `SetupConnection` for version 2 with the
+    /// [`Protocol::default_downstream_flags`].
+    ///
+    /// Errors if `host` or `vendor` is longer than 255 bytes.
+    pub fn new_template_distribution(
+        host: &str,
+        port: u16,
+        vendor: &str,
+    ) -> Result<Self, binary_sv2::Error> {
+        Self::new_for_protocol(Protocol::TemplateDistributionProtocol, host, port, vendor)
+    }
+
+    fn new_for_protocol(
+        protocol: Protocol,
+        host: &str,
+        port: u16,
+        vendor: &str,
+    ) -> Result<Self, binary_sv2::Error> {
+        Ok(Self {
+            protocol,
+            min_version: SV2_PROTOCOL_VERSION,
+            max_version: SV2_PROTOCOL_VERSION,
+            flags: protocol.default_downstream_flags(),
+            endpoint_host: host.as_bytes().to_vec().try_into()?,
+            endpoint_port: port,
+            vendor: vendor.as_bytes().to_vec().try_into()?,
+            hardware_version: Vec::new().try_into()?,
+            firmware: Vec::new().try_into()?,
+            device_id: Vec::new().try_into()?,
+        })
+    }
+}
+
 impl<'decoder> SetupConnection<'decoder> {
     /// Set the flag to indicate that the downstream requires a standard job
     pub fn set_requires_standard_job(&mut self) {
-        self.flags |= 0b_0000_0000_0000_0000_0000_0000_0000_0001;
+        self.flags |= REQUIRES_STANDARD_JOBS;
     }
 
     /// Set the flag to indicate that the downstream requires an asynchronous job negotiation
     pub fn set_async_job_nogotiation(&mut self) {
-        self.flags |= 0b_0000_0000_0000_0000_0000_0000_0000_0001;
+        self.flags |= REQUIRES_ASYNC_JOB_MINING;
+    }
+
+    /// Decodes `flags` into a human readable description of each set bit, as defined for
+    /// `protocol`.
+    ///
+    /// Set bits that `protocol` does not define are reported as reserved.
+    pub fn describe_flags(protocol: Protocol, flags: u32) -> Vec<String> {
+        let known: &[(u32, &str)] = match protocol {
+            Protocol::MiningProtocol => &[
+                (REQUIRES_STANDARD_JOBS, "REQUIRES_STANDARD_JOBS"),
+                (REQUIRES_WORK_SELECTION, "REQUIRES_WORK_SELECTION"),
+                (REQUIRES_VERSION_ROLLING, "REQUIRES_VERSION_ROLLING"),
+            ],
+            Protocol::JobDeclarationProtocol => {
+                &[(REQUIRES_ASYNC_JOB_MINING, "REQUIRES_ASYNC_JOB_MINING")]
+            }
+            Protocol::TemplateDistributionProtocol => &[],
+        };
+        (0..32)
+            .filter(|bit| flags & (1 << bit) != 0)
+            .map(
+                |bit| match known.iter().find(|(flag, _)| *flag == 1 << bit) {
+                    Some((_, name)) => format!("bit {}: {}", bit, name),
+                    None => format!("bit {}: reserved", bit),
+                },
+            )
+            .collect()
+    }
+
+    /// Lists the fields that differ between `self` and `other`, one human readable line per
+    /// field, e.g. `max_version: 2 -> 3`. Changed flags are decoded with
+    /// [`SetupConnection::describe_flags`]. Returns an empty vector if the two are equal.
+    pub fn diff(&self, other: &SetupConnection) -> Vec<String> {
+        let mut diff = Vec::new();
+        if self.protocol != other.protocol {
+            diff.push(format!(
+                "protocol: {:?} -> {:?}",
+                self.protocol, other.protocol
+            ));
+        }
+        if self.min_version != other.min_version {
+            diff.push(format!(
+                "min_version: {} -> {}",
+                self.min_version, other.min_version
+            ));
+        }
+        if self.max_version != other.max_version {
+            diff.push(format!(
+                "max_version: {} -> {}",
+                self.max_version, other.max_version
+            ));
+        }
+        if self.flags != other.flags {
+            let removed = Self::describe_flags(self.protocol, self.flags & !other.flags);
+            let added = Self::describe_flags(other.protocol, other.flags & !self.flags);
+            diff.push(format!(
+                "flags: {:#b} -> {:#b} (added: [{}], removed: [{}])",
+                self.flags,
+                other.flags,
+                added.join(", "),
+                removed.join(", ")
+            ));
+        }
+        let strings: [(&str, &Str0255, &Str0255); 5] = [
+            ("endpoint_host", &self.endpoint_host, &other.endpoint_host),
+            ("vendor", &self.vendor, &other.vendor),
+            (
+                "hardware_version",
+                &self.hardware_version,
+                &other.hardware_version,
+            ),
+            ("firmware", &self.firmware, &other.firmware),
+            ("device_id", &self.device_id, &other.device_id),
+        ];
+        for (field, ours, theirs) in strings.iter() {
+            if ours.as_ref() != theirs.as_ref() {
+                diff.push(format!(
+                    "{}: {:?} -> {:?}",
+                    field,
+                    String::from_utf8_lossy(ours.as_ref()),
+                    String::from_utf8_lossy(theirs.as_ref())
+                ));
+            }
+        }
+        if self.endpoint_port != other.endpoint_port {
+            diff.push(format!(
+                "endpoint_port: {} -> {}",
+                self.endpoint_port, other.endpoint_port
+            ));
+        }
+        diff
     }
 
     /// Check if passed flags support self flag
+    ///
+    /// `available_flags` are the flags of the upstream and `required_flags` the ones sent by the
+    /// downstream. Bit layout of the flags, bit 0 is the least significant one:
+    ///
+    /// | bit | Mining Protocol            | Job Declaration Protocol    |
+    /// |-----|----------------------------|-----------------------------|
+    /// | 0   | `REQUIRES_STANDARD_JOBS`   | `REQUIRES_ASYNC_JOB_MINING` |
+    /// | 1   | `REQUIRES_WORK_SELECTION`  | reserved                    |
+    /// | 2   | `REQUIRES_VERSION_ROLLING` | reserved                    |
+    /// | 3+  | reserved                   | reserved                    |
+    ///
+    /// The Template Distribution Protocol does not define any flag.
     pub fn check_flags(protocol: Protocol, available_flags: u32, required_flags: u32) -> bool {
         match protocol {
-            // [0] [0] -> true
-            // [0] [1] -> false
-            // [1] [1] -> true
-            // [0] [1] -> false
             Protocol::MiningProtocol => {
-                // Evaluates protocol requirements based on flag bits.
-                //
-                // Checks if the current protocol meets the required flags for work selection and
-                // version rolling by reversing the bits of `available_flags` and
-                // `required_flags`. It extracts the 30th and 29th bits to determine
-                // if work selection and version rolling are needed.
-                //
-                // Returns `true` if:
-                // - The work selection requirement is satisfied or not needed.
-                // - The version rolling requirement is satisfied or not needed.
-                //
-                // Otherwise, returns `false`.
-                let available = available_flags.reverse_bits();
-                let required_flags = required_flags.reverse_bits();
-                let requires_work_selection_passed = required_flags >> 30 > 0;
-                let requires_version_rolling_passed = required_flags >> 29 > 0;
-
-                let requires_work_selection_self = available >> 30 > 0;
-                let requires_version_rolling_self = available >> 29 > 0;
-
-                let work_selection =
-                    !requires_work_selection_self || requires_work_selection_passed;
-                let version_rolling =
-                    !requires_version_rolling_self || requires_version_rolling_passed;
+                // Each requirement is considered set when its bit or any lower mining bit is set,
+                // i.e. the masks are cumulative. This is the behaviour of the previous
+                // implementation and is kept unchanged.
+                let work_selection_mask = REQUIRES_STANDARD_JOBS | REQUIRES_WORK_SELECTION;
+                let version_rolling_mask = work_selection_mask | REQUIRES_VERSION_ROLLING;
 
-                work_selection && version_rolling
+                // if the upstream sets a requirement the downstream must set it too
+                let satisfied =
+                    |mask: u32| available_flags & mask == 0 || required_flags & mask != 0;
+                satisfied(work_selection_mask) && satisfied(version_rolling_mask)
             }
             Protocol::JobDeclarationProtocol => {
-                // Determines if asynchronous job mining is required based on flag bits.
-                //
-                // Reverses the bits of `available_flags` and `required_flags`, extracts the 31st
-                // bit from each, and evaluates if the condition is met using these
-                // bits. Returns `true` or `false` based on:
-                // - True if `requires_async_job_mining_self` is true, or both are true.
-                // - False if `requires_async_job_mining_self` is false and
-                //   `requires_async_job_mining_passed` is true.
-                // - True otherwise.
-                let available = available_flags.reverse_bits();
-                let required = required_flags.reverse_bits();
-
-                let requires_async_job_mining_passed = (required >> 31) & 1 > 0;
-                let requires_async_job_mining_self = (available >> 31) & 1 > 0;
-
-                match (
-                    requires_async_job_mining_self,
-                    requires_async_job_mining_passed,
-                ) {
-                    (true, true) => true,
-                    (true, false) => true,
-                    (false, true) => false,
-                    (false, false) => true,
-                }
+                // only fails when the downstream requires async job mining and the upstream does
+                // not support it
+                available_flags & REQUIRES_ASYNC_JOB_MINING != 0
+                    || required_flags & REQUIRES_ASYNC_JOB_MINING == 0
             }
             Protocol::TemplateDistributionProtocol => {
                 // These protocols do not define flags for setting up a connection.
@@ -143,24 +287,140 @@ impl<'decoder> SetupConnection<'decoder> {
         }
     }
 
+    /// Returns the flags that make [`SetupConnection::check_flags`] fail, 0 if it succeeds.
+    ///
+    /// - Mining: the requirements of the upstream (`available_flags`) not accepted by the
+    ///   downstream (`required_flags`).
+    /// - Job Declaration: `REQUIRES_ASYNC_JOB_MINING` if the downstream requires it and the upstream
+    ///   does not support it.
+    /// - Template Distribution: all the `required_flags`, as no flag is defined.
+    pub fn flag_conflicts(protocol: Protocol, available_flags: u32, required_flags: u32) -> u32 {
+        if Self::check_flags(protocol, available_flags, required_flags) {
+            return 0;
+        }
+        match protocol {
+            Protocol::MiningProtocol => {
+                let known =
+                    REQUIRES_STANDARD_JOBS | REQUIRES_WORK_SELECTION | REQUIRES_VERSION_ROLLING;
+                available_flags & !required_flags & known
+            }
+            Protocol::JobDeclarationProtocol => {
+                required_flags & !available_flags & REQUIRES_ASYNC_JOB_MINING
+            }
+            Protocol::TemplateDistributionProtocol => required_flags,
+        }
+    }
+
     /// Check whether received versions are supported.
     ///
     /// If the versions are not supported, return `None` otherwise return the biggest version
-    /// available
+    /// available. An inverted range (`min > max`), either the received or the given one, supports
+    /// no version.
     pub fn get_version(&self, min_version: u16, max_version: u16) -> Option<u16> {
-        if self.min_version > max_version || min_version > self.max_version {
+        if self.min_version > self.max_version
+            || min_version > max_version
+            || self.min_version > max_version
+            || min_version > self.max_version
+        {
             None
         } else {
             Some(self.max_version.min(max_version))
         }
     }
 
+    /// Like [`SetupConnection::get_version`], but tells whether the version is the caller's
+    /// preferred one (`max_version`) or a lower one.
+    pub fn negotiate_version(&self, min_version: u16, max_version: u16) -> VersionOutcome {
+        match self.get_version(min_version, max_version) {
+            None => VersionOutcome::Incompatible,
+            Some(version) if version == max_version => VersionOutcome::Exact(version),
+            Some(version) => VersionOutcome::Downgraded(version),
+        }
+    }
+
+    /// Checks that `min_version <= max_version` and that the range includes
+    /// [`SV2_PROTOCOL_VERSION`].
+    ///
+    /// Returns a `protocol-version-mismatch` [`SetupConnectionError`] otherwise.
+    pub fn validate_versions(&self) -> Result<(), SetupConnectionError<'static>> {
+        if self.min_version <= SV2_PROTOCOL_VERSION && SV2_PROTOCOL_VERSION <= self.max_version {
+            Ok(())
+        } else {
+            Err(SetupConnectionError::builder().version_mismatch())
+        }
+    }
+
+    /// Sanity checks to run right after decoding a [`SetupConnection`], returning the
+    /// [`SetupConnectionError`] to send back if it fails:
+    /// - `unsupported-feature-flags` for a Template Distribution connection with any flag set, the
+    ///   protocol does not define any.
+    /// - `protocol-version-mismatch` if [`SetupConnection::validate_versions`] fails.
+    ///
+    /// String lengths are not checked here, [`Str0255`] already rejects strings longer than 255
+    /// bytes when decoding.
+    pub fn validate(&self) -> Result<(), SetupConnectionError<'static>> {
+        if self.protocol == Protocol::TemplateDistributionProtocol && self.flags != 0 {
+            return Err(SetupConnectionError::builder().unsupported_flags(self.flags, 0));
+        }
+        self.validate_versions()
+    }
+
+    /// Checks the telemetry fields against the rules of the specification: [`SetupConnection::vendor`]
+    /// **must** be set, while [`SetupConnection::device_id`] may be left empty by downstreams not
+    /// willing to provide telemetry data.
+    pub fn validate_telemetry(&self) -> Result<(), TelemetryError> {
+        if !self.vendor.as_ref().is_empty() {
+            Ok(())
+        } else if self.device_id.as_ref().is_empty() {
+            Err(TelemetryError::MissingVendor)
+        } else {
+            Err(TelemetryError::DeviceIdWithoutVendor)
+        }
+    }
+
+    /// Returns the [`SetupConnection::endpoint_host`] and [`SetupConnection::endpoint_port`] the
+    /// downstream connected to.
+    ///
+    /// Errors if `endpoint_host` is not valid UTF-8.
+    pub fn endpoint(&self) -> Result<(String, u16), Utf8Error> {
+        let host = core::str::from_utf8(self.endpoint_host.as_ref())?;
+        Ok((host.into(), self.endpoint_port))
+    }
+
+    /// Resolves [`SetupConnection::endpoint`] to socket addresses.
+    ///
+    /// A non UTF-8 `endpoint_host` is reported as an [`std::io::ErrorKind::InvalidData`] error.
+    #[cfg(feature = "std")]
+    pub fn endpoint_socket_addrs(&self) -> std::io::Result<Vec<std::net::SocketAddr>> {
+        use std::net::ToSocketAddrs;
+        let (host, port) = self
+            .endpoint()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok((host.as_str(), port).to_socket_addrs()?.collect())
+    }
+
+    /// True if [`SetupConnection::protocol`] is one of the `supported` subprotocols, otherwise the
+    /// upstream should answer with [`SetupConnectionError::unsupported_protocol`].
+    pub fn is_protocol_supported(&self, supported: &[Protocol]) -> bool {
+        supported.contains(&self.protocol)
+    }
+
     /// Checks whether passed flags indicate that the downstream requires standard job.
     pub fn requires_standard_job(&self) -> bool {
         has_requires_std_job(self.flags)
     }
 }
 
+/// Reason why [`SetupConnection::validate_telemetry`] rejected a [`SetupConnection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryError {
+    /// [`SetupConnection::vendor`] is empty.
+    MissingVendor,
+    /// [`SetupConnection::vendor`] is empty but [`SetupConnection::device_id`] is set, which likely
+    /// means the downstream filled the wrong field.
+    DeviceIdWithoutVendor,
+}
+
 /// Helper function to check if `REQUIRES_STANDARD_JOBS` bit flag present.
 pub fn has_requires_std_job(flags: u32) -> bool {
     let flags = flags.reverse_bits();
@@ -222,12 +482,25 @@ impl<'a> CSetupConnection {
     #[cfg(not(feature = "with_serde"))]
     #[allow(clippy::wrong_self_convention)]
     /// Convert C representation to Rust representation
+    ///
+    /// Use [`CSetupConnection::to_rust_rep_mut_with_field_error`] to know which field could not
+    /// be converted.
     pub fn to_rust_rep_mut(&'a mut self) -> Result<SetupConnection<'a>, Error> {
-        let endpoint_host: Str0255 = self.endpoint_host.as_mut_slice().try_into()?;
-        let vendor: Str0255 = self.vendor.as_mut_slice().try_into()?;
-        let hardware_version: Str0255 = self.hardware_version.as_mut_slice().try_into()?;
-        let firmware: Str0255 = self.firmware.as_mut_slice().try_into()?;
-        let device_id: Str0255 = self.device_id.as_mut_slice().try_into()?;
+        Ok(self.to_rust_rep_mut_with_field_error()?)
+    }
+
+    #[cfg(not(feature = "with_serde"))]
+    #[allow(clippy::wrong_self_convention)]
+    /// Like [`CSetupConnection::to_rust_rep_mut`], but errors with the name of the first string
+    /// field that does not fit in a [`Str0255`].
+    pub fn to_rust_rep_mut_with_field_error(
+        &'a mut self,
+    ) -> Result<SetupConnection<'a>, CFieldError> {
+        let endpoint_host = c_str0255("endpoint_host", &mut self.endpoint_host)?;
+        let vendor = c_str0255("vendor", &mut self.vendor)?;
+        let hardware_version = c_str0255("hardware_version", &mut self.hardware_version)?;
+        let firmware = c_str0255("firmware", &mut self.firmware)?;
+        let device_id = c_str0255("device_id", &mut self.device_id)?;
 
         Ok(SetupConnection {
             protocol: self.protocol,
@@ -244,6 +517,30 @@ impl<'a> CSetupConnection {
     }
 }
 
+/// Error converting a field of a C message to its Rust representation.
+#[cfg(not(feature = "with_serde"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CFieldError {
+    /// Name of the field that could not be converted.
+    pub field: &'static str,
+    pub error: Error,
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl From<CFieldError> for Error {
+    fn from(e: CFieldError) -> Self {
+        e.error
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+fn c_str0255<'a>(field: &'static str, value: &'a mut CVec) -> Result<Str0255<'a>, CFieldError> {
+    value
+        .as_mut_slice()
+        .try_into()
+        .map_err(|error| CFieldError { field, error })
+}
+
 #[no_mangle]
 #[cfg(not(feature = "with_serde"))]
 pub extern "C" fn free_setup_connection(s: CSetupConnection) {
@@ -279,6 +576,48 @@ impl<'a> From<SetupConnection<'a>> for CSetupConnection {
     }
 }
 
+/// Reason why [`SetupConnection::decode_prefix`] could not decode a message.
+#[cfg(not(feature = "with_serde"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeStatus {
+    /// The buffer ends before the message does, at least this many more bytes are needed.
+    Incomplete(usize),
+    /// The bytes can not be decoded as a `SetupConnection`.
+    Invalid(Error),
+}
+
+#[cfg(not(feature = "with_serde"))]
+impl SetupConnection<'static> {
+    /// Decodes the `SetupConnection` at the start of `buf`, returning it together with the number
+    /// of bytes it takes.
+    ///
+    /// Only the length prefixes of the string fields are read until the whole message is in
+    /// `buf`, so a stream reader can use [`DecodeStatus::Incomplete`] to know how much more to read
+    /// without reading past the end of the message. Bytes after the message are left untouched.
+    pub fn decode_prefix(buf: &[u8]) -> Result<(Self, usize), DecodeStatus> {
+        // protocol, min_version, max_version and flags
+        let mut len = 1 + 2 + 2 + 4;
+        // endpoint_host, endpoint_port, vendor, hardware_version, firmware and device_id
+        for is_str0255 in [true, false, true, true, true, true].iter() {
+            len += if *is_str0255 {
+                match buf.get(len) {
+                    Some(str_len) => 1 + *str_len as usize,
+                    None => return Err(DecodeStatus::Incomplete(len + 1 - buf.len())),
+                }
+            } else {
+                2
+            };
+        }
+        if buf.len() < len {
+            return Err(DecodeStatus::Incomplete(len - buf.len()));
+        }
+        let mut message = buf[..len].to_vec();
+        let decoded: SetupConnection =
+            binary_sv2::from_bytes(&mut message).map_err(DecodeStatus::Invalid)?;
+        Ok((decoded.into_static(), len))
+    }
+}
+
 /// Message used by an upstream role to accept a connection setup request from a downstream role.
 ///
 /// This message is sent in response to a [`SetupConnection`] message.
@@ -298,6 +637,74 @@ pub struct SetupConnectionSuccess {
     pub flags: u32,
 }
 
+impl SetupConnectionSuccess {
+    /// True if the upstream accepts shares with a rolled version field, i.e.
+    /// [`REQUIRES_FIXED_VERSION`] is not set.
+    ///
+    /// Like the other flag readers, only meaningful for a Mining Protocol connection.
+    pub fn supports_version_rolling(&self) -> bool {
+        self.flags & REQUIRES_FIXED_VERSION == 0
+    }
+
+    /// True if the upstream accepts standard channels, i.e. [`REQUIRES_EXTENDED_CHANNELS`] is not
+    /// set.
+    pub fn supports_standard_channels(&self) -> bool {
+        self.flags & REQUIRES_EXTENDED_CHANNELS == 0
+    }
+
+    /// True if the upstream only accepts extended channels.
+    pub fn requires_extended_channels(&self) -> bool {
+        !self.supports_standard_channels()
+    }
+}
+
+/// Features in use on a connection once the upstream answered a [`SetupConnection`] with a
+/// [`SetupConnectionSuccess`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// [`SetupConnectionSuccess::used_version`].
+    pub version: u16,
+    /// The downstream asked for version rolling and the upstream does not require a fixed version.
+    pub version_rolling: bool,
+    /// The downstream asked for custom work selection.
+    pub work_selection: bool,
+    /// The downstream asked for standard jobs and the upstream accepts standard channels.
+    pub standard_job: bool,
+    /// The downstream asked for asynchronous job mining.
+    pub async_job_negotiation: bool,
+}
+
+impl Capabilities {
+    /// Intersects the flags requested in `req` with the ones granted in `success`.
+    ///
+    /// Flags are read as defined for `req.protocol`. The upstream has no flag to decline custom
+    /// work selection or asynchronous job mining, it answers with a [`SetupConnectionError`]
+    /// instead, so a success grants them whenever they are requested.
+    pub fn negotiate(req: &SetupConnection, success: &SetupConnectionSuccess) -> Self {
+        let mut capabilities = Self {
+            version: success.used_version,
+            version_rolling: false,
+            work_selection: false,
+            standard_job: false,
+            async_job_negotiation: false,
+        };
+        match req.protocol {
+            Protocol::MiningProtocol => {
+                capabilities.version_rolling =
+                    req.flags & REQUIRES_VERSION_ROLLING != 0 && success.supports_version_rolling();
+                capabilities.work_selection = req.flags & REQUIRES_WORK_SELECTION != 0;
+                capabilities.standard_job =
+                    req.flags & REQUIRES_STANDARD_JOBS != 0 && success.supports_standard_channels();
+            }
+            Protocol::JobDeclarationProtocol => {
+                capabilities.async_job_negotiation = req.flags & REQUIRES_ASYNC_JOB_MINING != 0;
+            }
+            Protocol::TemplateDistributionProtocol => (),
+        }
+        capabilities
+    }
+}
+
 /// Message used by an upstream role to reject a connection setup request from a downstream role.
 ///
 /// This message is sent in response to a [`SetupConnection`] message.
@@ -328,6 +735,132 @@ pub struct SetupConnectionError<'decoder> {
     pub error_code: Str0255<'decoder>,
 }
 
+impl SetupConnectionError<'static> {
+    /// Returns a [`SetupConnectionErrorBuilder`] that sets the error code and flags coherently.
+    pub fn builder() -> SetupConnectionErrorBuilder {
+        SetupConnectionErrorBuilder
+    }
+
+    /// Returns the `unsupported-protocol` error to send when [`SetupConnection::protocol`] is not
+    /// spoken by the upstream, see [`SetupConnection::is_protocol_supported`].
+    pub fn unsupported_protocol() -> Self {
+        Self::builder().unsupported_protocol()
+    }
+
+    /// Returns the `unsupported-feature-flags` error to send when
+    /// [`SetupConnection::check_flags`] fails, with the [`SetupConnection::flag_conflicts`] as
+    /// flags, or `None` if the flags are compatible.
+    pub fn from_flag_check(protocol: Protocol, available: u32, required: u32) -> Option<Self> {
+        if SetupConnection::check_flags(protocol, available, required) {
+            return None;
+        }
+        Some(Self::from_code(
+            SetupConnectionErrorCode::UnsupportedFeatureFlags,
+            SetupConnection::flag_conflicts(protocol, available, required),
+        ))
+    }
+
+    fn from_code(code: SetupConnectionErrorCode, flags: u32) -> Self {
+        Self {
+            flags,
+            // below unwrap never panics, the spec error codes are shorter than 255 bytes
+            error_code: code.as_bytes().to_vec().try_into().unwrap(),
+        }
+    }
+}
+
+impl<'decoder> SetupConnectionError<'decoder> {
+    /// Parses [`SetupConnectionError::error_code`].
+    pub fn code(&self) -> SetupConnectionErrorCode {
+        self.error_code.as_ref().into()
+    }
+
+    /// Checks whether both errors have the same error code, regardless of their flags.
+    pub fn same_reason(&self, other: &Self) -> bool {
+        self.code() == other.code()
+    }
+
+    /// Returns the flags supported by all the backends of a proxy, given the errors they returned
+    /// to a [`SetupConnection`] with all the `requested_all` flags set (see the type docs).
+    ///
+    /// A flag reported as unsupported by any `unsupported-feature-flags` error is removed, errors
+    /// with other codes do not carry flags and are ignored.
+    pub fn intersect_supported(errors: &[SetupConnectionError], requested_all: u32) -> u32 {
+        errors
+            .iter()
+            .filter(|error| error.code() == SetupConnectionErrorCode::UnsupportedFeatureFlags)
+            .fold(requested_all, |supported, error| supported & !error.flags)
+    }
+}
+
+/// Builds a [`SetupConnectionError`] for one of the error codes defined by the specification.
+///
+/// Each method returns the finished message, `flags` is only non zero for
+/// `unsupported-feature-flags`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SetupConnectionErrorBuilder;
+
+impl SetupConnectionErrorBuilder {
+    /// `unsupported-feature-flags` error, `flags` is set to the `requested` flags that are not in
+    /// `supported`.
+    pub fn unsupported_flags(
+        self,
+        requested: u32,
+        supported: u32,
+    ) -> SetupConnectionError<'static> {
+        SetupConnectionError::from_code(
+            SetupConnectionErrorCode::UnsupportedFeatureFlags,
+            requested & !supported,
+        )
+    }
+
+    /// `unsupported-protocol` error.
+    pub fn unsupported_protocol(self) -> SetupConnectionError<'static> {
+        SetupConnectionError::from_code(SetupConnectionErrorCode::UnsupportedProtocol, 0)
+    }
+
+    /// `protocol-version-mismatch` error.
+    pub fn version_mismatch(self) -> SetupConnectionError<'static> {
+        SetupConnectionError::from_code(SetupConnectionErrorCode::ProtocolVersionMismatch, 0)
+    }
+}
+
+/// Error codes that can be carried by [`SetupConnectionError::error_code`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SetupConnectionErrorCode {
+    /// `unsupported-feature-flags`
+    UnsupportedFeatureFlags,
+    /// `unsupported-protocol`
+    UnsupportedProtocol,
+    /// `protocol-version-mismatch`
+    ProtocolVersionMismatch,
+    /// Any error code not defined by the specification, kept verbatim.
+    Unknown(Vec<u8>),
+}
+
+impl SetupConnectionErrorCode {
+    /// Returns the error code as sent on the wire.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            SetupConnectionErrorCode::UnsupportedFeatureFlags => b"unsupported-feature-flags",
+            SetupConnectionErrorCode::UnsupportedProtocol => b"unsupported-protocol",
+            SetupConnectionErrorCode::ProtocolVersionMismatch => b"protocol-version-mismatch",
+            SetupConnectionErrorCode::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<&[u8]> for SetupConnectionErrorCode {
+    fn from(code: &[u8]) -> Self {
+        match code {
+            b"unsupported-feature-flags" => SetupConnectionErrorCode::UnsupportedFeatureFlags,
+            b"unsupported-protocol" => SetupConnectionErrorCode::UnsupportedProtocol,
+            b"protocol-version-mismatch" => SetupConnectionErrorCode::ProtocolVersionMismatch,
+            _ => SetupConnectionErrorCode::Unknown(code.to_vec()),
+        }
+    }
+}
+
 #[repr(C)]
 #[cfg(not(feature = "with_serde"))]
 #[derive(Debug, Clone)]
@@ -389,11 +922,54 @@ pub enum Protocol {
     TemplateDistributionProtocol = SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT,
 }
 
+impl Protocol {
+    /// Returns the `SV2_*_PROTOCOL_DISCRIMINANT` of the protocol.
+    pub const fn discriminant(self) -> u8 {
+        match self {
+            Protocol::MiningProtocol => SV2_MINING_PROTOCOL_DISCRIMINANT,
+            Protocol::JobDeclarationProtocol => SV2_JOB_DECLARATION_PROTOCOL_DISCRIMINANT,
+            Protocol::TemplateDistributionProtocol => SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT,
+        }
+    }
+
+    /// Returns the protocol discriminant, as sent on the wire.
+    pub fn as_u8(&self) -> u8 {
+        self.discriminant()
+    }
+
+    /// Baseline [`SetupConnection::flags`] for a downstream of this protocol.
+    ///
+    /// - Mining: [`REQUIRES_VERSION_ROLLING`], as any ASIC rolls the version bits.
+    /// - Job Declaration: 0, jobs are declared synchronously.
+    /// - Template Distribution: 0, no flags are defined.
+    pub fn default_downstream_flags(&self) -> u32 {
+        match self {
+            Protocol::MiningProtocol => REQUIRES_VERSION_ROLLING,
+            Protocol::JobDeclarationProtocol => 0,
+            Protocol::TemplateDistributionProtocol => 0,
+        }
+    }
+
+    /// Set of [`SetupConnection::flags`] commonly negotiated for this protocol, use
+    /// [`SetupConnection::describe_flags`] to get a human readable description.
+    ///
+    /// - Mining: [`REQUIRES_VERSION_ROLLING`] and [`REQUIRES_WORK_SELECTION`], as set by proxies
+    ///   that select their own work.
+    /// - Job Declaration: [`REQUIRES_ASYNC_JOB_MINING`].
+    /// - Template Distribution: 0, no flags are defined.
+    pub fn recommended_flags(&self) -> u32 {
+        match self {
+            Protocol::MiningProtocol => REQUIRES_VERSION_ROLLING | REQUIRES_WORK_SELECTION,
+            Protocol::JobDeclarationProtocol => REQUIRES_ASYNC_JOB_MINING,
+            Protocol::TemplateDistributionProtocol => 0,
+        }
+    }
+}
+
 #[cfg(not(feature = "with_serde"))]
 impl<'a> From<Protocol> for binary_sv2::encodable::EncodableField<'a> {
     fn from(v: Protocol) -> Self {
-        let val = v as u8;
-        val.into()
+        v.as_u8().into()
     }
 }
 
@@ -437,11 +1013,7 @@ impl GetSize for Protocol {
 #[cfg(feature = "with_serde")]
 impl From<Protocol> for u8 {
     fn from(val: Protocol) -> Self {
-        match val {
-            Protocol::MiningProtocol => SV2_MINING_PROTOCOL_DISCRIMINANT,
-            Protocol::JobDeclarationProtocol => SV2_JOB_DECLARATION_PROTOCOL_DISCRIMINANT,
-            Protocol::TemplateDistributionProtocol => SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT,
-        }
+        val.as_u8()
     }
 }
 
@@ -475,7 +1047,7 @@ impl<'d> GetSize for SetupConnection<'d> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::alloc::string::ToString;
+    use crate::alloc::{string::ToString, vec};
     use core::convert::TryInto;
 
     #[test]
@@ -500,6 +1072,83 @@ mod test {
         ));
     }
 
+    /// The `reverse_bits` based implementation replaced by the mask based `check_flags`, kept to
+    /// check that the two are equivalent.
+    fn legacy_check_flags(protocol: Protocol, available_flags: u32, required_flags: u32) -> bool {
+        match protocol {
+            Protocol::MiningProtocol => {
+                let available = available_flags.reverse_bits();
+                let required_flags = required_flags.reverse_bits();
+                let requires_work_selection_passed = required_flags >> 30 > 0;
+                let requires_version_rolling_passed = required_flags >> 29 > 0;
+
+                let requires_work_selection_self = available >> 30 > 0;
+                let requires_version_rolling_self = available >> 29 > 0;
+
+                let work_selection =
+                    !requires_work_selection_self || requires_work_selection_passed;
+                let version_rolling =
+                    !requires_version_rolling_self || requires_version_rolling_passed;
+
+                work_selection && version_rolling
+            }
+            Protocol::JobDeclarationProtocol => {
+                let available = available_flags.reverse_bits();
+                let required = required_flags.reverse_bits();
+
+                let requires_async_job_mining_passed = (required >> 31) & 1 > 0;
+                let requires_async_job_mining_self = (available >> 31) & 1 > 0;
+
+                match (
+                    requires_async_job_mining_self,
+                    requires_async_job_mining_passed,
+                ) {
+                    (true, true) => true,
+                    (true, false) => true,
+                    (false, true) => false,
+                    (false, false) => true,
+                }
+            }
+            Protocol::TemplateDistributionProtocol => false,
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn check_flags_matches_legacy_mining(available_flags: u32, required_flags: u32) -> bool {
+        let protocol = Protocol::MiningProtocol;
+        SetupConnection::check_flags(protocol, available_flags, required_flags)
+            == legacy_check_flags(protocol, available_flags, required_flags)
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn check_flags_matches_legacy_job_declaration(
+        available_flags: u32,
+        required_flags: u32,
+    ) -> bool {
+        let protocol = Protocol::JobDeclarationProtocol;
+        SetupConnection::check_flags(protocol, available_flags, required_flags)
+            == legacy_check_flags(protocol, available_flags, required_flags)
+    }
+
+    #[test]
+    fn check_flags_matches_legacy_on_low_bits() {
+        // random u32 rarely hit the interesting combinations, check all of them for the low bits
+        for protocol in [
+            Protocol::MiningProtocol,
+            Protocol::JobDeclarationProtocol,
+            Protocol::TemplateDistributionProtocol,
+        ] {
+            for available_flags in 0..16 {
+                for required_flags in 0..16 {
+                    assert_eq!(
+                        SetupConnection::check_flags(protocol, available_flags, required_flags),
+                        legacy_check_flags(protocol, available_flags, required_flags)
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_has_requires_std_job() {
         let flags = 0b_0000_0000_0000_0000_0000_0000_0000_0001;
@@ -539,6 +1188,55 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_endpoint_ip_host() {
+        let mut setup_conn = create_setup_connection();
+        setup_conn.endpoint_host = "127.0.0.1".to_string().into_bytes().try_into().unwrap();
+        setup_conn.endpoint_port = 34254;
+        assert_eq!(
+            setup_conn.endpoint().unwrap(),
+            ("127.0.0.1".to_string(), 34254)
+        );
+        #[cfg(feature = "std")]
+        assert_eq!(
+            setup_conn.endpoint_socket_addrs().unwrap(),
+            vec![std::net::SocketAddr::from(([127, 0, 0, 1], 34254))]
+        );
+    }
+
+    #[test]
+    fn test_endpoint_non_utf8_host() {
+        let mut setup_conn = create_setup_connection();
+        setup_conn.endpoint_host = vec![b'p', b'o', b'o', b'l', 0xff].try_into().unwrap();
+        assert!(setup_conn.endpoint().is_err());
+        #[cfg(feature = "std")]
+        assert_eq!(
+            setup_conn.endpoint_socket_addrs().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_validate_telemetry() {
+        let mut setup_conn = create_setup_connection();
+        assert_eq!(setup_conn.validate_telemetry(), Ok(()));
+
+        setup_conn.device_id = "".to_string().into_bytes().try_into().unwrap();
+        assert_eq!(setup_conn.validate_telemetry(), Ok(()));
+
+        setup_conn.vendor = "".to_string().into_bytes().try_into().unwrap();
+        assert_eq!(
+            setup_conn.validate_telemetry(),
+            Err(TelemetryError::MissingVendor)
+        );
+
+        setup_conn.device_id = "device_id".to_string().into_bytes().try_into().unwrap();
+        assert_eq!(
+            setup_conn.validate_telemetry(),
+            Err(TelemetryError::DeviceIdWithoutVendor)
+        );
+    }
+
     #[test]
     fn test_get_version() {
         let setup_conn = create_setup_connection();
@@ -546,6 +1244,181 @@ mod test {
         assert_eq!(setup_conn.get_version(6, 6), None);
     }
 
+    #[test]
+    fn test_describe_mining_flags() {
+        let flags = REQUIRES_WORK_SELECTION | REQUIRES_VERSION_ROLLING;
+        assert_eq!(
+            SetupConnection::describe_flags(Protocol::MiningProtocol, flags),
+            vec![
+                "bit 1: REQUIRES_WORK_SELECTION".to_string(),
+                "bit 2: REQUIRES_VERSION_ROLLING".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_job_declaration_flags() {
+        let flags = REQUIRES_ASYNC_JOB_MINING | 0b_1000_0000_0000_0000_0000_0000_0000_0000;
+        assert_eq!(
+            SetupConnection::describe_flags(Protocol::JobDeclarationProtocol, flags),
+            vec![
+                "bit 0: REQUIRES_ASYNC_JOB_MINING".to_string(),
+                "bit 31: reserved".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_setup_connection_error_same_reason() {
+        let a = SetupConnectionError {
+            flags: 0b_0000_0000_0000_0000_0000_0000_0000_0010,
+            error_code: "unsupported-feature-flags"
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+        };
+        let b = SetupConnectionError {
+            flags: 0b_0000_0000_0000_0000_0000_0000_0000_0100,
+            error_code: "unsupported-feature-flags"
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+        };
+        let c = SetupConnectionError {
+            flags: 0,
+            error_code: "unsupported-protocol"
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+        };
+        assert_ne!(a, b);
+        assert!(a.same_reason(&b));
+        assert!(!a.same_reason(&c));
+        assert_eq!(a.code(), SetupConnectionErrorCode::UnsupportedFeatureFlags);
+
+        extern crate std;
+        let mut buckets = std::collections::HashSet::new();
+        for e in [&a, &b, &c] {
+            buckets.insert(e.code());
+        }
+        assert_eq!(buckets.len(), 2);
+    }
+
+    #[test]
+    fn test_protocol_discriminant() {
+        let protocols = [
+            (Protocol::MiningProtocol, SV2_MINING_PROTOCOL_DISCRIMINANT),
+            (
+                Protocol::JobDeclarationProtocol,
+                SV2_JOB_DECLARATION_PROTOCOL_DISCRIMINANT,
+            ),
+            (
+                Protocol::TemplateDistributionProtocol,
+                SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT,
+            ),
+        ];
+        for (protocol, discriminant) in protocols {
+            assert_eq!(protocol.as_u8(), discriminant);
+            assert_eq!(protocol.discriminant(), discriminant);
+            assert_eq!(Protocol::try_from(discriminant), Ok(protocol));
+        }
+    }
+
+    #[test]
+    fn test_validate_versions() {
+        let mut setup_conn = create_setup_connection();
+        assert!(setup_conn.validate_versions().is_ok());
+
+        setup_conn.min_version = 3;
+        setup_conn.max_version = 2;
+        let error = setup_conn.validate_versions().unwrap_err();
+        assert_eq!(
+            error.code(),
+            SetupConnectionErrorCode::ProtocolVersionMismatch
+        );
+
+        setup_conn.min_version = 3;
+        setup_conn.max_version = 4;
+        let error = setup_conn.validate_versions().unwrap_err();
+        assert_eq!(
+            error.code(),
+            SetupConnectionErrorCode::ProtocolVersionMismatch
+        );
+        assert_eq!(error.flags, 0);
+    }
+
+    #[test]
+    fn test_setup_connection_error_builder() {
+        let requested = REQUIRES_STANDARD_JOBS | REQUIRES_VERSION_ROLLING;
+        let supported = REQUIRES_STANDARD_JOBS;
+        let error = SetupConnectionError::builder().unsupported_flags(requested, supported);
+        assert_eq!(
+            error.error_code.to_vec(),
+            b"unsupported-feature-flags".to_vec()
+        );
+        assert_eq!(error.flags, REQUIRES_VERSION_ROLLING);
+
+        let error = SetupConnectionError::builder().unsupported_protocol();
+        assert_eq!(error.error_code.to_vec(), b"unsupported-protocol".to_vec());
+        assert_eq!(error.flags, 0);
+
+        let error = SetupConnectionError::builder().version_mismatch();
+        assert_eq!(
+            error.error_code.to_vec(),
+            b"protocol-version-mismatch".to_vec()
+        );
+        assert_eq!(error.flags, 0);
+    }
+
+    #[test]
+    fn test_unsupported_protocol() {
+        let respond = |setup: &SetupConnection| {
+            if setup.is_protocol_supported(&[Protocol::MiningProtocol]) {
+                None
+            } else {
+                Some(SetupConnectionError::unsupported_protocol())
+            }
+        };
+
+        let mining = SetupConnection::new_mining("0.0.0.0", 3333, "Bitmain").unwrap();
+        assert!(mining
+            .is_protocol_supported(&[Protocol::JobDeclarationProtocol, Protocol::MiningProtocol]));
+        assert_eq!(respond(&mining), None);
+
+        let template_distribution =
+            SetupConnection::new_template_distribution("0.0.0.0", 8442, "SRI").unwrap();
+        assert!(!template_distribution.is_protocol_supported(&[]));
+        let error = respond(&template_distribution).unwrap();
+        assert_eq!(error.code(), SetupConnectionErrorCode::UnsupportedProtocol);
+        assert_eq!(error.error_code.to_vec(), b"unsupported-protocol".to_vec());
+        assert_eq!(error.flags, 0);
+    }
+
+    #[test]
+    fn test_protocol_default_flags() {
+        let tdp = Protocol::TemplateDistributionProtocol;
+        assert_eq!(tdp.default_downstream_flags(), 0);
+        assert_eq!(tdp.recommended_flags(), 0);
+
+        let mining = Protocol::MiningProtocol;
+        assert_eq!(mining.default_downstream_flags(), 0b0100);
+        assert_eq!(mining.recommended_flags(), 0b0110);
+        assert_eq!(
+            SetupConnection::describe_flags(mining, mining.recommended_flags()),
+            vec![
+                "bit 1: REQUIRES_WORK_SELECTION".to_string(),
+                "bit 2: REQUIRES_VERSION_ROLLING".to_string(),
+            ]
+        );
+
+        let jd = Protocol::JobDeclarationProtocol;
+        assert_eq!(jd.default_downstream_flags(), 0);
+        assert_eq!(jd.recommended_flags(), 0b0001);
+    }
+
     // Test SetupConnection::set_requires_std_job
     #[test]
     fn test_set_requires_std_job() {
@@ -554,4 +1427,334 @@ mod test {
         setup_conn.set_requires_standard_job();
         assert!(setup_conn.requires_standard_job());
     }
+
+    #[test]
+    fn test_protocol_constructors() {
+        let mining = SetupConnection::new_mining("pool.example.com", 34254, "vendor").unwrap();
+        assert_eq!(mining.protocol.as_u8(), SV2_MINING_PROTOCOL_DISCRIMINANT);
+        assert_eq!(mining.flags, REQUIRES_VERSION_ROLLING);
+        assert_eq!(mining.endpoint_port, 34254);
+        assert_eq!(mining.vendor.to_vec(), b"vendor".to_vec());
+
+        let jd = SetupConnection::new_job_declaration("127.0.0.1", 34264, "vendor").unwrap();
+        assert_eq!(
+            jd.protocol.as_u8(),
+            SV2_JOB_DECLARATION_PROTOCOL_DISCRIMINANT
+        );
+        assert_eq!(jd.flags, 0);
+
+        let tdp = SetupConnection::new_template_distribution("127.0.0.1", 8442, "vendor").unwrap();
+        assert_eq!(
+            tdp.protocol.as_u8(),
+            SV2_TEMPLATE_DISTR_PROTOCOL_DISCRIMINANT
+        );
+        assert_eq!(tdp.flags, 0);
+
+        for setup_conn in [mining, jd, tdp] {
+            assert_eq!(setup_conn.min_version, 2);
+            assert_eq!(setup_conn.max_version, 2);
+            assert!(setup_conn.validate_versions().is_ok());
+        }
+
+        let too_long = "a".repeat(256);
+        assert!(SetupConnection::new_mining(&too_long, 34254, "vendor").is_err());
+        assert!(SetupConnection::new_mining("127.0.0.1", 34254, &too_long).is_err());
+    }
+
+    #[cfg(not(feature = "with_serde"))]
+    #[test]
+    fn test_c_setup_connection_names_oversized_field() {
+        let mut c_setup_conn: CSetupConnection = create_setup_connection().into();
+        assert!(c_setup_conn.to_rust_rep_mut_with_field_error().is_ok());
+
+        let mut c_setup_conn: CSetupConnection = create_setup_connection().into();
+        free_vec(&mut c_setup_conn.vendor);
+        c_setup_conn.vendor = (&[b'a'; 256][..]).into();
+        let err = c_setup_conn.to_rust_rep_mut_with_field_error().unwrap_err();
+        assert_eq!(err.field, "vendor");
+        assert!(matches!(
+            err.error,
+            Error::ValueExceedsMaxSize(_, _, _, 255, _, 256)
+        ));
+        // to_rust_rep_mut keeps returning the codec error
+        assert_eq!(c_setup_conn.to_rust_rep_mut().unwrap_err(), err.error);
+
+        let mut c_setup_conn: CSetupConnection = create_setup_connection().into();
+        free_vec(&mut c_setup_conn.device_id);
+        c_setup_conn.device_id = (&[b'a'; 300][..]).into();
+        assert_eq!(
+            c_setup_conn
+                .to_rust_rep_mut_with_field_error()
+                .unwrap_err()
+                .field,
+            "device_id"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = SetupConnection::new_mining("0.0.0.0", 3333, "Bitmain").unwrap();
+        assert!(expected.diff(&expected).is_empty());
+
+        let mut received = expected.clone();
+        received.flags = REQUIRES_STANDARD_JOBS;
+        received.max_version = SV2_PROTOCOL_VERSION + 1;
+        assert_eq!(
+            expected.diff(&received),
+            vec![
+                format!(
+                    "max_version: {} -> {}",
+                    SV2_PROTOCOL_VERSION,
+                    SV2_PROTOCOL_VERSION + 1
+                ),
+                "flags: 0b100 -> 0b1 (added: [bit 0: REQUIRES_STANDARD_JOBS], removed: [bit 2: \
+                 REQUIRES_VERSION_ROLLING])"
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        let mut setup = SetupConnection::new_mining("0.0.0.0", 3333, "Bitmain").unwrap();
+        setup.min_version = 2;
+        setup.max_version = 3;
+
+        assert_eq!(setup.negotiate_version(2, 3), VersionOutcome::Exact(3));
+        assert_eq!(setup.negotiate_version(1, 2), VersionOutcome::Exact(2));
+        assert_eq!(setup.negotiate_version(2, 4), VersionOutcome::Downgraded(3));
+        assert_eq!(setup.negotiate_version(4, 5), VersionOutcome::Incompatible);
+        assert_eq!(setup.negotiate_version(0, 1), VersionOutcome::Incompatible);
+    }
+
+    #[test]
+    fn test_inverted_version_range() {
+        let mut setup = SetupConnection::new_mining("0.0.0.0", 3333, "Bitmain").unwrap();
+        setup.min_version = 3;
+        setup.max_version = 2;
+        assert_eq!(setup.get_version(2, 3), None);
+        assert_eq!(setup.negotiate_version(2, 3), VersionOutcome::Incompatible);
+        assert_eq!(
+            setup.validate().unwrap_err().code(),
+            SetupConnectionErrorCode::ProtocolVersionMismatch
+        );
+
+        // an inverted range given by the caller does not overlap anything either
+        setup.min_version = 2;
+        setup.max_version = 3;
+        assert_eq!(setup.get_version(3, 2), None);
+
+        // decoded from the wire, as a validator would receive it
+        #[cfg(not(feature = "with_serde"))]
+        {
+            setup.min_version = 2;
+            setup.max_version = 1;
+            let mut bytes = binary_sv2::to_bytes(setup).unwrap();
+            let decoded: SetupConnection = binary_sv2::from_bytes(&mut bytes).unwrap();
+            assert_eq!(decoded.get_version(1, 2), None);
+            assert!(decoded.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mining = SetupConnection::new_mining("0.0.0.0", 3333, "Bitmain").unwrap();
+        assert_eq!(mining.validate(), Ok(()));
+
+        let mut template_distribution =
+            SetupConnection::new_template_distribution("0.0.0.0", 8442, "SRI").unwrap();
+        assert_eq!(template_distribution.validate(), Ok(()));
+        template_distribution.flags = 0b101;
+        let error = template_distribution.validate().unwrap_err();
+        assert_eq!(
+            error.code(),
+            SetupConnectionErrorCode::UnsupportedFeatureFlags
+        );
+        assert_eq!(error.flags, 0b101);
+
+        let mut mining = mining;
+        mining.min_version = SV2_PROTOCOL_VERSION + 1;
+        mining.max_version = SV2_PROTOCOL_VERSION + 1;
+        assert_eq!(
+            mining.validate().unwrap_err().code(),
+            SetupConnectionErrorCode::ProtocolVersionMismatch
+        );
+    }
+
+    #[test]
+    fn test_setup_connection_success_flags() {
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: 0,
+        };
+        assert!(success.supports_version_rolling());
+        assert!(success.supports_standard_channels());
+        assert!(!success.requires_extended_channels());
+
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: REQUIRES_FIXED_VERSION | REQUIRES_EXTENDED_CHANNELS,
+        };
+        assert!(!success.supports_version_rolling());
+        assert!(!success.supports_standard_channels());
+        assert!(success.requires_extended_channels());
+    }
+
+    #[test]
+    fn test_capabilities_negotiate() {
+        let mut req = SetupConnection::new_mining("0.0.0.0", 3333, "Bitmain").unwrap();
+        req.flags = REQUIRES_STANDARD_JOBS | REQUIRES_WORK_SELECTION | REQUIRES_VERSION_ROLLING;
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: 0,
+        };
+        assert_eq!(
+            Capabilities::negotiate(&req, &success),
+            Capabilities {
+                version: 2,
+                version_rolling: true,
+                work_selection: true,
+                standard_job: true,
+                async_job_negotiation: false,
+            }
+        );
+
+        // the upstream requires a fixed version and extended channels
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: REQUIRES_FIXED_VERSION | REQUIRES_EXTENDED_CHANNELS,
+        };
+        assert_eq!(
+            Capabilities::negotiate(&req, &success),
+            Capabilities {
+                version: 2,
+                version_rolling: false,
+                work_selection: true,
+                standard_job: false,
+                async_job_negotiation: false,
+            }
+        );
+
+        // nothing granted that was not requested
+        req.flags = REQUIRES_VERSION_ROLLING;
+        let success = SetupConnectionSuccess {
+            used_version: 2,
+            flags: 0,
+        };
+        let capabilities = Capabilities::negotiate(&req, &success);
+        assert!(capabilities.version_rolling);
+        assert!(!capabilities.work_selection);
+        assert!(!capabilities.standard_job);
+
+        // bit 0 means asynchronous job mining for job declaration
+        let mut req = SetupConnection::new_job_declaration("0.0.0.0", 34264, "SRI").unwrap();
+        req.set_async_job_nogotiation();
+        let capabilities = Capabilities::negotiate(&req, &success);
+        assert!(capabilities.async_job_negotiation);
+        assert!(!capabilities.standard_job);
+    }
+
+    #[test]
+    fn test_intersect_supported() {
+        let all = REQUIRES_STANDARD_JOBS | REQUIRES_WORK_SELECTION | REQUIRES_VERSION_ROLLING;
+
+        // both backends only lack work selection
+        let errors = [
+            SetupConnectionError::builder().unsupported_flags(all, all & !REQUIRES_WORK_SELECTION),
+            SetupConnectionError::builder().unsupported_flags(all, all & !REQUIRES_WORK_SELECTION),
+        ];
+        assert_eq!(
+            SetupConnectionError::intersect_supported(&errors, all),
+            REQUIRES_STANDARD_JOBS | REQUIRES_VERSION_ROLLING
+        );
+
+        // the second backend also lacks version rolling
+        let errors = [
+            SetupConnectionError::builder().unsupported_flags(all, all & !REQUIRES_WORK_SELECTION),
+            SetupConnectionError::builder().unsupported_flags(all, REQUIRES_STANDARD_JOBS),
+            SetupConnectionError::builder().version_mismatch(),
+        ];
+        assert_eq!(
+            SetupConnectionError::intersect_supported(&errors, all),
+            REQUIRES_STANDARD_JOBS
+        );
+
+        assert_eq!(SetupConnectionError::intersect_supported(&[], all), all);
+    }
+
+    #[test]
+    fn test_setup_connection_error_from_flag_check() {
+        let mining = Protocol::MiningProtocol;
+        assert_eq!(
+            SetupConnectionError::from_flag_check(
+                mining,
+                REQUIRES_VERSION_ROLLING,
+                REQUIRES_VERSION_ROLLING
+            ),
+            None
+        );
+
+        // the upstream requires version rolling, the downstream does not accept it
+        let error =
+            SetupConnectionError::from_flag_check(mining, REQUIRES_VERSION_ROLLING, 0).unwrap();
+        assert_eq!(
+            error.code(),
+            SetupConnectionErrorCode::UnsupportedFeatureFlags
+        );
+        assert_eq!(error.flags, REQUIRES_VERSION_ROLLING);
+
+        // the downstream requires async job mining, the upstream does not support it
+        let jd = Protocol::JobDeclarationProtocol;
+        assert_eq!(SetupConnectionError::from_flag_check(jd, 0, 0), None);
+        let error =
+            SetupConnectionError::from_flag_check(jd, 0, REQUIRES_ASYNC_JOB_MINING).unwrap();
+        assert_eq!(error.flags, REQUIRES_ASYNC_JOB_MINING);
+    }
+
+    #[cfg(not(feature = "with_serde"))]
+    #[test]
+    fn test_decode_prefix() {
+        let setup = create_setup_connection();
+        let encoded = binary_sv2::to_bytes(setup.clone()).unwrap();
+        let mut stream = encoded.clone();
+        stream.extend_from_slice(&[0xff; 4]);
+
+        // a complete message followed by the start of the next one
+        let (decoded, consumed) = SetupConnection::decode_prefix(&stream).unwrap();
+        assert_eq!(decoded, setup);
+        assert_eq!(consumed, encoded.len());
+
+        // truncated in the fixed size fields, asks for the length of endpoint_host
+        assert_eq!(
+            SetupConnection::decode_prefix(&encoded[..4]),
+            Err(DecodeStatus::Incomplete(6))
+        );
+        // truncated in the last string, asks for exactly the missing bytes
+        assert_eq!(
+            SetupConnection::decode_prefix(&encoded[..encoded.len() - 3]),
+            Err(DecodeStatus::Incomplete(3))
+        );
+        assert_eq!(
+            SetupConnection::decode_prefix(&[]),
+            Err(DecodeStatus::Incomplete(10))
+        );
+
+        // complete but invalid
+        let mut invalid = encoded;
+        invalid[0] = 0xff;
+        assert!(matches!(
+            SetupConnection::decode_prefix(&invalid),
+            Err(DecodeStatus::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        use binary_sv2::Sv2Codec;
+
+        let setup_conn = create_setup_connection();
+        let mut bytes = setup_conn.encode().unwrap();
+        let decoded = SetupConnection::decode(&mut bytes[..]).unwrap();
+        assert_eq!(decoded, setup_conn);
+    }
 }
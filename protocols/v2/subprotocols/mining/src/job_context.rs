@@ -0,0 +1,241 @@
+use crate::{NewExtendedMiningJob, NewMiningJob, SetNewPrevHash, SubmitSharesStandard, Target};
+use core::convert::TryInto;
+
+/// Length in bytes of a serialized bitcoin block header.
+pub const BLOCK_HEADER_LEN: usize = 80;
+
+/// Serializes a bitcoin block header as in the consensus encoding.
+///
+/// `version`, `ntime`, `nbits` and `nonce` are written little-endian. `prev_hash` and
+/// `merkle_root` are copied as they are, so they must be in internal byte order: the same order
+/// used by [`SetNewPrevHash::prev_hash`] and [`NewMiningJob::merkle_root`], which is the reverse of
+/// the hex string shown by block explorers.
+pub fn build_block_header(
+    version: u32,
+    prev_hash: [u8; 32],
+    merkle_root: [u8; 32],
+    ntime: u32,
+    nbits: u32,
+    nonce: u32,
+) -> [u8; BLOCK_HEADER_LEN] {
+    let mut header = [0; BLOCK_HEADER_LEN];
+    header[0..4].copy_from_slice(&version.to_le_bytes());
+    header[4..36].copy_from_slice(&prev_hash);
+    header[36..68].copy_from_slice(&merkle_root);
+    header[68..72].copy_from_slice(&ntime.to_le_bytes());
+    header[72..76].copy_from_slice(&nbits.to_le_bytes());
+    header[76..80].copy_from_slice(&nonce.to_le_bytes());
+    header
+}
+
+/// Everything needed to rebuild the block header a share was mined on.
+///
+/// A validator populates it from the [`NewMiningJob`] (or [`NewExtendedMiningJob`]) and the
+/// [`SetNewPrevHash`] that activated it, then checks every share for that job against it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobContext {
+    /// Identifier of the job the context refers to.
+    pub job_id: u32,
+    /// Previous block hash, as carried by [`SetNewPrevHash::prev_hash`].
+    pub prevhash: [u8; 32],
+    /// Merkle root of the job.
+    ///
+    /// Fixed for standard jobs, for extended jobs it depends on the extranonce used by the miner.
+    pub merkle_root: [u8; 32],
+    /// Version of the job, the share `version` may differ in the rollable bits.
+    pub version: u32,
+    /// Smallest `nTime` a share for this job can carry.
+    pub ntime_base: u32,
+    /// Block header `nBits` field.
+    pub nbits: u32,
+}
+
+impl JobContext {
+    /// Builds the context of a standard job once its prevhash is known.
+    ///
+    /// `ntime_base` is the job `min_ntime` when set, otherwise the one of `prev_hash`.
+    pub fn from_standard_job(job: &NewMiningJob, prev_hash: &SetNewPrevHash) -> Self {
+        // B032 is at most 32 bytes long
+        let mut merkle_root = [0; 32];
+        let inner = job.merkle_root.inner_as_ref();
+        merkle_root[..inner.len()].copy_from_slice(inner);
+        Self {
+            job_id: job.job_id,
+            prevhash: Self::prevhash_from(prev_hash),
+            merkle_root,
+            version: job.version,
+            ntime_base: job
+                .min_ntime
+                .clone()
+                .into_inner()
+                .unwrap_or(prev_hash.min_ntime),
+            nbits: prev_hash.nbits,
+        }
+    }
+
+    /// Builds the context of an extended job once its prevhash is known.
+    ///
+    /// The merkle root of an extended job depends on the coinbase, so it must be computed by the
+    /// caller from the job coinbase prefix/suffix, the extranonce and the merkle path.
+    pub fn from_extended_job(
+        job: &NewExtendedMiningJob,
+        prev_hash: &SetNewPrevHash,
+        merkle_root: [u8; 32],
+    ) -> Self {
+        Self {
+            job_id: job.job_id,
+            prevhash: Self::prevhash_from(prev_hash),
+            merkle_root,
+            version: job.version,
+            ntime_base: job
+                .min_ntime
+                .clone()
+                .into_inner()
+                .unwrap_or(prev_hash.min_ntime),
+            nbits: prev_hash.nbits,
+        }
+    }
+
+    fn prevhash_from(prev_hash: &SetNewPrevHash) -> [u8; 32] {
+        // below unwrap never panics, U256 is always 32 bytes
+        prev_hash.prev_hash.inner_as_ref().try_into().unwrap()
+    }
+
+    /// Serializes the block header for the given `version`, `ntime` and `nonce`, see
+    /// [`build_block_header`].
+    pub fn header(&self, version: u32, ntime: u32, nonce: u32) -> [u8; BLOCK_HEADER_LEN] {
+        build_block_header(
+            version,
+            self.prevhash,
+            self.merkle_root,
+            ntime,
+            self.nbits,
+            nonce,
+        )
+    }
+}
+
+impl SubmitSharesStandard {
+    /// Checks the share against the job described by `ctx`.
+    ///
+    /// Returns `true` if the share refers to the context job, its `ntime` is not below
+    /// [`JobContext::ntime_base`] and the hash of the resulting header is lower than or equal to
+    /// `target`. This crate does not depend on any hashing library so the double SHA256 of the
+    /// header must be provided by `block_hash`, the returned hash is little-endian.
+    pub fn check_target<F>(&self, ctx: &JobContext, target: &Target, block_hash: F) -> bool
+    where
+        F: FnOnce(&[u8; BLOCK_HEADER_LEN]) -> [u8; 32],
+    {
+        if self.job_id != ctx.job_id || self.ntime < ctx.ntime_base {
+            return false;
+        }
+        let header = ctx.header(self.version, self.ntime, self.nonce);
+        let hash: Target = block_hash(&header).into();
+        hash <= *target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use binary_sv2::{Sv2Option, B032, U256};
+    use core::convert::TryFrom;
+
+    fn context() -> JobContext {
+        let job = NewMiningJob {
+            channel_id: 1,
+            job_id: 7,
+            min_ntime: Sv2Option::new(None),
+            version: 0x2000_0000,
+            merkle_root: B032::try_from(vec![0x22; 32]).unwrap(),
+        };
+        let prev_hash = SetNewPrevHash {
+            channel_id: 1,
+            job_id: 7,
+            prev_hash: U256::try_from(vec![0x11; 32]).unwrap(),
+            min_ntime: 1_700_000_000,
+            nbits: 0x1703_4219,
+        };
+        JobContext::from_standard_job(&job, &prev_hash)
+    }
+
+    fn share(job_id: u32, ntime: u32) -> SubmitSharesStandard {
+        SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 0,
+            job_id,
+            nonce: 0xdead_beef,
+            ntime,
+            version: 0x2000_0004,
+        }
+    }
+
+    fn from_display_hex(hex: &str) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        bytes.reverse();
+        bytes
+    }
+
+    #[test]
+    fn test_build_block_header_mainnet_block_100000() {
+        let header = build_block_header(
+            1,
+            from_display_hex("000000000002d01c1fccc21636b607dfd930d31d01c3a62104612a1719011250"),
+            from_display_hex("f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766"),
+            1_293_623_863,
+            0x1b04_864c,
+            274_148_111,
+        );
+        let expected: [u8; BLOCK_HEADER_LEN] = [
+            0x01, 0x00, 0x00, 0x00, 0x50, 0x12, 0x01, 0x19, 0x17, 0x2a, 0x61, 0x04, 0x21, 0xa6,
+            0xc3, 0x01, 0x1d, 0xd3, 0x30, 0xd9, 0xdf, 0x07, 0xb6, 0x36, 0x16, 0xc2, 0xcc, 0x1f,
+            0x1c, 0xd0, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x57, 0xa9, 0x25, 0x2a, 0xac,
+            0xd5, 0xc0, 0xb2, 0x94, 0x09, 0x96, 0xec, 0xff, 0x95, 0x22, 0x28, 0xc3, 0x06, 0x7c,
+            0xc3, 0x8d, 0x48, 0x85, 0xef, 0xb5, 0xa4, 0xac, 0x42, 0x47, 0xe9, 0xf3, 0x37, 0x22,
+            0x1b, 0x4d, 0x4c, 0x86, 0x04, 0x1b, 0x0f, 0x2b, 0x57, 0x10,
+        ];
+        assert_eq!(header, expected);
+    }
+
+    #[test]
+    fn test_job_context_from_messages() {
+        let ctx = context();
+        assert_eq!(ctx.job_id, 7);
+        assert_eq!(ctx.prevhash, [0x11; 32]);
+        assert_eq!(ctx.merkle_root, [0x22; 32]);
+        assert_eq!(ctx.version, 0x2000_0000);
+        assert_eq!(ctx.ntime_base, 1_700_000_000);
+        assert_eq!(ctx.nbits, 0x1703_4219);
+    }
+
+    #[test]
+    fn test_check_target_against_context() {
+        let ctx = context();
+        let share = share(7, 1_700_000_010);
+        let target = Target::new(0, 1);
+
+        let meets = share.check_target(&ctx, &target, |header| {
+            assert_eq!(&header[0..4], &0x2000_0004_u32.to_le_bytes());
+            assert_eq!(&header[4..36], &[0x11; 32]);
+            assert_eq!(&header[36..68], &[0x22; 32]);
+            assert_eq!(&header[68..72], &1_700_000_010_u32.to_le_bytes());
+            assert_eq!(&header[72..76], &0x1703_4219_u32.to_le_bytes());
+            assert_eq!(&header[76..80], &0xdead_beef_u32.to_le_bytes());
+            [0; 32]
+        });
+        assert!(meets);
+        assert!(!share.check_target(&ctx, &target, |_| [0xff; 32]));
+    }
+
+    #[test]
+    fn test_check_target_wrong_job_or_ntime() {
+        let ctx = context();
+        let target = Target::new(u128::MAX, u128::MAX);
+        assert!(!share(8, 1_700_000_010).check_target(&ctx, &target, |_| [0; 32]));
+        assert!(!share(7, 1_699_999_999).check_target(&ctx, &target, |_| [0; 32]));
+    }
+}
@@ -39,6 +39,7 @@ use core::{
 extern crate alloc;
 
 mod close_channel;
+mod job_context;
 mod new_mining_job;
 mod open_channel;
 mod reconnect;
@@ -52,6 +53,7 @@ mod update_channel;
 
 pub use close_channel::CloseChannel;
 use core::ops::Range;
+pub use job_context::{build_block_header, JobContext, BLOCK_HEADER_LEN};
 pub use new_mining_job::{NewExtendedMiningJob, NewMiningJob};
 pub use open_channel::{
     OpenExtendedMiningChannel, OpenExtendedMiningChannelSuccess, OpenMiningChannelError,
@@ -66,7 +68,8 @@ pub use set_group_channel::SetGroupChannel;
 pub use set_new_prev_hash::SetNewPrevHash;
 pub use set_target::SetTarget;
 pub use submit_shares::{
-    SubmitSharesError, SubmitSharesExtended, SubmitSharesStandard, SubmitSharesSuccess,
+    SubmitShareRejectReason, SubmitSharesError, SubmitSharesExtended, SubmitSharesStandard,
+    SubmitSharesSuccess, SUBMIT_SHARES_STANDARD_WIRE_LEN,
 };
 pub use update_channel::{UpdateChannel, UpdateChannelError};
 const MAX_EXTRANONCE_LEN: usize = 32;
@@ -82,6 +85,57 @@ impl Target {
     pub fn new(head: u128, tail: u128) -> Self {
         Self { head, tail }
     }
+
+    /// Decodes the compact representation used by the block header `nBits` field: the highest
+    /// byte is the size in bytes of the target and the lower 3 bytes its most significant bytes.
+    ///
+    /// Bit `0x0080_0000` is a sign bit: negative values, like the ones that do not fit in 256
+    /// bits, are not valid targets and are decoded as zero, which no hash can meet.
+    pub fn from_compact(nbits: u32) -> Self {
+        let size = (nbits >> 24) as usize;
+        let mantissa = nbits & 0x007f_ffff;
+        let negative = mantissa != 0 && nbits & 0x0080_0000 != 0;
+        let overflow = mantissa != 0
+            && (size > 34 || (mantissa > 0xff && size > 33) || (mantissa > 0xffff && size > 32));
+        if negative || overflow {
+            return Self::new(0, 0);
+        }
+        let mut bytes = [0; 32];
+        if size <= 3 {
+            let value = mantissa >> (8 * (3 - size));
+            bytes[0..4].copy_from_slice(&value.to_le_bytes());
+        } else {
+            for (i, byte) in mantissa.to_le_bytes()[0..3].iter().enumerate() {
+                // the overflow check guarantees that the bytes that do not fit are zero
+                if let Some(b) = bytes.get_mut(size - 3 + i) {
+                    *b = *byte;
+                }
+            }
+        }
+        bytes.into()
+    }
+
+    /// Encodes the target in the compact representation of the block header `nBits` field, see
+    /// [`Target::from_compact`]. Precision is lost, only the 3 most significant bytes are kept.
+    pub fn to_compact(&self) -> u32 {
+        let mut bytes = [0; 32];
+        bytes[0..16].copy_from_slice(&self.head.to_le_bytes());
+        bytes[16..32].copy_from_slice(&self.tail.to_le_bytes());
+        let mut size = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        let mut mantissa = if size <= 3 {
+            let mut low = [0; 4];
+            low[0..3].copy_from_slice(&bytes[0..3]);
+            u32::from_le_bytes(low) << (8 * (3 - size))
+        } else {
+            u32::from_le_bytes([bytes[size - 3], bytes[size - 2], bytes[size - 1], 0])
+        };
+        // the mantissa is signed, keep its highest bit clear
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            size += 1;
+        }
+        mantissa | (size as u32) << 24
+    }
 }
 
 impl From<[u8; 32]> for Target {
@@ -912,6 +966,70 @@ pub mod tests {
             }
         }
     }
+    fn target_from_be_hex(hex: &str) -> Target {
+        let mut bytes = [0; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        bytes.reverse();
+        bytes.into()
+    }
+
+    #[test]
+    fn test_target_compact_known_answers() {
+        // difficulty 1
+        let target =
+            target_from_be_hex("00000000ffff0000000000000000000000000000000000000000000000000000");
+        assert_eq!(Target::from_compact(0x1d00_ffff), target);
+        assert_eq!(target.to_compact(), 0x1d00_ffff);
+
+        // block 100000
+        let target =
+            target_from_be_hex("000000000004864c000000000000000000000000000000000000000000000000");
+        assert_eq!(Target::from_compact(0x1b04_864c), target);
+        assert_eq!(target.to_compact(), 0x1b04_864c);
+
+        // block 800000
+        let target =
+            target_from_be_hex("0000000000000000000534190000000000000000000000000000000000000000");
+        assert_eq!(Target::from_compact(0x1705_3419), target);
+        assert_eq!(target.to_compact(), 0x1705_3419);
+    }
+
+    #[test]
+    fn test_target_compact_edge_cases() {
+        // small sizes shift the mantissa right
+        assert_eq!(Target::from_compact(0x0100_3456), Target::new(0, 0));
+        assert_eq!(Target::from_compact(0x0112_3456), Target::new(0x12, 0));
+        assert_eq!(Target::from_compact(0x0212_3456), Target::new(0x1234, 0));
+        assert_eq!(Target::from_compact(0x0312_3456), Target::new(0x12_3456, 0));
+        assert_eq!(
+            Target::from_compact(0x0500_9234),
+            Target::new(0x9234_0000, 0)
+        );
+        assert_eq!(Target::from_compact(0), Target::new(0, 0));
+
+        // the mantissa sign bit is not part of the value
+        assert_eq!(Target::new(0x80, 0).to_compact(), 0x0200_8000);
+        assert_eq!(Target::from_compact(0x0200_8000), Target::new(0x80, 0));
+        assert_eq!(Target::new(0, 0).to_compact(), 0);
+
+        // negative and overflowing values
+        assert_eq!(Target::from_compact(0x0492_3456), Target::new(0, 0));
+        assert_eq!(Target::from_compact(0xff12_3456), Target::new(0, 0));
+        assert_eq!(Target::from_compact(0x2301_0000), Target::new(0, 0));
+        // a zero mantissa is zero whatever the size
+        assert_eq!(Target::from_compact(0xff00_0000), Target::new(0, 0));
+
+        // the largest target keeps its 3 most significant bytes
+        let max = Target::new(u128::MAX, u128::MAX);
+        assert_eq!(max.to_compact(), 0x2100_ffff);
+        assert_eq!(
+            Target::from_compact(0x2100_ffff),
+            Target::new(0, 0xffff << 112)
+        );
+    }
+
     #[quickcheck_macros::quickcheck]
     fn test_target_from_u256(input: (u128, u128)) -> bool {
         let target_expected = Target {
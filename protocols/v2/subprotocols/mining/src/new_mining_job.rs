@@ -161,11 +161,17 @@ impl<'d> GetSize for NewMiningJob<'d> {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(not(feature = "with_serde"))]
     use super::*;
+    #[cfg(not(feature = "with_serde"))]
     use crate::tests::from_arbitrary_vec_to_array;
+    #[cfg(not(feature = "with_serde"))]
     use core::convert::TryFrom;
+    #[cfg(not(feature = "with_serde"))]
     use quickcheck_macros;
 
+    // as_static panics with serde, see the impls below
+    #[cfg(not(feature = "with_serde"))]
     #[quickcheck_macros::quickcheck]
     fn test_new_extended_mining_job(
         channel_id: u32,
@@ -201,6 +207,7 @@ mod tests {
             && static_nmj.coinbase_tx_suffix == coinbase_tx_suffix
     }
 
+    #[cfg(not(feature = "with_serde"))]
     #[quickcheck_macros::quickcheck]
     fn test_new_mining_job(
         channel_id: u32,
@@ -226,6 +233,7 @@ mod tests {
             && static_nmj.merkle_root == nmj.merkle_root
     }
 
+    #[cfg(not(feature = "with_serde"))]
     pub mod helpers {
         use super::*;
 
@@ -1,10 +1,15 @@
+use alloc::string::String;
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
 use binary_sv2::binary_codec_sv2;
 use binary_sv2::{Deserialize, Serialize, Str0255, B032};
-#[cfg(not(feature = "with_serde"))]
 use core::convert::TryInto;
+use core::fmt::{self, Write};
+
+/// Length in bytes of a serialized [`SubmitSharesStandard`]: the message only has fixed size
+/// fields, so it is the same for every share.
+pub const SUBMIT_SHARES_STANDARD_WIRE_LEN: usize = 24;
 
 /// Message used by downstream to send result of its hashing work to an upstream.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,13 +35,33 @@ pub struct SubmitSharesStandard {
     pub version: u32,
 }
 
+#[cfg(feature = "tracing")]
+impl SubmitSharesStandard {
+    /// Identifiers that correlate a share across logs: `(channel_id, sequence_number, job_id)`.
+    pub fn log_fields(&self) -> (u32, u32, u32) {
+        (self.channel_id, self.sequence_number, self.job_id)
+    }
+
+    /// Span carrying the [`SubmitSharesStandard::log_fields`], to be entered while handling the
+    /// share or used as `parent` of an `#[instrument]`ed handler.
+    pub fn span(&self) -> tracing::Span {
+        let (channel_id, sequence_number, job_id) = self.log_fields();
+        tracing::info_span!(
+            "submit_shares_standard",
+            channel_id,
+            sequence_number,
+            job_id
+        )
+    }
+}
+
 /// Message used by downstream to send result of its hashing work to an upstream.
 ///
 /// The message is the same as [`SubmitShares`], but with an additional field,
 /// [`SubmitSharesExtended::extranonce`].
 ///
 /// Only relevant for Extended Channels.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SubmitSharesExtended<'decoder> {
     /// Channel identification.
     pub channel_id: u32,
@@ -67,6 +92,54 @@ pub struct SubmitSharesExtended<'decoder> {
     pub extranonce: B032<'decoder>,
 }
 
+impl<'decoder> SubmitSharesExtended<'decoder> {
+    /// Returns [`SubmitSharesExtended::extranonce`] as a lowercase hex string, the way Bitcoin
+    /// tooling displays extranonces.
+    pub fn extranonce_hex(&self) -> String {
+        let extranonce = self.extranonce.inner_as_ref();
+        let mut hex = String::with_capacity(extranonce.len() * 2);
+        for byte in extranonce {
+            // writing to a String never fails
+            let _ = write!(hex, "{:02x}", byte);
+        }
+        hex
+    }
+
+    /// Builds the full coinbase transaction of the share: `prefix + extranonce_prefix +
+    /// extranonce + suffix`, where `prefix` and `suffix` are the `coinbase_tx_prefix` and
+    /// `coinbase_tx_suffix` of the job and `extranonce_prefix` the one assigned to the channel.
+    pub fn reconstruct_coinbase(
+        &self,
+        prefix: &[u8],
+        extranonce_prefix: &[u8],
+        suffix: &[u8],
+    ) -> alloc::vec::Vec<u8> {
+        let extranonce = self.extranonce.inner_as_ref();
+        let mut coinbase = alloc::vec::Vec::with_capacity(
+            prefix.len() + extranonce_prefix.len() + extranonce.len() + suffix.len(),
+        );
+        coinbase.extend_from_slice(prefix);
+        coinbase.extend_from_slice(extranonce_prefix);
+        coinbase.extend_from_slice(extranonce);
+        coinbase.extend_from_slice(suffix);
+        coinbase
+    }
+}
+
+impl<'decoder> fmt::Debug for SubmitSharesExtended<'decoder> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubmitSharesExtended")
+            .field("channel_id", &self.channel_id)
+            .field("sequence_number", &self.sequence_number)
+            .field("job_id", &self.job_id)
+            .field("nonce", &self.nonce)
+            .field("ntime", &self.ntime)
+            .field("version", &self.version)
+            .field("extranonce", &self.extranonce_hex())
+            .finish()
+    }
+}
+
 /// Message used by upstream to accept [`SubmitSharesStandard`] or [`SubmitSharesExtended`].
 ///
 /// Because it is a common case that shares submission is successful, this response can be provided
@@ -123,7 +196,89 @@ impl<'a> SubmitSharesError<'a> {
     pub fn invalid_job_id_error_code() -> &'static str {
         "invalid-job-id"
     }
+
+    /// The reason of the rejection, `None` if the error code is not one defined by the spec.
+    pub fn reject_reason(&self) -> Option<SubmitShareRejectReason> {
+        core::str::from_utf8(self.error_code.as_ref())
+            .ok()
+            .and_then(SubmitShareRejectReason::from_error_code)
+    }
+}
+
+impl<'decoder> fmt::Display for SubmitSharesError<'decoder> {
+    /// One line summary for logs, e.g. `share rejected on channel 1 seq 2: stale-share`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "share rejected on channel {} seq {}: ",
+            self.channel_id, self.sequence_number
+        )?;
+        match core::str::from_utf8(self.error_code.as_ref()) {
+            Ok(error_code) => match SubmitShareRejectReason::from_error_code(error_code) {
+                Some(reason) => write!(f, "{}", reason),
+                None => write!(f, "unknown error code {:?}", error_code),
+            },
+            Err(_) => write!(f, "invalid error code {:?}", self.error_code.as_ref()),
+        }
+    }
+}
+
+/// Reason why a share has been rejected, one for each error code of [`SubmitSharesError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitShareRejectReason {
+    InvalidChannelId,
+    StaleShare,
+    DifficultyTooLow,
+    InvalidJobId,
+}
+
+impl SubmitShareRejectReason {
+    const ALL: [Self; 4] = [
+        Self::InvalidChannelId,
+        Self::StaleShare,
+        Self::DifficultyTooLow,
+        Self::InvalidJobId,
+    ];
+
+    /// Returns the error code sent to the downstream in [`SubmitSharesError::error_code`].
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::InvalidChannelId => SubmitSharesError::invalid_channel_error_code(),
+            Self::StaleShare => SubmitSharesError::stale_share_error_code(),
+            Self::DifficultyTooLow => SubmitSharesError::difficulty_too_low_error_code(),
+            Self::InvalidJobId => SubmitSharesError::invalid_job_id_error_code(),
+        }
+    }
+
+    /// Parses an error code received in [`SubmitSharesError::error_code`], `None` if it is not
+    /// one of the codes defined by the spec.
+    pub fn from_error_code(error_code: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|reason| reason.error_code() == error_code)
+    }
+
+    /// Builds the [`SubmitSharesError`] to send downstream for the rejected `submit`.
+    pub fn to_submit_shares_error(
+        &self,
+        submit: &SubmitSharesStandard,
+    ) -> SubmitSharesError<'static> {
+        SubmitSharesError {
+            channel_id: submit.channel_id,
+            sequence_number: submit.sequence_number,
+            // Infallible unwrap error codes are static strings shorter than 255 bytes
+            error_code: self.error_code().as_bytes().to_vec().try_into().unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for SubmitShareRejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.error_code())
+    }
 }
+
 #[cfg(feature = "with_serde")]
 use binary_sv2::GetSize;
 #[cfg(feature = "with_serde")]
@@ -164,13 +319,19 @@ impl<'d> GetSize for SubmitSharesError<'d> {
         self.channel_id.get_size() + self.sequence_number.get_size() + self.error_code.get_size()
     }
 }
+// without serde `into_static` and `as_static` are derived
 #[cfg(feature = "with_serde")]
 impl<'a> SubmitSharesError<'a> {
+    /// Copies the error code into owned storage so the error can outlive the decoding buffer.
     pub fn into_static(self) -> SubmitSharesError<'static> {
-        panic!("This function shouldn't be called by the Message Generator");
+        SubmitSharesError {
+            channel_id: self.channel_id,
+            sequence_number: self.sequence_number,
+            error_code: self.error_code.into_static(),
+        }
     }
     pub fn as_static(&self) -> SubmitSharesError<'static> {
-        panic!("This function shouldn't be called by the Message Generator");
+        self.clone().into_static()
     }
 }
 #[cfg(feature = "with_serde")]
@@ -182,3 +343,204 @@ impl<'a> SubmitSharesExtended<'a> {
         panic!("This function shouldn't be called by the Message Generator");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::{TryFrom, TryInto};
+
+    #[test]
+    fn test_submit_shares_standard_wire_len() {
+        use binary_sv2::GetSize;
+
+        let share = SubmitSharesStandard {
+            channel_id: u32::MAX,
+            sequence_number: 1,
+            job_id: 2,
+            nonce: 3,
+            ntime: 4,
+            version: 5,
+        };
+        assert_eq!(share.get_size(), SUBMIT_SHARES_STANDARD_WIRE_LEN);
+    }
+
+    #[test]
+    fn test_extranonce_hex() {
+        let extranonce = B032::try_from(vec![0xde, 0xad, 0xbe, 0xef, 0x00, 0x01, 0x02, 0x0a])
+            .expect("8 bytes always fit in a B032");
+        let share = SubmitSharesExtended {
+            channel_id: 1,
+            sequence_number: 2,
+            job_id: 3,
+            nonce: 4,
+            ntime: 5,
+            version: 6,
+            extranonce,
+        };
+        assert_eq!(share.extranonce_hex(), "deadbeef0001020a");
+        assert!(format!("{:?}", share).contains("extranonce: \"deadbeef0001020a\""));
+    }
+
+    #[test]
+    fn test_submit_shares_error_display() {
+        let error = SubmitSharesError {
+            channel_id: 1,
+            sequence_number: 42,
+            error_code: SubmitSharesError::stale_share_error_code()
+                .as_bytes()
+                .to_vec()
+                .try_into()
+                .unwrap(),
+        };
+        assert_eq!(
+            error.reject_reason(),
+            Some(SubmitShareRejectReason::StaleShare)
+        );
+        assert_eq!(
+            format!("{}", error),
+            "share rejected on channel 1 seq 42: stale-share"
+        );
+
+        let unknown = SubmitSharesError {
+            error_code: "duplicate-share".as_bytes().to_vec().try_into().unwrap(),
+            ..error.clone()
+        };
+        assert_eq!(unknown.reject_reason(), None);
+        assert_eq!(
+            format!("{}", unknown),
+            "share rejected on channel 1 seq 42: unknown error code \"duplicate-share\""
+        );
+
+        let invalid = SubmitSharesError {
+            error_code: vec![b'o', b'k', 0xff].try_into().unwrap(),
+            ..error
+        };
+        assert_eq!(
+            format!("{}", invalid),
+            "share rejected on channel 1 seq 42: invalid error code [111, 107, 255]"
+        );
+    }
+
+    #[test]
+    fn test_reject_reason_error_code_round_trip() {
+        for reason in SubmitShareRejectReason::ALL.iter() {
+            assert_eq!(
+                SubmitShareRejectReason::from_error_code(reason.error_code()),
+                Some(*reason)
+            );
+        }
+        assert_eq!(
+            SubmitShareRejectReason::from_error_code("duplicate-share"),
+            None
+        );
+    }
+
+    // runs against the derived `into_static` by default and against the one above with serde
+    #[test]
+    fn test_submit_shares_error_into_static() {
+        let detached: SubmitSharesError<'static> = {
+            let mut encoded = vec![1, 0, 0, 0, 42, 0, 0, 0, 11];
+            encoded.extend_from_slice(b"stale-share");
+            let error: SubmitSharesError = binary_sv2::from_bytes(&mut encoded).unwrap();
+            let detached = error.into_static();
+            drop(encoded);
+            detached
+        };
+        assert_eq!(detached.channel_id, 1);
+        assert_eq!(detached.sequence_number, 42);
+        assert_eq!(detached.error_code.as_ref(), b"stale-share");
+        assert_eq!(detached.as_static().error_code.as_ref(), b"stale-share");
+    }
+
+    #[test]
+    fn test_reconstruct_coinbase() {
+        let extranonce = B032::try_from(vec![0xee; 8]).expect("8 bytes always fit in a B032");
+        let share = SubmitSharesExtended {
+            channel_id: 1,
+            sequence_number: 2,
+            job_id: 3,
+            nonce: 4,
+            ntime: 5,
+            version: 6,
+            extranonce,
+        };
+        let prefix = [0x01, 0x00, 0x00, 0x00, 0x01];
+        let extranonce_prefix = [0xaa, 0xbb, 0xcc, 0xdd];
+        let suffix = [0xff, 0xff, 0xff, 0xff, 0x00];
+
+        let coinbase = share.reconstruct_coinbase(&prefix, &extranonce_prefix, &suffix);
+
+        let mut expected = vec![0x01, 0x00, 0x00, 0x00, 0x01, 0xaa, 0xbb, 0xcc, 0xdd];
+        expected.extend_from_slice(&[0xee; 8]);
+        expected.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x00]);
+        assert_eq!(coinbase, expected);
+        // the extranonce follows the prefix and the extranonce prefix
+        let offset = prefix.len() + extranonce_prefix.len();
+        assert_eq!(&coinbase[offset..offset + 8], &[0xee; 8]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_span_fields() {
+        extern crate std;
+        use alloc::{string::ToString, sync::Arc, vec::Vec};
+        use std::sync::Mutex;
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// Subscriber recording the `u64` fields of every new span.
+        #[derive(Clone, Default)]
+        struct FieldRecorder(Arc<Mutex<Vec<(String, u64)>>>);
+
+        impl Visit for FieldRecorder {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((field.name().to_string(), value));
+            }
+
+            fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+        }
+
+        impl Subscriber for FieldRecorder {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, span: &span::Attributes) -> span::Id {
+                span.record(&mut self.clone());
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &Event) {}
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let share = SubmitSharesStandard {
+            channel_id: 1,
+            sequence_number: 2,
+            job_id: 3,
+            nonce: 4,
+            ntime: 5,
+            version: 6,
+        };
+        assert_eq!(share.log_fields(), (1, 2, 3));
+
+        // the global default is the only dispatcher available without tracing's std feature
+        let recorder = FieldRecorder::default();
+        tracing::dispatcher::set_global_default(tracing::Dispatch::new(recorder.clone())).unwrap();
+        let _span = share.span();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                ("channel_id".to_string(), 1),
+                ("sequence_number".to_string(), 2),
+                ("job_id".to_string(), 3),
+            ]
+        );
+    }
+}
@@ -41,7 +41,8 @@ pub use new_template::NewTemplate;
 #[cfg(not(feature = "with_serde"))]
 pub use request_transaction_data::{CRequestTransactionDataError, CRequestTransactionDataSuccess};
 pub use request_transaction_data::{
-    RequestTransactionData, RequestTransactionDataError, RequestTransactionDataSuccess,
+    RequestTransactionData, RequestTransactionDataError, RequestTransactionDataErrorCode,
+    RequestTransactionDataSuccess,
 };
 #[cfg(not(feature = "with_serde"))]
 pub use set_new_prev_hash::CSetNewPrevHash;
@@ -1,12 +1,11 @@
+use alloc::{format, string::String, vec::Vec};
 #[cfg(not(feature = "with_serde"))]
-use alloc::vec::Vec;
-#[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2::{self, free_vec, free_vec_2, CVec, CVec2};
+use binary_sv2::binary_codec_sv2::{self, free_vec, free_vec_2, CVec, CVec2, CVecPool};
 #[cfg(not(feature = "with_serde"))]
 use binary_sv2::Error;
 use binary_sv2::{Deserialize, Seq064K, Serialize, Str0255, B016M, B064K};
 #[cfg(not(feature = "with_serde"))]
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
 
 /// Message used by a downstream to request data about all transactions in a block template.
 ///
@@ -22,6 +21,20 @@ pub struct RequestTransactionData {
     pub template_id: u64,
 }
 
+#[cfg(feature = "tracing")]
+impl RequestTransactionData {
+    /// Identifier that correlates the request across logs: the `template_id`.
+    pub fn log_fields(&self) -> u64 {
+        self.template_id
+    }
+
+    /// Span carrying the [`RequestTransactionData::log_fields`], to be entered while handling the
+    /// request or used as `parent` of an `#[instrument]`ed handler.
+    pub fn span(&self) -> tracing::Span {
+        tracing::info_span!("request_transaction_data", template_id = self.log_fields())
+    }
+}
+
 /// Message used by an upstream(Template Provider) to respond successfully to a
 /// [`RequestTransactionData`] message.
 ///
@@ -67,6 +80,107 @@ pub struct RequestTransactionDataSuccess<'decoder> {
     pub transaction_list: Seq064K<'decoder, B016M<'decoder>>,
 }
 
+/// Maximum number of elements a [`Seq064K`] can hold.
+#[cfg(not(feature = "with_serde"))]
+const SEQ064K_MAX_LEN: usize = u16::MAX as usize;
+
+#[cfg(not(feature = "with_serde"))]
+impl<'a> RequestTransactionDataSuccess<'a> {
+    /// Iterates over the transactions, borrowing each one from
+    /// [`RequestTransactionDataSuccess::transaction_list`] without allocating, e.g. to stream them
+    /// to a socket.
+    pub fn transaction_slices(&self) -> impl Iterator<Item = &[u8]> {
+        self.transaction_list.iter().map(|tx| tx.inner_as_ref())
+    }
+
+    /// Encodes [`RequestTransactionDataSuccess::transaction_list`] as a single blob:
+    /// `varint(count) || (varint(len) || tx)*`, where `varint` is the bitcoin CompactSize.
+    ///
+    /// This is not a wire format, it is a convenience for downstreams that prefer one buffer to a
+    /// list of small ones. [`RequestTransactionDataSuccess::transactions_from_concatenated`]
+    /// parses it back.
+    pub fn transactions_concatenated(&self) -> Vec<u8> {
+        let len: usize = self.transaction_slices().map(|tx| 9 + tx.len()).sum();
+        let mut blob = Vec::with_capacity(9 + len);
+        write_compact_size(&mut blob, self.transaction_list.iter().len() as u64);
+        for tx in self.transaction_slices() {
+            write_compact_size(&mut blob, tx.len() as u64);
+            blob.extend_from_slice(tx);
+        }
+        blob
+    }
+
+    /// Parses a blob built by [`RequestTransactionDataSuccess::transactions_concatenated`] into a
+    /// transaction list.
+    ///
+    /// Returns [`Error::OutOfBound`] if the blob is truncated and [`Error::ReadError`] (bytes
+    /// used, blob length) if it has trailing bytes.
+    pub fn transactions_from_concatenated(
+        blob: &[u8],
+    ) -> Result<Seq064K<'static, B016M<'static>>, Error> {
+        let mut cursor = blob;
+        let count = read_compact_size(&mut cursor)?;
+        if count > SEQ064K_MAX_LEN as u64 {
+            return Err(Error::SeqExceedsMaxSize);
+        }
+        // Do not trust `count` for the allocation, every transaction takes at least one byte.
+        let mut transactions = Vec::with_capacity((count as usize).min(cursor.len()));
+        for _ in 0..count {
+            let len = read_compact_size(&mut cursor)?;
+            if len > cursor.len() as u64 {
+                return Err(Error::OutOfBound);
+            }
+            let (tx, rest) = cursor.split_at(len as usize);
+            transactions.push(B016M::try_from(tx.to_vec())?);
+            cursor = rest;
+        }
+        if !cursor.is_empty() {
+            return Err(Error::ReadError(blob.len() - cursor.len(), blob.len()));
+        }
+        Seq064K::new(transactions)
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+#[cfg(not(feature = "with_serde"))]
+fn read_compact_size(cursor: &mut &[u8]) -> Result<u64, Error> {
+    let (prefix, rest) = cursor.split_first().ok_or(Error::OutOfBound)?;
+    let len = match prefix {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        n => {
+            *cursor = rest;
+            return Ok(*n as u64);
+        }
+    };
+    if rest.len() < len {
+        return Err(Error::OutOfBound);
+    }
+    let mut bytes = [0; 8];
+    bytes[..len].copy_from_slice(&rest[..len]);
+    *cursor = &rest[len..];
+    Ok(u64::from_le_bytes(bytes))
+}
+
 /// C representation of [`RequestTransactionDataSuccess`].
 #[repr(C)]
 #[cfg(not(feature = "with_serde"))]
@@ -84,7 +198,12 @@ impl<'a> CRequestTransactionDataSuccess {
     pub fn to_rust_rep_mut(&'a mut self) -> Result<RequestTransactionDataSuccess<'a>, Error> {
         let excess_data: B064K = self.excess_data.as_mut_slice().try_into()?;
         let transaction_list_ = self.transaction_list.as_mut_slice();
-        let mut transaction_list: Vec<B016M> = Vec::new();
+        // Reject oversized lists before converting every element, `Seq064K::new` would only catch
+        // them once the whole vector has been built.
+        if transaction_list_.len() > SEQ064K_MAX_LEN {
+            return Err(Error::SeqExceedsMaxSize);
+        }
+        let mut transaction_list: Vec<B016M> = Vec::with_capacity(transaction_list_.len());
         for cvec in transaction_list_ {
             transaction_list.push(cvec.as_mut_slice().try_into()?);
         }
@@ -95,6 +214,22 @@ impl<'a> CRequestTransactionDataSuccess {
             transaction_list,
         })
     }
+
+    /// Like `From<RequestTransactionDataSuccess>` but copies the excess data and the transactions
+    /// in buffers taken from `pool`.
+    pub fn from_pooled(v: &RequestTransactionDataSuccess, pool: &mut CVecPool) -> Self {
+        Self {
+            template_id: v.template_id,
+            excess_data: pool.cvec_from_slice(v.excess_data.inner_as_ref()),
+            transaction_list: pool.cvec2_from_seq064k(&v.transaction_list),
+        }
+    }
+
+    /// Gives the buffers back to `pool` instead of freeing them.
+    pub fn recycle(mut self, pool: &mut CVecPool) {
+        pool.release(&mut self.excess_data);
+        pool.release_2(&mut self.transaction_list);
+    }
 }
 
 /// Drops the CRequestTransactionDataSuccess object.
@@ -137,6 +272,58 @@ pub struct RequestTransactionDataError<'decoder> {
     pub error_code: Str0255<'decoder>,
 }
 
+impl<'decoder> RequestTransactionDataError<'decoder> {
+    /// Parses [`RequestTransactionDataError::error_code`].
+    pub fn code(&self) -> RequestTransactionDataErrorCode {
+        self.error_code.as_ref().into()
+    }
+
+    /// One line description of the error for operators, e.g. `tx data unavailable for template
+    /// 42: template-id-not-found`.
+    pub fn describe(&self) -> String {
+        match self.code() {
+            RequestTransactionDataErrorCode::Unknown(code) => format!(
+                "tx data unavailable for template {}: unknown error code {:?}",
+                self.template_id,
+                String::from_utf8_lossy(&code)
+            ),
+            code => format!(
+                "tx data unavailable for template {}: {}",
+                self.template_id,
+                String::from_utf8_lossy(code.as_bytes())
+            ),
+        }
+    }
+}
+
+/// Error codes of [`RequestTransactionDataError`] defined by the specification.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RequestTransactionDataErrorCode {
+    /// `template-id-not-found`
+    TemplateIdNotFound,
+    /// Any error code not defined by the specification, kept verbatim.
+    Unknown(Vec<u8>),
+}
+
+impl RequestTransactionDataErrorCode {
+    /// Returns the error code as sent on the wire.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            RequestTransactionDataErrorCode::TemplateIdNotFound => b"template-id-not-found",
+            RequestTransactionDataErrorCode::Unknown(code) => code,
+        }
+    }
+}
+
+impl From<&[u8]> for RequestTransactionDataErrorCode {
+    fn from(code: &[u8]) -> Self {
+        match code {
+            b"template-id-not-found" => RequestTransactionDataErrorCode::TemplateIdNotFound,
+            _ => RequestTransactionDataErrorCode::Unknown(code.to_vec()),
+        }
+    }
+}
+
 /// C representation of [`RequestTransactionDataError`].
 #[repr(C)]
 #[cfg(not(feature = "with_serde"))]
@@ -191,6 +378,22 @@ impl<'d> GetSize for RequestTransactionDataSuccess<'d> {
         self.template_id.get_size() + self.excess_data.get_size() + self.transaction_list.get_size()
     }
 }
+// without serde `into_static` and `as_static` are derived
+#[cfg(feature = "with_serde")]
+impl<'a> RequestTransactionDataSuccess<'a> {
+    /// Copies the excess data and every transaction into owned storage so the message can outlive
+    /// the decoding buffer.
+    pub fn into_static(self) -> RequestTransactionDataSuccess<'static> {
+        RequestTransactionDataSuccess {
+            template_id: self.template_id,
+            excess_data: self.excess_data.into_static(),
+            transaction_list: self.transaction_list.into_static(),
+        }
+    }
+    pub fn as_static(&self) -> RequestTransactionDataSuccess<'static> {
+        self.clone().into_static()
+    }
+}
 #[cfg(feature = "with_serde")]
 impl<'d> GetSize for RequestTransactionDataError<'d> {
     fn get_size(&self) -> usize {
@@ -203,3 +406,213 @@ impl GetSize for RequestTransactionData {
         self.template_id.get_size()
     }
 }
+
+#[cfg(test)]
+#[cfg(not(feature = "with_serde"))]
+mod tests {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    #[test]
+    fn test_error_describe() {
+        let error = RequestTransactionDataError {
+            template_id: 42,
+            error_code: "template-id-not-found"
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+        };
+        assert_eq!(
+            error.code(),
+            RequestTransactionDataErrorCode::TemplateIdNotFound
+        );
+        assert_eq!(
+            error.describe(),
+            "tx data unavailable for template 42: template-id-not-found"
+        );
+
+        let error = RequestTransactionDataError {
+            template_id: 7,
+            error_code: "node-is-syncing"
+                .to_string()
+                .into_bytes()
+                .try_into()
+                .unwrap(),
+        };
+        assert_eq!(
+            error.describe(),
+            "tx data unavailable for template 7: unknown error code \"node-is-syncing\""
+        );
+    }
+
+    #[test]
+    fn test_oversized_transaction_list_is_rejected() {
+        let tx: B016M = vec![0x01].try_into().unwrap();
+        // `From<Vec<T>>` does not check the length, so an oversized sequence can reach the C side.
+        let transaction_list: Seq064K<B016M> = vec![tx; SEQ064K_MAX_LEN + 1].into();
+        let excess_data: B064K = vec![].try_into().unwrap();
+        let mut c_message = CRequestTransactionDataSuccess {
+            template_id: 1,
+            excess_data: excess_data.into(),
+            transaction_list: transaction_list.into(),
+        };
+        match c_message.to_rust_rep_mut() {
+            Err(Error::SeqExceedsMaxSize) => (),
+            other => panic!("expected SeqExceedsMaxSize, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_into_static_outlives_decoded_buffer() {
+        let tx: B016M = vec![0x01, 0x02, 0x03].try_into().unwrap();
+        let message = RequestTransactionDataSuccess {
+            template_id: 1,
+            excess_data: vec![0x04].try_into().unwrap(),
+            transaction_list: Seq064K::new(vec![tx]).unwrap(),
+        };
+        let mut encoded = binary_sv2::to_bytes(message).unwrap();
+
+        let decoded: RequestTransactionDataSuccess = binary_sv2::from_bytes(&mut encoded).unwrap();
+        let owned: RequestTransactionDataSuccess<'static> = decoded.into_static();
+        drop(encoded);
+
+        assert_eq!(owned.excess_data.inner_as_ref(), &[0x04]);
+        let transactions = owned.transaction_list.into_inner();
+        assert_eq!(transactions[0].inner_as_ref(), &[0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn test_transactions_concatenated_round_trip() {
+        let transactions: Vec<B016M> = vec![vec![0xaa], vec![], vec![0xbb; 300]]
+            .into_iter()
+            .map(|tx| tx.try_into().unwrap())
+            .collect();
+        let message = RequestTransactionDataSuccess {
+            template_id: 1,
+            excess_data: vec![].try_into().unwrap(),
+            transaction_list: Seq064K::new(transactions).unwrap(),
+        };
+
+        let blob = message.transactions_concatenated();
+        assert_eq!(&blob[..5], &[0x03, 0x01, 0xaa, 0x00, 0xfd]);
+        assert_eq!(&blob[5..7], &300_u16.to_le_bytes());
+        assert_eq!(blob.len(), 7 + 300);
+
+        let parsed = RequestTransactionDataSuccess::transactions_from_concatenated(&blob).unwrap();
+        assert_eq!(parsed, message.transaction_list);
+
+        assert!(matches!(
+            RequestTransactionDataSuccess::transactions_from_concatenated(&blob[..blob.len() - 1]),
+            Err(Error::OutOfBound)
+        ));
+        let mut trailing = blob.clone();
+        trailing.push(0);
+        assert!(matches!(
+            RequestTransactionDataSuccess::transactions_from_concatenated(&trailing),
+            Err(Error::ReadError(307, 308))
+        ));
+    }
+
+    #[test]
+    fn test_transaction_list_round_trip() {
+        let tx: B016M = vec![0x01, 0x02].try_into().unwrap();
+        let message = RequestTransactionDataSuccess {
+            template_id: 1,
+            excess_data: vec![0x03].try_into().unwrap(),
+            transaction_list: Seq064K::new(vec![tx.clone(), tx]).unwrap(),
+        };
+        let mut c_message: CRequestTransactionDataSuccess = message.clone().into();
+        assert_eq!(c_message.to_rust_rep_mut().unwrap(), message);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "with_serde")]
+mod serde_tests {
+    use super::*;
+    use alloc::vec;
+    use core::convert::TryInto;
+
+    #[test]
+    fn test_into_static_outlives_decoded_buffer() {
+        let tx: B016M = vec![0x01, 0x02, 0x03].try_into().unwrap();
+        let message = RequestTransactionDataSuccess {
+            template_id: 1,
+            excess_data: vec![0x04].try_into().unwrap(),
+            transaction_list: Seq064K::new(vec![tx, vec![0x05].try_into().unwrap()]).unwrap(),
+        };
+        let expected = binary_sv2::to_bytes(&message).unwrap();
+        let mut encoded = expected.clone();
+
+        let decoded: RequestTransactionDataSuccess = binary_sv2::from_bytes(&mut encoded).unwrap();
+        let copy = decoded.as_static();
+        let owned: RequestTransactionDataSuccess<'static> = decoded.into_static();
+        drop(encoded);
+
+        // the serde byte arrays can not all be read back, compare the encodings instead
+        for message in [owned, copy] {
+            assert_eq!(message.excess_data.to_vec(), vec![0x04]);
+            assert_eq!(binary_sv2::to_bytes(&message).unwrap(), expected);
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "tracing")]
+mod tracing_tests {
+    extern crate std;
+    use super::*;
+    use alloc::{string::ToString, sync::Arc, vec};
+    use core::fmt;
+    use std::sync::Mutex;
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    /// Subscriber recording the `u64` fields of every new span.
+    #[derive(Clone, Default)]
+    struct FieldRecorder(Arc<Mutex<Vec<(String, u64)>>>);
+
+    impl Visit for FieldRecorder {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((field.name().to_string(), value));
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+    }
+
+    impl Subscriber for FieldRecorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn new_span(&self, span: &span::Attributes) -> span::Id {
+            span.record(&mut self.clone());
+            span::Id::from_u64(1)
+        }
+        fn record(&self, _: &span::Id, _: &span::Record) {}
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, _: &Event) {}
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn test_span_fields() {
+        let request = RequestTransactionData { template_id: 42 };
+        assert_eq!(request.log_fields(), 42);
+
+        // the global default is the only dispatcher available without tracing's std feature
+        let recorder = FieldRecorder::default();
+        tracing::dispatcher::set_global_default(tracing::Dispatch::new(recorder.clone())).unwrap();
+        let _span = request.span();
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![("template_id".to_string(), 42)]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 #[cfg(not(feature = "with_serde"))]
 use alloc::vec::Vec;
 #[cfg(not(feature = "with_serde"))]
-use binary_sv2::binary_codec_sv2::{self, free_vec, CVec};
+use binary_sv2::binary_codec_sv2::{self, free_vec, CVec, CVecPool};
 #[cfg(not(feature = "with_serde"))]
 use binary_sv2::Error;
 use binary_sv2::{Deserialize, Serialize, B064K};
@@ -43,6 +43,65 @@ pub struct SubmitSolution<'decoder> {
     pub coinbase_tx: B064K<'decoder>,
 }
 
+impl<'decoder> SubmitSolution<'decoder> {
+    /// Checks whether [`SubmitSolution::coinbase_tx`] is exactly `prefix + extranonce + suffix`.
+    ///
+    /// Used by an upstream to verify that the solution coinbase is the one built from the job it
+    /// issued and the extranonce assigned to the downstream.
+    pub fn coinbase_matches(&self, prefix: &[u8], extranonce: &[u8], suffix: &[u8]) -> bool {
+        let coinbase = self.coinbase_tx.inner_as_ref();
+        coinbase.len() == prefix.len() + extranonce.len() + suffix.len()
+            && coinbase.starts_with(prefix)
+            && coinbase[prefix.len()..].starts_with(extranonce)
+            && coinbase.ends_with(suffix)
+    }
+
+    /// Recombines the bits of `job_version` outside `rolling_mask` with the rolled bits of
+    /// [`SubmitSolution::version`].
+    ///
+    /// `rolling_mask` is the set of bits the downstream is allowed to roll, usually the BIP320
+    /// general purpose bits `0x1fffe000`.
+    pub fn normalized_version(&self, job_version: u32, rolling_mask: u32) -> u32 {
+        (job_version & !rolling_mask) | (self.version & rolling_mask)
+    }
+
+    /// Checks whether [`SubmitSolution::version`] differs from `job_version` in any bit outside
+    /// `rolling_mask`, in which case the solution must be rejected.
+    pub fn has_illegal_version_bits(&self, job_version: u32, rolling_mask: u32) -> bool {
+        (self.version ^ job_version) & !rolling_mask != 0
+    }
+}
+
+/// Maximum length in bytes of [`SubmitSolution::coinbase_tx`].
+#[cfg(not(feature = "with_serde"))]
+const MAX_COINBASE_TX_LEN: usize = u16::MAX as usize;
+
+#[cfg(not(feature = "with_serde"))]
+impl SubmitSolution<'static> {
+    /// Builds a [`SubmitSolution`] from a raw serialized coinbase transaction.
+    ///
+    /// Returns [`Error::InvalidB064KSize`] with the coinbase length if it does not fit in a
+    /// `B064K`.
+    pub fn new(
+        template_id: u64,
+        version: u32,
+        header_timestamp: u32,
+        header_nonce: u32,
+        coinbase: Vec<u8>,
+    ) -> Result<Self, Error> {
+        if coinbase.len() > MAX_COINBASE_TX_LEN {
+            return Err(Error::InvalidB064KSize(coinbase.len()));
+        }
+        Ok(Self {
+            template_id,
+            version,
+            header_timestamp,
+            header_nonce,
+            coinbase_tx: coinbase.try_into()?,
+        })
+    }
+}
+
 /// C representation of [`SubmitSolution`].
 #[cfg(not(feature = "with_serde"))]
 #[repr(C)]
@@ -70,6 +129,22 @@ impl<'a> CSubmitSolution {
             coinbase_tx,
         })
     }
+
+    /// Like `From<SubmitSolution>` but copies the coinbase in a buffer taken from `pool`.
+    pub fn from_pooled(v: &SubmitSolution, pool: &mut CVecPool) -> Self {
+        Self {
+            template_id: v.template_id,
+            version: v.version,
+            header_timestamp: v.header_timestamp,
+            header_nonce: v.header_nonce,
+            coinbase_tx: pool.cvec_from_slice(v.coinbase_tx.inner_as_ref()),
+        }
+    }
+
+    /// Gives the coinbase buffer back to `pool` instead of freeing it.
+    pub fn recycle(mut self, pool: &mut CVecPool) {
+        pool.release(&mut self.coinbase_tx);
+    }
 }
 
 /// Drops the CSubmitSolution object.
@@ -110,3 +185,94 @@ impl<'d> GetSize for SubmitSolution<'d> {
             + self.coinbase_tx.get_size()
     }
 }
+
+#[cfg(test)]
+#[cfg(not(feature = "with_serde"))]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_new_coinbase_at_limit() {
+        let coinbase = vec![0xab; MAX_COINBASE_TX_LEN];
+        let solution = SubmitSolution::new(1, 0x2000_0000, 1_700_000_000, 42, coinbase).unwrap();
+        assert_eq!(
+            solution.coinbase_tx.inner_as_ref().len(),
+            MAX_COINBASE_TX_LEN
+        );
+    }
+
+    #[test]
+    fn test_coinbase_matches() {
+        let prefix = [0x01, 0x02, 0x03];
+        let extranonce = [0xaa, 0xbb];
+        let suffix = [0x04, 0x05];
+        let coinbase = [&prefix[..], &extranonce[..], &suffix[..]].concat();
+        let solution = SubmitSolution::new(1, 0x2000_0000, 1_700_000_000, 42, coinbase).unwrap();
+        assert!(solution.coinbase_matches(&prefix, &extranonce, &suffix));
+    }
+
+    #[test]
+    fn test_coinbase_tampered() {
+        let prefix = [0x01, 0x02, 0x03];
+        let extranonce = [0xaa, 0xbb];
+        let suffix = [0x04, 0x05];
+        let tampered = vec![0x01, 0x02, 0x03, 0xaa, 0xbc, 0x04, 0x05];
+        let solution = SubmitSolution::new(1, 0x2000_0000, 1_700_000_000, 42, tampered).unwrap();
+        assert!(!solution.coinbase_matches(&prefix, &extranonce, &suffix));
+        let longer = vec![0x01, 0x02, 0x03, 0xaa, 0xbb, 0x00, 0x04, 0x05];
+        let solution = SubmitSolution::new(1, 0x2000_0000, 1_700_000_000, 42, longer).unwrap();
+        assert!(!solution.coinbase_matches(&prefix, &extranonce, &suffix));
+    }
+
+    #[test]
+    fn test_new_coinbase_over_limit() {
+        let coinbase = vec![0xab; MAX_COINBASE_TX_LEN + 1];
+        match SubmitSolution::new(1, 0x2000_0000, 1_700_000_000, 42, coinbase) {
+            Err(Error::InvalidB064KSize(len)) => assert_eq!(len, MAX_COINBASE_TX_LEN + 1),
+            other => panic!("expected InvalidB064KSize, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_version_rolled_in_mask() {
+        let job_version = 0x2000_0000;
+        let mask = 0x1fff_e000;
+        let solution = SubmitSolution::new(1, 0x2abc_e000, 2, 3, vec![]).unwrap();
+        assert!(!solution.has_illegal_version_bits(job_version, mask));
+        assert_eq!(solution.normalized_version(job_version, mask), 0x2abc_e000);
+    }
+
+    #[test]
+    fn test_version_reserved_bit_flipped() {
+        let job_version = 0x2000_0000;
+        let mask = 0x1fff_e000;
+        // bit 0 is outside the rolling region
+        let solution = SubmitSolution::new(1, 0x2abc_e001, 2, 3, vec![]).unwrap();
+        assert!(solution.has_illegal_version_bits(job_version, mask));
+        assert_eq!(solution.normalized_version(job_version, mask), 0x2abc_e000);
+    }
+}
+
+// Runs with and without the `with_serde` feature
+#[cfg(test)]
+mod codec_tests {
+    use super::*;
+    use alloc::vec;
+    use binary_sv2::Sv2Codec;
+    use core::convert::TryInto;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let solution = SubmitSolution {
+            template_id: 1,
+            version: 0x2000_0000,
+            header_timestamp: 1_700_000_000,
+            header_nonce: 42,
+            coinbase_tx: vec![0xab; 64].try_into().unwrap(),
+        };
+        let mut bytes = solution.encode().unwrap();
+        let decoded = SubmitSolution::decode(&mut bytes[..]).unwrap();
+        assert_eq!(decoded, solution);
+    }
+}
@@ -0,0 +1,138 @@
+//! Counts the allocations done by the C conversions with and without a `CVecPool`, and by the
+//! borrowing accessors of the messages.
+#![cfg(not(feature = "with_serde"))]
+
+use binary_sv2::{binary_codec_sv2::CVecPool, Seq064K, B016M, B064K};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    convert::TryInto,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+use template_distribution_sv2::{
+    CRequestTransactionDataSuccess, CSubmitSolution, RequestTransactionDataSuccess, SubmitSolution,
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ROUNDS: usize = 100;
+
+/// The counter is global: tests hold this lock so that they do not count each other allocations.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Allocations done by `f`.
+fn count_allocations(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    f();
+    ALLOCATIONS.load(Ordering::SeqCst) - before
+}
+
+/// Builds the messages borrowing the buffers, as when they are decoded from a frame.
+fn solution(coinbase: &mut [u8]) -> SubmitSolution<'_> {
+    SubmitSolution {
+        template_id: 1,
+        version: 0x2000_0000,
+        header_timestamp: 2,
+        header_nonce: 3,
+        coinbase_tx: coinbase.try_into().unwrap(),
+    }
+}
+
+fn tx_data<'a>(
+    excess_data: &'a mut [u8],
+    transactions: &'a mut [Vec<u8>],
+) -> RequestTransactionDataSuccess<'a> {
+    let transaction_list: Vec<B016M> = transactions
+        .iter_mut()
+        .map(|tx| tx.as_mut_slice().try_into().unwrap())
+        .collect();
+    let excess_data: B064K = excess_data.try_into().unwrap();
+    RequestTransactionDataSuccess {
+        template_id: 1,
+        excess_data,
+        transaction_list: Seq064K::new(transaction_list).unwrap(),
+    }
+}
+
+#[test]
+fn test_pooled_conversions_do_not_allocate() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut coinbase = vec![0xab_u8; 200];
+    let mut excess_data = vec![0xcd_u8; 32];
+    let mut transactions = vec![vec![0xef_u8; 250]; 10];
+
+    let mut unpooled = 0;
+    for _ in 0..ROUNDS {
+        let solution = solution(&mut coinbase);
+        let tx_data = tx_data(&mut excess_data, &mut transactions);
+        unpooled += count_allocations(|| {
+            drop(CSubmitSolution::from(solution));
+            drop(CRequestTransactionDataSuccess::from(tx_data));
+        });
+    }
+    // one coinbase, one excess data, one list and ten transactions per round
+    assert!(unpooled >= ROUNDS * 13);
+
+    let solution = solution(&mut coinbase);
+    let tx_data = tx_data(&mut excess_data, &mut transactions);
+    let mut pool = CVecPool::new(16);
+    // warm up: fill the pool and let the reused buffers grow to the biggest field
+    for _ in 0..3 {
+        CSubmitSolution::from_pooled(&solution, &mut pool).recycle(&mut pool);
+        CRequestTransactionDataSuccess::from_pooled(&tx_data, &mut pool).recycle(&mut pool);
+    }
+    let pooled = count_allocations(|| {
+        for _ in 0..ROUNDS {
+            CSubmitSolution::from_pooled(&solution, &mut pool).recycle(&mut pool);
+            CRequestTransactionDataSuccess::from_pooled(&tx_data, &mut pool).recycle(&mut pool);
+        }
+    });
+    assert_eq!(pooled, 0);
+}
+
+#[test]
+fn test_transaction_slices_do_not_allocate() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut excess_data = vec![0xcd_u8; 32];
+    let mut transactions: Vec<Vec<u8>> = (1..=10).map(|len| vec![len as u8; len * 100]).collect();
+    let expected = transactions.clone();
+    let tx_data = tx_data(&mut excess_data, &mut transactions);
+
+    let mut total_len = 0;
+    let mut count = 0;
+    let allocations = count_allocations(|| {
+        for tx in tx_data.transaction_slices() {
+            total_len += tx.len();
+            count += 1;
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert_eq!(count, 10);
+    assert_eq!(total_len, expected.iter().map(Vec::len).sum::<usize>());
+    assert!(tx_data
+        .transaction_slices()
+        .eq(expected.iter().map(Vec::as_slice)));
+}
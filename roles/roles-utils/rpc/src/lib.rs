@@ -1,11 +1,284 @@
 pub mod mini_rpc_client;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use stratum_common::bitcoin::hashes::{sha256d, Hash as _};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Hash([u8; 32]);
 
-#[derive(Clone, Deserialize)]
+/// Number of satoshis in one bitcoin.
+const SATS_PER_BTC: f64 = 100_000_000.0;
+
+/// Bitcoin amount as returned by the node RPC, in BTC.
+///
+/// Comparisons are done in satoshi space (see [`Amount::to_sat`]) so that amounts that differ only
+/// by floating point noise compare equal.
+#[derive(Debug, Clone)]
 pub struct Amount(f64);
 
+/// Reason why an [`Amount`] could not be built.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmountError {
+    /// The amount in BTC is NaN or infinite.
+    NotFinite(f64),
+    /// The amount in BTC is negative.
+    Negative(f64),
+    /// The string is not a number optionally followed by a `btc` or `sat` unit.
+    Invalid(String),
+}
+
+impl std::fmt::Display for AmountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AmountError::NotFinite(value) => write!(f, "invalid amount {}: must be finite", value),
+            AmountError::Negative(value) => {
+                write!(f, "invalid amount {}: must not be negative", value)
+            }
+            AmountError::Invalid(s) => write!(f, "invalid amount {:?}", s),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+impl<'de> Deserialize<'de> for Amount {
+    /// Only finite, non negative amounts are accepted.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = f64::deserialize(deserializer)?;
+        Amount::from_btc(value).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Amount {
+    /// Serialized as a float in BTC, like the node RPC does. See [`serde_sat`] for satoshis.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// (De)serializes an [`Amount`] as an integer number of satoshis instead of a float in BTC, for
+/// JSON schemas that expect them. Opt in per field with `#[serde(with = "rpc_sv2::serde_sat")]`.
+pub mod serde_sat {
+    use super::Amount;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        // constructors and deserializers reject negative amounts
+        serializer.serialize_u64(amount.to_sat() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        u64::deserialize(deserializer).map(Amount::from_sat)
+    }
+}
+
+impl Amount {
+    /// Builds an amount from a value in BTC, which must be finite and non negative.
+    pub fn from_btc(btc: f64) -> Result<Self, AmountError> {
+        if !btc.is_finite() {
+            return Err(AmountError::NotFinite(btc));
+        }
+        if btc < 0.0 {
+            return Err(AmountError::Negative(btc));
+        }
+        Ok(Amount(btc))
+    }
+
+    /// Builds an amount from a value in satoshis.
+    pub fn from_sat(sat: u64) -> Self {
+        Amount(sat as f64 / SATS_PER_BTC)
+    }
+
+    /// Parses amounts as written in config files and command lines: a number followed by an
+    /// optional `btc` or `sat` unit, case insensitive and possibly separated by whitespace, e.g.
+    /// `"0.5btc"`, `"50000 SAT"`. Amounts without unit are in BTC.
+    pub fn from_str_with_unit(s: &str) -> Result<Self, AmountError> {
+        let invalid = || AmountError::Invalid(s.to_string());
+        let lowercase = s.trim().to_ascii_lowercase();
+        if let Some(sat) = lowercase.strip_suffix("sat") {
+            let sat = sat.trim_end().parse::<u64>().map_err(|_| invalid())?;
+            Ok(Amount::from_sat(sat))
+        } else {
+            let btc = lowercase.strip_suffix("btc").unwrap_or(&lowercase);
+            let btc = btc.trim_end().parse::<f64>().map_err(|_| invalid())?;
+            Amount::from_btc(btc)
+        }
+    }
+
+    /// Returns the amount in satoshis, rounded to the nearest satoshi (half away from zero).
+    pub fn to_sat(&self) -> i64 {
+        (self.0 * SATS_PER_BTC).round() as i64
+    }
+
+    /// Total order in satoshi space, consistent with `==` and `<`, to use with `sort_by`.
+    ///
+    /// [`Amount::to_sat`] is always an integer, so this never depends on how floats order NaN (that
+    /// the constructors and the deserializer reject anyway).
+    pub fn cmp_total(&self, other: &Amount) -> std::cmp::Ordering {
+        self.to_sat().cmp(&other.to_sat())
+    }
+
+    /// Returns true if the two amounts differ by at most `tolerance_sat` satoshis.
+    pub fn approx_eq(&self, other: &Amount, tolerance_sat: u64) -> bool {
+        self.to_sat().abs_diff(other.to_sat()) <= tolerance_sat
+    }
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_sat() == other.to_sat()
+    }
+}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.to_sat().partial_cmp(&other.to_sat())
+    }
+}
+
+/// Hash of a block, in internal byte order.
+///
+/// The byte order is the one of [`sha256d::Hash`], so converting between the two is a plain copy.
+/// The usual hex representation of a block hash (as displayed by [`sha256d::Hash`]) is reversed.
 #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BlockHash(Hash);
+
+impl From<sha256d::Hash> for BlockHash {
+    fn from(hash: sha256d::Hash) -> Self {
+        BlockHash(Hash(hash.into_inner()))
+    }
+}
+
+impl From<BlockHash> for sha256d::Hash {
+    fn from(hash: BlockHash) -> Self {
+        sha256d::Hash::from_inner(hash.0 .0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amount_compares_in_sat_space() {
+        let sum = Amount(0.1 + 0.2);
+        let expected = Amount(0.3);
+        assert_ne!(0.1 + 0.2, 0.3);
+        assert_eq!(sum.to_sat(), 30_000_000);
+        assert!(sum == expected);
+        assert!(sum.approx_eq(&expected, 1));
+        assert!(Amount(0.3) < Amount(0.30000001));
+        assert!(!Amount(0.3).approx_eq(&Amount(0.30000002), 1));
+    }
+
+    #[test]
+    fn amount_sort_by_cmp_total() {
+        let mut amounts = [
+            Amount::from_sat(625_000_000),
+            Amount(0.1 + 0.2),
+            Amount::from_sat(0),
+            Amount::from_btc(0.00000001).unwrap(),
+            Amount(0.3),
+            Amount::from_str_with_unit("50000sat").unwrap(),
+        ];
+        amounts.sort_by(Amount::cmp_total);
+        let sats: Vec<i64> = amounts.iter().map(Amount::to_sat).collect();
+        assert_eq!(sats, [0, 1, 50_000, 30_000_000, 30_000_000, 625_000_000]);
+        assert_eq!(
+            Amount(0.1 + 0.2).cmp_total(&Amount(0.3)),
+            std::cmp::Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn amount_deserialize_rejects_invalid_values() {
+        use serde::de::{value::Error, IntoDeserializer};
+
+        let amount: Amount = serde_json::from_str("6.25").unwrap();
+        assert_eq!(amount.to_sat(), 625_000_000);
+
+        let err = serde_json::from_str::<Amount>("-1.5").err().unwrap();
+        assert!(err.to_string().contains("must not be negative"));
+
+        // JSON can't carry non finite numbers, other deserializers can
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let deserializer = IntoDeserializer::<Error>::into_deserializer(value);
+            assert!(Amount::deserialize(deserializer).is_err());
+        }
+        let deserializer = IntoDeserializer::<Error>::into_deserializer(0.0_f64);
+        assert_eq!(Amount::deserialize(deserializer).unwrap().to_sat(), 0);
+    }
+
+    #[test]
+    fn amount_serialize_btc_or_sat() {
+        #[derive(Serialize, Deserialize)]
+        struct Fees {
+            btc: Amount,
+            #[serde(with = "crate::serde_sat")]
+            sat: Amount,
+        }
+
+        let amount = Amount::from_sat(625_000_001);
+        let fees = Fees {
+            btc: amount.clone(),
+            sat: amount,
+        };
+        let json = serde_json::to_string(&fees).unwrap();
+        assert_eq!(json, r#"{"btc":6.25000001,"sat":625000001}"#);
+
+        let fees: Fees = serde_json::from_str(&json).unwrap();
+        assert_eq!(fees.btc.to_sat(), 625_000_001);
+        assert_eq!(fees.sat.to_sat(), 625_000_001);
+        assert!(serde_json::from_str::<Fees>(r#"{"btc":1.0,"sat":-1}"#).is_err());
+        assert!(serde_json::from_str::<Fees>(r#"{"btc":1.0,"sat":1.5}"#).is_err());
+    }
+
+    #[test]
+    fn amount_from_str_with_unit() {
+        assert_eq!(
+            Amount::from_str_with_unit("0.5btc").unwrap().to_sat(),
+            50_000_000
+        );
+        assert_eq!(
+            Amount::from_str_with_unit("50000sat").unwrap().to_sat(),
+            50_000
+        );
+        assert_eq!(
+            Amount::from_str_with_unit("0.5").unwrap().to_sat(),
+            50_000_000
+        );
+        assert_eq!(
+            Amount::from_str_with_unit(" 2 BTC ").unwrap().to_sat(),
+            200_000_000
+        );
+        assert_eq!(Amount::from_str_with_unit("1 Sat").unwrap().to_sat(), 1);
+
+        assert_eq!(
+            Amount::from_str_with_unit("abc"),
+            Err(AmountError::Invalid("abc".to_string()))
+        );
+        assert_eq!(
+            Amount::from_str_with_unit("-1btc"),
+            Err(AmountError::Negative(-1.0))
+        );
+        // fractions of a satoshi
+        assert!(Amount::from_str_with_unit("0.5sat").is_err());
+    }
+
+    #[test]
+    fn block_hash_sha256d_round_trip() {
+        use std::str::FromStr;
+
+        // genesis block hash, in display order
+        let display = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+        let hash = sha256d::Hash::from_str(display).unwrap();
+
+        let block_hash = BlockHash::from(hash);
+        let mut internal = hex::decode(display).unwrap();
+        internal.reverse();
+        assert_eq!(&block_hash.0 .0[..], &internal[..]);
+
+        let back = sha256d::Hash::from(block_hash);
+        assert_eq!(back, hash);
+        assert_eq!(back.to_string(), display);
+    }
+}
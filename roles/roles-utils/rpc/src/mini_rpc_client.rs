@@ -14,7 +14,12 @@ use hyper_util::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use stratum_common::bitcoin::{consensus::encode::deserialize as consensus_decode, Transaction};
+use std::{collections::HashMap, str::FromStr};
+use stratum_common::bitcoin::{
+    consensus::encode::{deserialize as consensus_decode, serialize as consensus_encode},
+    hashes::sha256d,
+    Transaction,
+};
 
 use super::BlockHash;
 
@@ -76,6 +81,40 @@ impl MiniRpcClient {
         }
     }
 
+    /// Returns the hash and the height of the tip of the best chain.
+    ///
+    /// Both come from the same `getblockchaininfo` call, so they always refer to the same block.
+    pub async fn get_best_block(&self) -> Result<(BlockHash, u64), RpcError> {
+        let response = self
+            .send_json_rpc_request("getblockchaininfo", json!([]))
+            .await?;
+        parse_best_block(&response)
+    }
+
+    /// Returns the serialized transactions `txids`, in the same order.
+    ///
+    /// The transactions are taken from a single `getblocktemplate` call, only the ones that are
+    /// not in the template are fetched one by one with `getrawtransaction`. This saves a round
+    /// trip per transaction and, since the template is a snapshot, avoids failing on transactions
+    /// evicted from the mempool while they are fetched.
+    pub async fn template_transactions(&self, txids: &[String]) -> Result<Vec<Vec<u8>>, RpcError> {
+        let template = self
+            .send_json_rpc_request("getblocktemplate", json!([{"rules": ["segwit"]}]))
+            .await?;
+        let from_template = transactions_from_template(&template, txids)?;
+        let mut transactions = Vec::with_capacity(txids.len());
+        for (txid, transaction) in txids.iter().zip(from_template) {
+            match transaction {
+                Some(transaction) => transactions.push(transaction),
+                None => {
+                    let transaction = self.get_raw_transaction(txid, None).await?;
+                    transactions.push(consensus_encode(&transaction));
+                }
+            }
+        }
+        Ok(transactions)
+    }
+
     pub async fn submit_block(&self, block_hex: String) -> Result<(), RpcError> {
         let response = self
             .send_json_rpc_request("submitblock", json!([block_hex]))
@@ -149,6 +188,64 @@ impl MiniRpcClient {
     }
 }
 
+/// The fields of the `getblockchaininfo` result used by [`MiniRpcClient::get_best_block`].
+#[derive(Debug, Deserialize)]
+struct BlockchainInfo {
+    bestblockhash: String,
+    blocks: u64,
+}
+
+fn parse_best_block(response: &str) -> Result<(BlockHash, u64), RpcError> {
+    let response: JsonRpcResult<BlockchainInfo> =
+        serde_json::from_str(response).map_err(|e| RpcError::Deserialization(e.to_string()))?;
+    let info = response
+        .result
+        .ok_or_else(|| RpcError::Other("Result not found".to_string()))?;
+    // the node returns the hash in display order, `sha256d::Hash` parses it as such
+    let hash = sha256d::Hash::from_str(&info.bestblockhash)
+        .map_err(|e| RpcError::Deserialization(e.to_string()))?;
+    Ok((hash.into(), info.blocks))
+}
+
+/// The fields of the `getblocktemplate` result used by [`MiniRpcClient::template_transactions`].
+#[derive(Debug, Deserialize)]
+struct BlockTemplate {
+    transactions: Vec<TemplateTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TemplateTransaction {
+    txid: String,
+    data: String,
+}
+
+/// Returns, for each of `txids`, the transaction data included in the `getblocktemplate`
+/// `response`, or `None` if the template does not include it.
+fn transactions_from_template(
+    response: &str,
+    txids: &[String],
+) -> Result<Vec<Option<Vec<u8>>>, RpcError> {
+    let response: JsonRpcResult<BlockTemplate> =
+        serde_json::from_str(response).map_err(|e| RpcError::Deserialization(e.to_string()))?;
+    let template = response
+        .result
+        .ok_or_else(|| RpcError::Other("Result not found".to_string()))?;
+    let mut by_txid: HashMap<String, String> = template
+        .transactions
+        .into_iter()
+        .map(|transaction| (transaction.txid, transaction.data))
+        .collect();
+    txids
+        .iter()
+        .map(|txid| {
+            by_txid
+                .remove(txid)
+                .map(|data| decode(data).map_err(|e| RpcError::Deserialization(e.to_string())))
+                .transpose()
+        })
+        .collect()
+}
+
 #[derive(Clone, Debug)]
 pub struct Auth {
     username: String,
@@ -201,3 +298,140 @@ impl From<JsonRpcResult<JsonRpcError>> for RpcError {
         Self::JsonRpc(error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{body::Incoming, server::conn::http1, service::service_fn, Response};
+    use hyper_util::rt::TokioIo;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// Starts a node answering every JSON-RPC request with the canned result of its method,
+    /// returns its url and the (method, params) of the requests it received.
+    async fn mock_node(
+        results: HashMap<&'static str, serde_json::Value>,
+    ) -> (String, Arc<Mutex<Vec<(String, serde_json::Value)>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let calls = Arc::new(Mutex::new(vec![]));
+        let received = calls.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let results = results.clone();
+                let received = received.clone();
+                let service = service_fn(move |request: Request<Incoming>| {
+                    let results = results.clone();
+                    let received = received.clone();
+                    async move {
+                        let body = request.into_body().collect().await?.to_bytes();
+                        let request: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                        let method = request["method"].as_str().unwrap().to_string();
+                        let response = json!({
+                            "result": results[method.as_str()],
+                            "error": null,
+                            "id": 1
+                        });
+                        received
+                            .lock()
+                            .unwrap()
+                            .push((method, request["params"].clone()));
+                        Ok::<_, hyper::Error>(Response::new(Full::<Bytes>::from(
+                            response.to_string(),
+                        )))
+                    }
+                });
+                tokio::spawn(async move {
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (url, calls)
+    }
+
+    #[test]
+    fn parse_best_block_from_blockchain_info() {
+        let response = r#"{"result":{"chain":"main","blocks":100000,"headers":100000,"bestblockhash":"000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506","difficulty":14484.1623612254,"mediantime":1293622620,"verificationprogress":1,"initialblockdownload":false,"pruned":false,"warnings":""},"error":null,"id":1}"#;
+        let (hash, height) = parse_best_block(response).unwrap();
+        assert_eq!(height, 100_000);
+        assert_eq!(
+            sha256d::Hash::from(hash).to_string(),
+            "000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506"
+        );
+
+        let error =
+            r#"{"result":null,"error":{"code":-28,"message":"Loading block index..."},"id":1}"#;
+        assert!(matches!(parse_best_block(error), Err(RpcError::Other(_))));
+    }
+
+    fn template_response() -> String {
+        json!({
+            "result": {
+                "version": 536870912,
+                "previousblockhash": "000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506",
+                "transactions": [
+                    {"data": "0100", "txid": "aa".repeat(32), "hash": "aa".repeat(32), "fee": 1000},
+                    {"data": "0200", "txid": "bb".repeat(32), "hash": "cc".repeat(32), "fee": 2000}
+                ],
+                "height": 100001
+            },
+            "error": null,
+            "id": 1
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn template_transactions_all_in_template() {
+        let txids = ["bb".repeat(32), "aa".repeat(32)];
+        let transactions = transactions_from_template(&template_response(), &txids).unwrap();
+        assert_eq!(
+            transactions,
+            vec![Some(vec![0x02, 0x00]), Some(vec![0x01, 0x00])]
+        );
+    }
+
+    #[test]
+    fn template_transactions_one_to_fetch() {
+        // the second one is not in the template, e.g. it entered the mempool after the template
+        let txids = ["aa".repeat(32), "dd".repeat(32)];
+        let transactions = transactions_from_template(&template_response(), &txids).unwrap();
+        assert_eq!(transactions, vec![Some(vec![0x01, 0x00]), None]);
+    }
+
+    #[tokio::test]
+    async fn template_transactions_fetches_missing_with_getrawtransaction() {
+        // a legacy transaction with one input and one output
+        let missing = format!(
+            "0100000001{}ffffffff00ffffffff01{}0000000000",
+            "00".repeat(32),
+            "00".repeat(8)
+        );
+        let template: serde_json::Value = serde_json::from_str(&template_response()).unwrap();
+        let results = [
+            ("getblocktemplate", template["result"].clone()),
+            ("getrawtransaction", json!(missing)),
+        ]
+        .into_iter()
+        .collect();
+        let (url, calls) = mock_node(results).await;
+        let client = MiniRpcClient::new(url, Auth::new("user".into(), "pass".into()));
+
+        let txids = ["aa".repeat(32), "dd".repeat(32)];
+        let transactions = client.template_transactions(&txids).await.unwrap();
+        assert_eq!(
+            transactions,
+            vec![vec![0x01, 0x00], decode(&missing).unwrap()]
+        );
+
+        // only the transaction missing from the template is fetched on its own
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].0, "getblocktemplate");
+        assert_eq!(calls[1].0, "getrawtransaction");
+        assert_eq!(calls[1].1, json!(["dd".repeat(32), false]));
+    }
+}
@@ -1,5 +1,12 @@
-use roles_logic_sv2::mining_sv2::Target;
-use v1::{client_to_server::Submit, utils::HexU32Be};
+use roles_logic_sv2::{
+    mining_sv2::{SubmitSharesError, Target},
+    share_validator::SubmitShareRejectReason,
+};
+use v1::{
+    client_to_server::Submit,
+    json_rpc::{JsonRpcError, Response},
+    utils::HexU32Be,
+};
 pub mod diff_management;
 pub mod downstream;
 pub use downstream::Downstream;
@@ -49,3 +56,103 @@ pub async fn kill(sender: &async_channel::Sender<bool>) {
 pub fn new_subscription_id() -> String {
     "ae6812eb4cd7735a302a8a9dd95cf71f".into()
 }
+
+/// Translates the SV2 `SubmitSharesError` received for a share into the response to the SV1
+/// `mining.submit` with id `id`.
+///
+/// The response has a `false` result and the error given by
+/// [`JsonRpcError::from_sv2_error_code`]. Send it to the miner as [`Response::to_sv1_value`], that
+/// has the error in the SV1 `[code, message, data]` array form.
+pub fn sv1_reject_from_sv2(id: u64, err: &SubmitSharesError) -> Response {
+    let reason = String::from_utf8_lossy(err.error_code.as_ref());
+    Response {
+        id,
+        result: false.into(),
+        error: Some(JsonRpcError::from_sv2_error_code(&reason)),
+    }
+}
+
+/// Builds the SV1 error for a share rejected by the proxy itself.
+pub trait FromSv2ShareReject {
+    fn from_sv2_share_reject(reason: &SubmitShareRejectReason) -> Self;
+}
+
+impl FromSv2ShareReject for JsonRpcError {
+    fn from_sv2_share_reject(reason: &SubmitShareRejectReason) -> Self {
+        JsonRpcError::from_sv2_error_code(reason.error_code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Returns the `error` field of the SV1 response to the rejected share.
+    fn reject(error_code: &str) -> serde_json::Value {
+        let err = SubmitSharesError {
+            channel_id: 1,
+            sequence_number: 2,
+            error_code: error_code.to_string().into_bytes().try_into().unwrap(),
+        };
+        let mut response = sv1_reject_from_sv2(7, &err).to_sv1_value();
+        assert_eq!(response["id"], json!(7));
+        assert_eq!(response["result"], json!(false));
+        response["error"].take()
+    }
+
+    #[test]
+    fn stale_share_reject() {
+        assert_eq!(reject("stale-share"), json!([21, "Job not found", null]));
+    }
+
+    #[test]
+    fn invalid_job_id_reject() {
+        assert_eq!(reject("invalid-job-id"), json!([21, "Job not found", null]));
+    }
+
+    #[test]
+    fn difficulty_too_low_reject() {
+        assert_eq!(
+            reject("difficulty-too-low"),
+            json!([23, "Low difficulty share", null])
+        );
+    }
+
+    #[test]
+    fn invalid_channel_id_reject() {
+        assert_eq!(
+            reject("invalid-channel-id"),
+            json!([25, "Not subscribed", null])
+        );
+    }
+
+    #[test]
+    fn unknown_reject() {
+        assert_eq!(
+            reject("duplicate-share"),
+            json!([20, "Other/Unknown: duplicate-share", null])
+        );
+    }
+
+    #[test]
+    fn share_reject_reason_codes() {
+        for (reason, code, message) in [
+            (SubmitShareRejectReason::StaleShare, 21, "Job not found"),
+            (SubmitShareRejectReason::InvalidJobId, 21, "Job not found"),
+            (
+                SubmitShareRejectReason::DifficultyTooLow,
+                23,
+                "Low difficulty share",
+            ),
+            (
+                SubmitShareRejectReason::InvalidChannelId,
+                25,
+                "Not subscribed",
+            ),
+        ] {
+            let error = JsonRpcError::from_sv2_share_reject(&reason);
+            assert_eq!((error.code, error.message.as_str()), (code, message));
+        }
+    }
+}